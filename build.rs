@@ -0,0 +1,34 @@
+fn main() {
+    // Linker setup for loading the module into node (undefined symbols on macOS)
+    #[cfg(feature = "node")]
+    napi_build::setup();
+
+    #[cfg(feature = "capi")]
+    generate_c_header();
+}
+
+/// Generate the C header from src/capi.rs into OUT_DIR, configured by cbindgen.toml.
+/// A build script must not write to the source tree, so the shipped copy in
+/// include/ is only updated when RTLSDR_RS_UPDATE_HEADER is set; the capi tests fail
+/// while it is stale.
+#[cfg(feature = "capi")]
+fn generate_c_header() {
+    println!("cargo:rerun-if-changed=src/capi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=RTLSDR_RS_UPDATE_HEADER");
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let header = format!("{}/rtlsdr_rs.h", out_dir);
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("Invalid cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_src(format!("{}/src/capi.rs", crate_dir))
+        .with_config(config)
+        .generate()
+        .expect("Unable to generate C header")
+        .write_to_file(&header);
+    if std::env::var_os("RTLSDR_RS_UPDATE_HEADER").is_some() {
+        std::fs::copy(&header, format!("{}/include/rtlsdr_rs.h", crate_dir))
+            .expect("Unable to update include/rtlsdr_rs.h");
+    }
+}
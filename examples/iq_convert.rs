@@ -0,0 +1,155 @@
+use log::info;
+use rtlsdr_rs::convert::Endianness;
+use rtlsdr_rs::error::Result;
+use rtlsdr_rs::record::{convert_file, ConvertOptions, IqFormat};
+use sdre_rust_logging::SetupLogging;
+use std::{path::PathBuf, process::exit};
+
+#[derive(Debug)]
+enum ArgParseError {
+    InvalidLogLevel(String),
+    UnknownArg(String),
+    BadFormat(String),
+    BadValue(String),
+    MissingFile(String),
+}
+
+#[derive(Debug)]
+struct Args {
+    log_level: String,
+    input: PathBuf,
+    output: PathBuf,
+    options: ConvertOptions,
+}
+
+fn parse_format(name: &str) -> Option<(IqFormat, Option<Endianness>)> {
+    match name {
+        "cu8" => Some((IqFormat::Cu8, None)),
+        "cf32" => Some((IqFormat::Cf32, None)),
+        "cs16" => Some((IqFormat::Cs16, None)),
+        "cs16le" => Some((IqFormat::Cs16, Some(Endianness::Little))),
+        "cs16be" => Some((IqFormat::Cs16, Some(Endianness::Big))),
+        "wav" => Some((IqFormat::Wav, None)),
+        _ => None,
+    }
+}
+
+impl Args {
+    fn try_parse<It: Iterator<Item = String>>(
+        mut arg_it: It,
+    ) -> std::result::Result<Args, ArgParseError> {
+        // Skip program name
+        let _ = arg_it.next();
+
+        let mut log_level = "info".to_string();
+        let mut options = ConvertOptions::default();
+        let mut files = Vec::new();
+
+        while let Some(arg) = arg_it.next() {
+            match arg.as_str() {
+                "--loglevel" | "-l" => {
+                    log_level = arg_it
+                        .next()
+                        .ok_or(ArgParseError::InvalidLogLevel(arg.clone()))?;
+                }
+                "--from" | "-F" | "--to" | "-T" => {
+                    let (format, endianness) = arg_it
+                        .next()
+                        .and_then(|name| parse_format(&name))
+                        .ok_or(ArgParseError::BadFormat(arg.clone()))?;
+                    if arg == "--from" || arg == "-F" {
+                        options.input_format = Some(format);
+                        options.input_endianness = endianness;
+                    } else {
+                        options.output_format = Some(format);
+                        options.output_endianness = endianness;
+                    }
+                }
+                "--sample-rate" | "-s" => {
+                    options.sample_rate = Some(
+                        arg_it
+                            .next()
+                            .ok_or(ArgParseError::BadValue(arg.clone()))?
+                            .parse()
+                            .map_err(|_| ArgParseError::BadValue(arg.clone()))?,
+                    );
+                }
+                "--frequency" | "-f" => {
+                    options.center_freq = Some(
+                        arg_it
+                            .next()
+                            .ok_or(ArgParseError::BadValue(arg.clone()))?
+                            .parse()
+                            .map_err(|_| ArgParseError::BadValue(arg.clone()))?,
+                    );
+                }
+                "--help" | "-h" => {
+                    println!("{}", Args::help());
+                    exit(0);
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(ArgParseError::UnknownArg(arg));
+                }
+                _ => files.push(PathBuf::from(arg)),
+            }
+        }
+
+        let mut files = files.into_iter();
+        let input = files
+            .next()
+            .ok_or(ArgParseError::MissingFile("input".to_string()))?;
+        let output = files
+            .next()
+            .ok_or(ArgParseError::MissingFile("output".to_string()))?;
+        if let Some(extra) = files.next() {
+            return Err(ArgParseError::UnknownArg(extra.display().to_string()));
+        }
+
+        Ok(Args {
+            log_level,
+            input,
+            output,
+            options,
+        })
+    }
+
+    fn parse<It: Iterator<Item = String>>(arg_it: It) -> Args {
+        match Self::try_parse(arg_it) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Argument parsing failed: {e:?}");
+                println!("{}", Args::help());
+                exit(1);
+            }
+        }
+    }
+
+    fn help() -> String {
+        format!(
+            "Usage: {} [options] <input> <output>
+Formats are taken from the file extensions (cu8/bin/raw, cf32/cfile, cs16, wav) unless given.
+| Short Option | Long Option   | Description                                                  |
+|--------------|---------------|--------------------------------------------------------------|
+| -F           | --from        | Input format: cu8, cf32, cs16, cs16le, cs16be or wav.        |
+| -T           | --to          | Output format: cu8, cf32, cs16, cs16le, cs16be or wav.       |
+| -s           | --sample-rate | Sample rate in hertz, overriding the input's metadata.       |
+| -f           | --frequency   | Center frequency in hertz, overriding the input's metadata.  |
+| -l           | --loglevel    | The log level to use. Default is 'info'.                     |
+| -h           | --help        | Display this help message.                                   |",
+            env!("CARGO_PKG_NAME")
+        )
+    }
+}
+
+fn main() -> Result<()> {
+    "info".enable_logging();
+    let args: Args = Args::parse(std::env::args());
+    args.log_level.enable_logging();
+
+    let summary = convert_file(&args.input, &args.output, &args.options)?;
+    info!(
+        "{} samples, sample rate {:?}, center frequency {:?}",
+        summary.samples, summary.sample_rate, summary.center_freq
+    );
+    Ok(())
+}
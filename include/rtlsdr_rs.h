@@ -0,0 +1,107 @@
+#ifndef RTLSDR_RS_H
+#define RTLSDR_RS_H
+
+/* Generated by cbindgen from src/capi.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Opaque device handle
+ */
+typedef struct RtlSdrDev RtlSdrDev;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Description of the last failure on this thread, or NULL. Valid until the next call
+ * into this library on the same thread.
+ */
+const char *rtlsdr_rs_last_error(void);
+
+/**
+ * `build_info` as a JSON object. The string is static and must not be freed.
+ */
+const char *rtlsdr_rs_build_info(void);
+
+int rtlsdr_rs_open(uint32_t index, struct RtlSdrDev **out);
+
+int rtlsdr_rs_open_by_serial(const char *serial, struct RtlSdrDev **out);
+
+/**
+ * Shut down and free the device. The handle must not be used afterwards.
+ */
+int rtlsdr_rs_close(struct RtlSdrDev *dev);
+
+int rtlsdr_rs_set_center_freq(struct RtlSdrDev *dev, uint32_t freq);
+
+/**
+ * Center frequency in Hz, 0 for a null handle
+ */
+uint32_t rtlsdr_rs_get_center_freq(struct RtlSdrDev *dev);
+
+int rtlsdr_rs_set_sample_rate(struct RtlSdrDev *dev, uint32_t rate);
+
+/**
+ * Sample rate in Hz, 0 for a null handle
+ */
+uint32_t rtlsdr_rs_get_sample_rate(struct RtlSdrDev *dev);
+
+/**
+ * Manual gain in tenths of a dB
+ */
+int rtlsdr_rs_set_tuner_gain(struct RtlSdrDev *dev, int gain);
+
+int rtlsdr_rs_set_tuner_gain_auto(struct RtlSdrDev *dev);
+
+int rtlsdr_rs_set_freq_correction(struct RtlSdrDev *dev, int ppm);
+
+/**
+ * Actual crystal frequencies in Hz. 0 keeps the RTL2832's current value, or for
+ * the tuner, uses the RTL2832's clock.
+ */
+int rtlsdr_rs_set_xtal_freq(struct RtlSdrDev *dev, uint32_t rtl_freq, uint32_t tuner_freq);
+
+/**
+ * Crystal frequencies in Hz with the frequency correction applied. Either pointer
+ * may be null.
+ */
+int rtlsdr_rs_get_xtal_freq(struct RtlSdrDev *dev, uint32_t *rtl_freq, uint32_t *tuner_freq);
+
+int rtlsdr_rs_set_bias_tee(struct RtlSdrDev *dev, int on);
+
+/**
+ * Bias tee on another GPIO than the usual 0
+ */
+int rtlsdr_rs_set_bias_tee_gpio(struct RtlSdrDev *dev, uint8_t gpio, int on);
+
+/**
+ * 1 if the bias tee on GPIO 0 is on, 0 if off, -1 on error
+ */
+int rtlsdr_rs_get_bias_tee(struct RtlSdrDev *dev);
+
+int rtlsdr_rs_reset_buffer(struct RtlSdrDev *dev);
+
+/**
+ * Read raw interleaved u8 I/Q into `buf`. `len` should be a multiple of 512.
+ * The number of bytes read is stored in `n_read`.
+ */
+int rtlsdr_rs_read_sync(struct RtlSdrDev *dev, uint8_t *buf, size_t len, size_t *n_read);
+
+/**
+ * Read up to `samples` complex samples as interleaved f32 I/Q (GNU Radio `gr_complex`)
+ * into `out`, which must hold `2 * samples` floats. The number of complex samples
+ * written is stored in `n_read`.
+ */
+int rtlsdr_rs_read_cf32(struct RtlSdrDev *dev, float *out, size_t samples, size_t *n_read);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RTLSDR_RS_H */
@@ -0,0 +1,16 @@
+'use strict';
+// Loads the module built with `cargo build --release --features node`, copied to
+// rtlsdr_rs.node next to this file (or pointed to by RTLSDR_RS_NODE).
+const path = require('path');
+
+const native = require(process.env.RTLSDR_RS_NODE || path.join(__dirname, 'rtlsdr_rs.node'));
+
+// Async iterator of raw u8 I/Q buffers of `numBytes` each. Ends when the loop is
+// exited; read errors are thrown from the loop.
+native.RtlSdr.prototype.stream = async function* stream(numBytes) {
+  for (;;) {
+    yield await this.read(numBytes);
+  }
+};
+
+module.exports = native;
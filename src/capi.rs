@@ -0,0 +1,466 @@
+//! C-compatible interface for using the driver from C, C++ and other languages with a
+//! C FFI (e.g. a GNU Radio source block). Enabled with the `capi` feature; build the
+//! shared library with `cargo rustc --release --features capi --crate-type cdylib`.
+//!
+//! Functions returning `c_int` return 0 on success and -1 on failure, with a description
+//! of the failure available from `rtlsdr_rs_last_error` on the same thread.
+//!
+//! Safety: pointer arguments must be NULL or valid for the access described; device
+//! handles must come from `rtlsdr_rs_open*` and not be used after `rtlsdr_rs_close`.
+//! A handle must not be used from two threads at once.
+#![allow(clippy::missing_safety_doc)]
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+use std::sync::OnceLock;
+
+use crate::convert::Converter;
+use crate::error::Result;
+use crate::{RtlSdr, TunerGain};
+
+/// Opaque device handle
+pub struct RtlSdrDev {
+    sdr: RtlSdr,
+    raw: Vec<u8>,
+    converted: Vec<f32>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(msg: String) {
+    let msg = CString::new(msg.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+}
+
+fn status(result: Result<()>) -> c_int {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            set_error(e.to_string());
+            -1
+        }
+    }
+}
+
+unsafe fn dev<'a>(dev: *mut RtlSdrDev) -> Option<&'a mut RtlSdrDev> {
+    let dev = dev.as_mut();
+    if dev.is_none() {
+        set_error("Null device handle".to_string());
+    }
+    dev
+}
+
+fn open_with(result: Result<RtlSdr>, out: *mut *mut RtlSdrDev) -> c_int {
+    if out.is_null() {
+        set_error("Null output pointer".to_string());
+        return -1;
+    }
+    match result {
+        Ok(sdr) => {
+            let dev = Box::new(RtlSdrDev {
+                sdr,
+                raw: Vec::new(),
+                converted: Vec::new(),
+            });
+            unsafe { *out = Box::into_raw(dev) };
+            0
+        }
+        Err(e) => {
+            set_error(e.to_string());
+            unsafe { *out = ptr::null_mut() };
+            -1
+        }
+    }
+}
+
+/// Description of the last failure on this thread, or NULL. Valid until the next call
+/// into this library on the same thread.
+#[no_mangle]
+pub extern "C" fn rtlsdr_rs_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| match e.borrow().as_ref() {
+        Some(msg) => msg.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// `build_info` as a JSON object. The string is static and must not be freed.
+#[no_mangle]
+pub extern "C" fn rtlsdr_rs_build_info() -> *const c_char {
+    static INFO: OnceLock<CString> = OnceLock::new();
+    INFO.get_or_init(|| CString::new(crate::build_info().to_json()).unwrap_or_default())
+        .as_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_open(index: u32, out: *mut *mut RtlSdrDev) -> c_int {
+    open_with(RtlSdr::open_by_index(index as usize), out)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_open_by_serial(
+    serial: *const c_char,
+    out: *mut *mut RtlSdrDev,
+) -> c_int {
+    if serial.is_null() {
+        set_error("Null serial".to_string());
+        return -1;
+    }
+    let serial = CStr::from_ptr(serial).to_string_lossy();
+    open_with(RtlSdr::open_by_serial(&serial), out)
+}
+
+/// Shut down and free the device. The handle must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_close(dev: *mut RtlSdrDev) -> c_int {
+    if dev.is_null() {
+        return 0;
+    }
+    let dev = Box::from_raw(dev);
+    status(dev.sdr.close())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_set_center_freq(dev: *mut RtlSdrDev, freq: u32) -> c_int {
+    match self::dev(dev) {
+        Some(dev) => status(dev.sdr.set_center_freq(freq)),
+        None => -1,
+    }
+}
+
+/// Center frequency in Hz, 0 for a null handle
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_get_center_freq(dev: *mut RtlSdrDev) -> u32 {
+    self::dev(dev).map_or(0, |dev| dev.sdr.get_center_freq())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_set_sample_rate(dev: *mut RtlSdrDev, rate: u32) -> c_int {
+    match self::dev(dev) {
+        Some(dev) => status(dev.sdr.set_sample_rate(rate).map(|_| ())),
+        None => -1,
+    }
+}
+
+/// Sample rate in Hz, 0 for a null handle
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_get_sample_rate(dev: *mut RtlSdrDev) -> u32 {
+    self::dev(dev).map_or(0, |dev| dev.sdr.get_sample_rate())
+}
+
+/// Manual gain in tenths of a dB
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_set_tuner_gain(dev: *mut RtlSdrDev, gain: c_int) -> c_int {
+    match self::dev(dev) {
+        Some(dev) => status(dev.sdr.set_tuner_gain(TunerGain::Manual(gain))),
+        None => -1,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_set_tuner_gain_auto(dev: *mut RtlSdrDev) -> c_int {
+    match self::dev(dev) {
+        Some(dev) => status(dev.sdr.set_tuner_gain(TunerGain::Auto)),
+        None => -1,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_set_freq_correction(dev: *mut RtlSdrDev, ppm: c_int) -> c_int {
+    match self::dev(dev) {
+        Some(dev) => status(dev.sdr.set_freq_correction(ppm)),
+        None => -1,
+    }
+}
+
+/// Actual crystal frequencies in Hz. 0 keeps the RTL2832's current value, or for
+/// the tuner, uses the RTL2832's clock.
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_set_xtal_freq(
+    dev: *mut RtlSdrDev,
+    rtl_freq: u32,
+    tuner_freq: u32,
+) -> c_int {
+    match self::dev(dev) {
+        Some(dev) => status(dev.sdr.set_xtal_freq(rtl_freq, tuner_freq)),
+        None => -1,
+    }
+}
+
+/// Crystal frequencies in Hz with the frequency correction applied. Either pointer
+/// may be null.
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_get_xtal_freq(
+    dev: *mut RtlSdrDev,
+    rtl_freq: *mut u32,
+    tuner_freq: *mut u32,
+) -> c_int {
+    let Some(dev) = self::dev(dev) else {
+        return -1;
+    };
+    let (rtl, tuner) = dev.sdr.get_xtal_freq();
+    if !rtl_freq.is_null() {
+        *rtl_freq = rtl;
+    }
+    if !tuner_freq.is_null() {
+        *tuner_freq = tuner;
+    }
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_set_bias_tee(dev: *mut RtlSdrDev, on: c_int) -> c_int {
+    match self::dev(dev) {
+        Some(dev) => status(dev.sdr.set_bias_tee(on != 0)),
+        None => -1,
+    }
+}
+
+/// Bias tee on another GPIO than the usual 0
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_set_bias_tee_gpio(
+    dev: *mut RtlSdrDev,
+    gpio: u8,
+    on: c_int,
+) -> c_int {
+    match self::dev(dev) {
+        Some(dev) => status(dev.sdr.set_bias_tee_gpio(gpio, on != 0)),
+        None => -1,
+    }
+}
+
+/// 1 if the bias tee on GPIO 0 is on, 0 if off, -1 on error
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_get_bias_tee(dev: *mut RtlSdrDev) -> c_int {
+    let Some(dev) = self::dev(dev) else {
+        return -1;
+    };
+    match dev.sdr.get_bias_tee() {
+        Ok(on) => on as c_int,
+        Err(e) => {
+            set_error(e.to_string());
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_reset_buffer(dev: *mut RtlSdrDev) -> c_int {
+    match self::dev(dev) {
+        Some(dev) => status(dev.sdr.reset_buffer()),
+        None => -1,
+    }
+}
+
+/// Read raw interleaved u8 I/Q into `buf`. `len` should be a multiple of 512.
+/// The number of bytes read is stored in `n_read`.
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_read_sync(
+    dev: *mut RtlSdrDev,
+    buf: *mut u8,
+    len: usize,
+    n_read: *mut usize,
+) -> c_int {
+    let Some(dev) = self::dev(dev) else {
+        return -1;
+    };
+    if buf.is_null() || n_read.is_null() {
+        set_error("Null buffer".to_string());
+        return -1;
+    }
+    let buf = std::slice::from_raw_parts_mut(buf, len);
+    match dev.sdr.read_sync(buf) {
+        Ok(len) => {
+            *n_read = len;
+            0
+        }
+        Err(e) => {
+            set_error(e.to_string());
+            -1
+        }
+    }
+}
+
+/// Read up to `samples` complex samples as interleaved f32 I/Q (GNU Radio `gr_complex`)
+/// into `out`, which must hold `2 * samples` floats. The number of complex samples
+/// written is stored in `n_read`.
+#[no_mangle]
+pub unsafe extern "C" fn rtlsdr_rs_read_cf32(
+    dev: *mut RtlSdrDev,
+    out: *mut f32,
+    samples: usize,
+    n_read: *mut usize,
+) -> c_int {
+    let Some(dev) = self::dev(dev) else {
+        return -1;
+    };
+    if out.is_null() || n_read.is_null() {
+        set_error("Null buffer".to_string());
+        return -1;
+    }
+    // Whole USB packets only; the remainder of the request is left for the next call
+    let len = (samples * 2) / 512 * 512;
+    if len == 0 {
+        *n_read = 0;
+        return 0;
+    }
+    dev.raw.resize(len, 0);
+    let len = match dev.sdr.read_sync(&mut dev.raw) {
+        Ok(len) => len,
+        Err(e) => {
+            set_error(e.to_string());
+            return -1;
+        }
+    };
+    dev.converted.clear();
+    Converter::default().to_f32(&dev.raw[..len], &mut dev.converted);
+    let out = std::slice::from_raw_parts_mut(out, dev.converted.len());
+    out.copy_from_slice(&dev.converted);
+    *n_read = len / 2;
+    0
+}
+
+// ABI check: these are the signatures published in include/rtlsdr_rs.h, so an
+// incompatible change fails to compile instead of breaking C callers at runtime.
+type DevFn<A> = unsafe extern "C" fn(*mut RtlSdrDev, A) -> c_int;
+const _: () = {
+    let _: extern "C" fn() -> *const c_char = rtlsdr_rs_last_error;
+    let _: extern "C" fn() -> *const c_char = rtlsdr_rs_build_info;
+    let _: unsafe extern "C" fn(u32, *mut *mut RtlSdrDev) -> c_int = rtlsdr_rs_open;
+    let _: unsafe extern "C" fn(*const c_char, *mut *mut RtlSdrDev) -> c_int =
+        rtlsdr_rs_open_by_serial;
+    let _: unsafe extern "C" fn(*mut RtlSdrDev) -> c_int = rtlsdr_rs_close;
+    let _: DevFn<u32> = rtlsdr_rs_set_center_freq;
+    let _: unsafe extern "C" fn(*mut RtlSdrDev) -> u32 = rtlsdr_rs_get_center_freq;
+    let _: DevFn<u32> = rtlsdr_rs_set_sample_rate;
+    let _: unsafe extern "C" fn(*mut RtlSdrDev) -> u32 = rtlsdr_rs_get_sample_rate;
+    let _: DevFn<c_int> = rtlsdr_rs_set_tuner_gain;
+    let _: unsafe extern "C" fn(*mut RtlSdrDev) -> c_int = rtlsdr_rs_set_tuner_gain_auto;
+    let _: DevFn<c_int> = rtlsdr_rs_set_freq_correction;
+    let _: unsafe extern "C" fn(*mut RtlSdrDev, u32, u32) -> c_int = rtlsdr_rs_set_xtal_freq;
+    let _: unsafe extern "C" fn(*mut RtlSdrDev, *mut u32, *mut u32) -> c_int =
+        rtlsdr_rs_get_xtal_freq;
+    let _: DevFn<c_int> = rtlsdr_rs_set_bias_tee;
+    let _: unsafe extern "C" fn(*mut RtlSdrDev, u8, c_int) -> c_int = rtlsdr_rs_set_bias_tee_gpio;
+    let _: unsafe extern "C" fn(*mut RtlSdrDev) -> c_int = rtlsdr_rs_get_bias_tee;
+    let _: unsafe extern "C" fn(*mut RtlSdrDev) -> c_int = rtlsdr_rs_reset_buffer;
+    let _: unsafe extern "C" fn(*mut RtlSdrDev, *mut u8, usize, *mut usize) -> c_int =
+        rtlsdr_rs_read_sync;
+    let _: unsafe extern "C" fn(*mut RtlSdrDev, *mut f32, usize, *mut usize) -> c_int =
+        rtlsdr_rs_read_cf32;
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    // Every export, assigned to a pointer of the exact type a C caller would use
+    const C_CHECK: &str = r#"#include "rtlsdr_rs.h"
+
+#ifndef __cplusplus
+#define static_assert _Static_assert
+#endif
+static_assert(sizeof(size_t) == RUST_USIZE, "size_t differs from usize");
+static_assert(sizeof(int) == RUST_C_INT, "int differs from c_int");
+
+int main(void) {
+    const char *(*last_error)(void) = rtlsdr_rs_last_error;
+    const char *(*build_info)(void) = rtlsdr_rs_build_info;
+    int (*open)(uint32_t, RtlSdrDev **) = rtlsdr_rs_open;
+    int (*open_by_serial)(const char *, RtlSdrDev **) = rtlsdr_rs_open_by_serial;
+    int (*close)(RtlSdrDev *) = rtlsdr_rs_close;
+    int (*set_center_freq)(RtlSdrDev *, uint32_t) = rtlsdr_rs_set_center_freq;
+    uint32_t (*get_center_freq)(RtlSdrDev *) = rtlsdr_rs_get_center_freq;
+    int (*set_sample_rate)(RtlSdrDev *, uint32_t) = rtlsdr_rs_set_sample_rate;
+    uint32_t (*get_sample_rate)(RtlSdrDev *) = rtlsdr_rs_get_sample_rate;
+    int (*set_tuner_gain)(RtlSdrDev *, int) = rtlsdr_rs_set_tuner_gain;
+    int (*set_tuner_gain_auto)(RtlSdrDev *) = rtlsdr_rs_set_tuner_gain_auto;
+    int (*set_freq_correction)(RtlSdrDev *, int) = rtlsdr_rs_set_freq_correction;
+    int (*set_xtal_freq)(RtlSdrDev *, uint32_t, uint32_t) = rtlsdr_rs_set_xtal_freq;
+    int (*get_xtal_freq)(RtlSdrDev *, uint32_t *, uint32_t *) = rtlsdr_rs_get_xtal_freq;
+    int (*set_bias_tee)(RtlSdrDev *, int) = rtlsdr_rs_set_bias_tee;
+    int (*set_bias_tee_gpio)(RtlSdrDev *, uint8_t, int) = rtlsdr_rs_set_bias_tee_gpio;
+    int (*get_bias_tee)(RtlSdrDev *) = rtlsdr_rs_get_bias_tee;
+    int (*reset_buffer)(RtlSdrDev *) = rtlsdr_rs_reset_buffer;
+    int (*read_sync)(RtlSdrDev *, uint8_t *, size_t, size_t *) = rtlsdr_rs_read_sync;
+    int (*read_cf32)(RtlSdrDev *, float *, size_t, size_t *) = rtlsdr_rs_read_cf32;
+    (void)last_error; (void)build_info; (void)open; (void)open_by_serial; (void)close;
+    (void)set_center_freq; (void)get_center_freq; (void)set_sample_rate;
+    (void)get_sample_rate; (void)set_tuner_gain; (void)set_tuner_gain_auto;
+    (void)set_freq_correction; (void)set_xtal_freq; (void)get_xtal_freq;
+    (void)set_bias_tee; (void)set_bias_tee_gpio; (void)get_bias_tee;
+    (void)reset_buffer; (void)read_sync; (void)read_cf32;
+    return 0;
+}
+"#;
+
+    #[test]
+    fn shipped_header_is_current() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/rtlsdr_rs.h"));
+        let shipped = include_str!("../include/rtlsdr_rs.h");
+        assert!(
+            generated == shipped,
+            "include/rtlsdr_rs.h is stale; rebuild with RTLSDR_RS_UPDATE_HEADER=1"
+        );
+    }
+
+    #[test]
+    fn header_compiles_as_c_and_cpp() {
+        let dir = std::env::temp_dir().join(format!("rtlsdr_rs_abi_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("abi_check.c");
+        std::fs::write(&source, C_CHECK).unwrap();
+        let include = concat!(env!("CARGO_MANIFEST_DIR"), "/include");
+        let usize_len = format!("-DRUST_USIZE={}", std::mem::size_of::<usize>());
+        let c_int_len = format!("-DRUST_C_INT={}", std::mem::size_of::<c_int>());
+        let compilers: [(&str, &[&str]); 2] =
+            [("CC", &["-std=c11"]), ("CXX", &["-x", "c++", "-std=c++11"])];
+        for (var, args) in compilers {
+            let default = if var == "CC" { "cc" } else { "c++" };
+            let compiler = std::env::var(var).unwrap_or_else(|_| default.to_string());
+            let output = Command::new(&compiler)
+                .args(args)
+                .args(["-Wall", "-Werror", "-fsyntax-only", "-I", include])
+                .args([&usize_len, &c_int_len])
+                .arg(&source)
+                .output();
+            match output {
+                Ok(output) => assert!(
+                    output.status.success(),
+                    "{} rejected the header:\n{}",
+                    compiler,
+                    String::from_utf8_lossy(&output.stderr)
+                ),
+                // No toolchain to check against; the Rust-side checks still ran
+                Err(e) => eprintln!("Skipping {} check: {}", compiler, e),
+            }
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn null_handle_reports_error() {
+        unsafe {
+            assert_eq!(rtlsdr_rs_set_center_freq(ptr::null_mut(), 100_000_000), -1);
+            let error = CStr::from_ptr(rtlsdr_rs_last_error());
+            assert_eq!(error.to_str().unwrap(), "Null device handle");
+            assert_eq!(rtlsdr_rs_get_sample_rate(ptr::null_mut()), 0);
+            assert_eq!(rtlsdr_rs_close(ptr::null_mut()), 0);
+            let mut out = ptr::null_mut();
+            assert_eq!(rtlsdr_rs_open_by_serial(ptr::null(), &mut out), -1);
+        }
+    }
+
+    #[test]
+    fn build_info_is_json_object() {
+        let info = unsafe { CStr::from_ptr(rtlsdr_rs_build_info()) };
+        let info = info.to_str().unwrap();
+        assert!(info.starts_with('{') && info.ends_with('}'));
+        assert!(info.contains(&format!(
+            "\"crate_version\":\"{}\"",
+            env!("CARGO_PKG_VERSION")
+        )));
+    }
+}
@@ -0,0 +1,148 @@
+//! Magnitude (|IQ|) of raw samples for power-only consumers such as energy detectors
+//! and Mode S front-ends, skipping conversion to complex samples.
+use super::DEFAULT_DC_OFFSET;
+
+// Alpha max plus beta min coefficients with the smallest peak error (~3.96%)
+const ALPHA: f32 = 0.960_433_9;
+const BETA: f32 = 0.397_824_7;
+
+/// Approximate `sqrt(i * i + q * q)` without a square root
+#[inline]
+pub fn approx_magnitude(i: f32, q: f32) -> f32 {
+    let (i, q) = (i.abs(), q.abs());
+    let (max, min) = if i > q { (i, q) } else { (q, i) };
+    ALPHA * max + BETA * min
+}
+
+/// Turns raw interleaved u8 I/Q into magnitudes, averaging `decimation` consecutive
+/// samples into each output. Partial averages carry over between blocks.
+#[derive(Debug, Clone)]
+pub struct MagnitudeDecimator {
+    decimation: usize,
+    dc_offset: f32,
+    acc: f32,
+    count: usize,
+}
+
+impl MagnitudeDecimator {
+    /// `decimation` of 0 is treated as 1 (no decimation)
+    pub fn new(decimation: usize) -> Self {
+        MagnitudeDecimator {
+            decimation: decimation.max(1),
+            dc_offset: DEFAULT_DC_OFFSET,
+            acc: 0.0,
+            count: 0,
+        }
+    }
+
+    /// Use `dc_offset` instead of `DEFAULT_DC_OFFSET` as the center of the raw samples
+    pub fn with_dc_offset(mut self, dc_offset: f32) -> Self {
+        self.dc_offset = dc_offset;
+        self
+    }
+
+    pub fn set_dc_offset(&mut self, dc_offset: f32) {
+        self.dc_offset = dc_offset;
+    }
+
+    pub fn dc_offset(&self) -> f32 {
+        self.dc_offset
+    }
+
+    pub fn decimation(&self) -> usize {
+        self.decimation
+    }
+
+    /// Number of magnitudes the next `raw_len` bytes produce, counting the partial
+    /// average carried over
+    pub fn output_len(&self, raw_len: usize) -> usize {
+        (self.count + raw_len / 2) / self.decimation
+    }
+
+    /// Magnitudes scaled so a full-scale I or Q is 1.0, appending to `out`
+    pub fn to_f32(&mut self, raw: &[u8], out: &mut Vec<f32>) {
+        out.reserve(self.output_len(raw.len()));
+        self.process(raw, |m| out.push(m));
+    }
+
+    /// Magnitudes scaled so the largest possible value (full-scale I and Q) is 65535,
+    /// appending to `out`
+    pub fn to_u16(&mut self, raw: &[u8], out: &mut Vec<u16>) {
+        out.reserve(self.output_len(raw.len()));
+        self.process(raw, |m| out.push(to_u16(m)));
+    }
+
+    /// `to_u16` into a slice, for callers reusing one buffer. Returns the number of
+    /// magnitudes written.
+    ///
+    /// # Panics
+    /// If `out` is shorter than `output_len(raw.len())`
+    pub fn to_u16_into(&mut self, raw: &[u8], out: &mut [u16]) -> usize {
+        let len = self.output_len(raw.len());
+        assert!(
+            out.len() >= len,
+            "{} magnitudes don't fit in {}",
+            len,
+            out.len()
+        );
+        let mut written = 0;
+        self.process(raw, |m| {
+            out[written] = to_u16(m);
+            written += 1;
+        });
+        written
+    }
+
+    // Pass each completed average to `emit`, scaled so a full-scale I or Q is 1.0
+    fn process<F: FnMut(f32)>(&mut self, raw: &[u8], mut emit: F) {
+        let scale = 1.0 / DEFAULT_DC_OFFSET;
+        for iq in raw.chunks_exact(2) {
+            let i = iq[0] as f32 - self.dc_offset;
+            let q = iq[1] as f32 - self.dc_offset;
+            self.acc += approx_magnitude(i, q);
+            self.count += 1;
+            if self.count == self.decimation {
+                emit(self.acc * scale / self.decimation as f32);
+                self.acc = 0.0;
+                self.count = 0;
+            }
+        }
+    }
+}
+
+// Scale a magnitude from `MagnitudeDecimator::to_f32` to the full u16 range
+fn to_u16(magnitude: f32) -> u16 {
+    let scale = u16::MAX as f32 / approx_magnitude(1.0, 1.0);
+    (magnitude * scale).round().min(u16::MAX as f32) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn u16_into_matches_u16() {
+        let raw: Vec<u8> = (0..=255).collect();
+        let mut expected = Vec::new();
+        MagnitudeDecimator::new(3).to_u16(&raw, &mut expected);
+
+        let mut decimator = MagnitudeDecimator::new(3);
+        let mut out = [0; 64];
+        let first = decimator.to_u16_into(&raw[..100], &mut out);
+        // The partial average left by the first block carries into the second
+        let second = decimator.to_u16_into(&raw[100..], &mut out[first..]);
+        assert_eq!(first + second, expected.len());
+        assert_eq!(out[..first + second], expected[..]);
+    }
+
+    #[test]
+    fn dc_offset_moves_the_zero() {
+        let mut out = Vec::new();
+        let mut decimator = MagnitudeDecimator::new(1).with_dc_offset(127.0);
+        decimator.to_f32(&[127, 127], &mut out);
+        decimator.set_dc_offset(DEFAULT_DC_OFFSET);
+        decimator.to_f32(&[127, 127], &mut out);
+        assert_eq!(out[0], 0.0);
+        assert!(out[1] > 0.0);
+    }
+}
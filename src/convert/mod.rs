@@ -0,0 +1,306 @@
+//! Conversion of raw samples (interleaved unsigned 8-bit I/Q) into other sample formats,
+//! and packing of 16-bit samples into bytes for files and network streams.
+pub mod magnitude;
+pub mod pool;
+pub use magnitude::MagnitudeDecimator;
+pub use pool::ConversionPool;
+
+/// Byte order of multi-byte samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Endianness {
+    /// What GNU Radio and inspectrum expect for `cs16`/`sc16` files
+    #[default]
+    Little,
+    Big,
+}
+
+/// Arrangement of I and Q samples in a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Packing {
+    /// I0 Q0 I1 Q1 ...
+    #[default]
+    Interleaved,
+    /// I0 I1 ... followed by Q0 Q1 ...
+    Planar,
+}
+
+/// Layout of 16-bit output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct I16Format {
+    pub endianness: Endianness,
+    pub packing: Packing,
+}
+
+impl I16Format {
+    /// SigMF datatype of the output; SigMF has none for planar blocks
+    pub fn sigmf_datatype(&self) -> Option<&'static str> {
+        match (self.packing, self.endianness) {
+            (Packing::Interleaved, Endianness::Little) => Some("ci16_le"),
+            (Packing::Interleaved, Endianness::Big) => Some("ci16_be"),
+            (Packing::Planar, _) => None,
+        }
+    }
+}
+
+/// Default value subtracted from raw samples to center them on zero.
+///
+/// 127.5 is the midpoint of the 8-bit ADC range. In test mode (`set_testmode(true)`)
+/// the device emits an incrementing 8-bit counter, and a full 0..=255 cycle averages
+/// to exactly 127.5, so this offset leaves no DC component. Some tools use 127
+/// instead, which leaves a +0.5 LSB bias.
+pub const DEFAULT_DC_OFFSET: f32 = 127.5;
+
+/// Scale between floats in [-1.0, 1.0] and i16, as GNU Radio uses for `cs16` files
+pub const I16_SCALE: f32 = 32768.0;
+
+/// Converts raw u8 samples using a configurable DC offset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Converter {
+    pub dc_offset: f32,
+}
+
+impl Default for Converter {
+    fn default() -> Self {
+        Converter {
+            dc_offset: DEFAULT_DC_OFFSET,
+        }
+    }
+}
+
+impl Converter {
+    pub fn new(dc_offset: f32) -> Self {
+        Converter { dc_offset }
+    }
+
+    /// Convert to floats in roughly [-1.0, 1.0], appending to `out`
+    pub fn to_f32(&self, raw: &[u8], out: &mut Vec<f32>) {
+        let scale = 1.0 / DEFAULT_DC_OFFSET;
+        out.extend(raw.iter().map(|x| (*x as f32 - self.dc_offset) * scale));
+    }
+
+    /// Convert to full scale i16, appending to `out`
+    pub fn to_i16(&self, raw: &[u8], out: &mut Vec<i16>) {
+        out.extend(raw.iter().map(|x| {
+            ((*x as f32 - self.dc_offset) * 256.0)
+                .round()
+                .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+        }));
+    }
+}
+
+/// Convert raw u8 samples to floats with the default DC offset, appending to `out`
+pub fn u8_to_f32(raw: &[u8], out: &mut Vec<f32>) {
+    Converter::default().to_f32(raw, out)
+}
+
+/// Convert raw u8 samples to full scale i16 with the default DC offset, appending to `out`
+pub fn u8_to_i16(raw: &[u8], out: &mut Vec<i16>) {
+    Converter::default().to_i16(raw, out)
+}
+
+/// Convert raw u8 samples to full scale i16 with the default DC offset and pack them
+/// as `format`, appending to `out`. With planar packing, `raw` is one block.
+pub fn u8_to_packed_i16(raw: &[u8], format: I16Format, out: &mut Vec<u8>) {
+    let mut samples = Vec::with_capacity(raw.len());
+    u8_to_i16(raw, &mut samples);
+    pack_i16(&samples, format, out);
+}
+
+/// Convert floats in [-1.0, 1.0] to i16 at `I16_SCALE`, saturating, appending to `out`
+pub fn f32_to_i16(samples: &[f32], out: &mut Vec<i16>) {
+    out.extend(samples.iter().map(|s| {
+        (s * I16_SCALE)
+            .round()
+            .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+    }));
+}
+
+/// Swap I and Q of interleaved u8 samples in place. A trailing odd byte is left alone.
+pub fn swap_iq(samples: &mut [u8]) {
+    samples.chunks_exact_mut(2).for_each(|pair| pair.swap(0, 1));
+}
+
+/// Pack interleaved I/Q i16 samples into bytes using `format`, appending to `out`
+pub fn pack_i16(samples: &[i16], format: I16Format, out: &mut Vec<u8>) {
+    out.reserve(samples.len() * 2);
+    let mut push = |sample: i16| match format.endianness {
+        Endianness::Little => out.extend_from_slice(&sample.to_le_bytes()),
+        Endianness::Big => out.extend_from_slice(&sample.to_be_bytes()),
+    };
+    match format.packing {
+        Packing::Interleaved => samples.iter().for_each(|s| push(*s)),
+        Packing::Planar => {
+            samples.iter().step_by(2).for_each(|s| push(*s));
+            samples.iter().skip(1).step_by(2).for_each(|s| push(*s));
+        }
+    }
+}
+
+/// Read interleaved 16-bit samples stored in `endianness` byte order, appending to
+/// `out`. A trailing odd byte is ignored.
+pub fn unpack_i16(bytes: &[u8], endianness: Endianness, out: &mut Vec<i16>) {
+    out.extend(bytes.chunks_exact(2).map(|b| match endianness {
+        Endianness::Little => i16::from_le_bytes([b[0], b[1]]),
+        Endianness::Big => i16::from_be_bytes([b[0], b[1]]),
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What the RTL2832 emits in test mode: an 8-bit counter incremented for every
+    // byte, I and Q alike, starting wherever the counter happened to be
+    fn test_mode_block(start: u8, len: usize) -> Vec<u8> {
+        (0..len).map(|i| start.wrapping_add(i as u8)).collect()
+    }
+
+    fn mean(samples: &[f32]) -> f64 {
+        samples.iter().map(|s| *s as f64).sum::<f64>() / samples.len() as f64
+    }
+
+    fn format(endianness: Endianness, packing: Packing) -> I16Format {
+        I16Format {
+            endianness,
+            packing,
+        }
+    }
+
+    #[test]
+    fn pack_i16_interleaved_little() {
+        let mut out = Vec::new();
+        pack_i16(
+            &[0x0102, 0x0304, -2, 5],
+            format(Endianness::Little, Packing::Interleaved),
+            &mut out,
+        );
+        assert_eq!(out, [0x02, 0x01, 0x04, 0x03, 0xfe, 0xff, 0x05, 0x00]);
+    }
+
+    #[test]
+    fn pack_i16_interleaved_big() {
+        let mut out = Vec::new();
+        pack_i16(
+            &[0x0102, 0x0304, -2, 5],
+            format(Endianness::Big, Packing::Interleaved),
+            &mut out,
+        );
+        assert_eq!(out, [0x01, 0x02, 0x03, 0x04, 0xff, 0xfe, 0x00, 0x05]);
+    }
+
+    #[test]
+    fn unpack_i16_reverses_interleaved_packing() {
+        let samples = [0x0102, 0x0304, -2, i16::MIN];
+        for endianness in [Endianness::Little, Endianness::Big] {
+            let mut bytes = Vec::new();
+            pack_i16(
+                &samples,
+                format(endianness, Packing::Interleaved),
+                &mut bytes,
+            );
+            let mut out = Vec::new();
+            unpack_i16(&bytes, endianness, &mut out);
+            assert_eq!(out, samples);
+        }
+    }
+
+    #[test]
+    fn f32_to_i16_saturates() {
+        let mut out = Vec::new();
+        f32_to_i16(&[0.0, 0.5, -1.0, 1.0, -2.0], &mut out);
+        assert_eq!(out, [0, 16384, i16::MIN, i16::MAX, i16::MIN]);
+    }
+
+    #[test]
+    fn pack_i16_planar_little() {
+        let mut out = Vec::new();
+        pack_i16(
+            &[0x0102, 0x0304, -2, 5],
+            format(Endianness::Little, Packing::Planar),
+            &mut out,
+        );
+        // I0 I1 then Q0 Q1
+        assert_eq!(out, [0x02, 0x01, 0xfe, 0xff, 0x04, 0x03, 0x05, 0x00]);
+    }
+
+    #[test]
+    fn pack_i16_planar_big() {
+        let mut out = Vec::new();
+        pack_i16(
+            &[0x0102, 0x0304, -2, 5],
+            format(Endianness::Big, Packing::Planar),
+            &mut out,
+        );
+        assert_eq!(out, [0x01, 0x02, 0xff, 0xfe, 0x03, 0x04, 0x00, 0x05]);
+    }
+
+    #[test]
+    fn sigmf_datatypes() {
+        use Endianness::*;
+        assert_eq!(
+            format(Little, Packing::Interleaved).sigmf_datatype(),
+            Some("ci16_le")
+        );
+        assert_eq!(
+            format(Big, Packing::Interleaved).sigmf_datatype(),
+            Some("ci16_be")
+        );
+        assert_eq!(format(Little, Packing::Planar).sigmf_datatype(), None);
+        assert_eq!(format(Big, Packing::Planar).sigmf_datatype(), None);
+    }
+
+    #[test]
+    fn u8_to_packed_i16_converts_then_packs() {
+        let mut out = Vec::new();
+        u8_to_packed_i16(
+            &[255, 0],
+            format(Endianness::Big, Packing::Interleaved),
+            &mut out,
+        );
+        // (255 - 127.5) * 256 and (0 - 127.5) * 256
+        assert_eq!(out, [0x7f, 0x80, 0x80, 0x80]);
+    }
+
+    #[test]
+    fn default_offset_centers_test_mode_ramp() {
+        for start in [0, 1, 127, 200, 255] {
+            let raw = test_mode_block(start, 256 * 64);
+            let mut out = Vec::new();
+            u8_to_f32(&raw, &mut out);
+            assert!(
+                mean(&out).abs() < 1e-9,
+                "start {}: mean {}",
+                start,
+                mean(&out)
+            );
+        }
+    }
+
+    #[test]
+    fn offset_127_leaves_half_lsb_bias() {
+        let raw = test_mode_block(0, 256 * 64);
+        let mut out = Vec::new();
+        Converter::new(127.0).to_f32(&raw, &mut out);
+        let half_lsb = 0.5 / DEFAULT_DC_OFFSET as f64;
+        assert!((mean(&out) - half_lsb).abs() < 1e-9, "mean {}", mean(&out));
+    }
+
+    #[test]
+    fn default_offset_is_symmetric() {
+        let mut out = Vec::new();
+        u8_to_f32(&[0, 255, 127, 128], &mut out);
+        assert_eq!(out[0], -1.0);
+        assert_eq!(out[1], 1.0);
+        assert_eq!(out[2], -out[3]);
+    }
+
+    #[test]
+    fn i16_test_mode_ramp_sums_to_zero() {
+        let raw = test_mode_block(42, 256 * 4);
+        let mut out = Vec::new();
+        u8_to_i16(&raw, &mut out);
+        assert_eq!(out.iter().map(|s| *s as i64).sum::<i64>(), 0);
+        assert_eq!(out.iter().min(), Some(&-32640));
+        assert_eq!(out.iter().max(), Some(&32640));
+    }
+}
@@ -0,0 +1,123 @@
+//! Worker pool for running sample conversion off the USB read thread.
+//!
+//! Hosts streaming from several devices can share one small pool: each stream gets a
+//! `ConversionSender`/`ConversionReceiver` pair from `ConversionPool::channel`, hands
+//! raw buffers to the pool and receives converted buffers back in submission order.
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use super::Converter;
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::platform;
+
+struct Job {
+    seq: u64,
+    raw: Vec<u8>,
+    converter: Converter,
+    reply: Sender<(u64, Vec<f32>)>,
+}
+
+/// Fixed set of worker threads converting raw buffers to f32
+pub struct ConversionPool {
+    jobs: Sender<Job>,
+    workers: usize,
+}
+
+impl ConversionPool {
+    /// Spawn `workers` conversion threads (at least one). Workers exit once the pool
+    /// and every sender created from it have been dropped.
+    pub fn new(workers: usize) -> Result<ConversionPool> {
+        let workers = workers.max(1);
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        for i in 0..workers {
+            let queue = queue.clone();
+            platform::spawn(&format!("rtlsdr-convert-{}", i), move || loop {
+                // Hold the lock only while waiting for the next job
+                let job = match queue.lock() {
+                    Ok(queue) => queue.recv(),
+                    Err(_) => break,
+                };
+                let Ok(job) = job else {
+                    break;
+                };
+                let mut out = Vec::with_capacity(job.raw.len());
+                job.converter.to_f32(&job.raw, &mut out);
+                // Receiver may have gone away; nothing to do about it here
+                let _ = job.reply.send((job.seq, out));
+            })?;
+        }
+        Ok(ConversionPool { jobs, workers })
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Create a submission/result pair for one stream
+    pub fn channel(&self, converter: Converter) -> (ConversionSender, ConversionReceiver) {
+        let (reply, results) = mpsc::channel();
+        (
+            ConversionSender {
+                jobs: self.jobs.clone(),
+                reply,
+                converter,
+                next_seq: 0,
+            },
+            ConversionReceiver {
+                results,
+                next_seq: 0,
+                pending: BTreeMap::new(),
+            },
+        )
+    }
+}
+
+/// Submits raw buffers of one stream to the pool
+pub struct ConversionSender {
+    jobs: Sender<Job>,
+    reply: Sender<(u64, Vec<f32>)>,
+    converter: Converter,
+    next_seq: u64,
+}
+
+impl ConversionSender {
+    /// Queue a buffer for conversion. Returns immediately.
+    pub fn submit(&mut self, raw: Vec<u8>) -> Result<()> {
+        let job = Job {
+            seq: self.next_seq,
+            raw,
+            converter: self.converter,
+            reply: self.reply.clone(),
+        };
+        self.jobs
+            .send(job)
+            .map_err(|_| RtlsdrErr("Conversion pool has shut down".to_string()))?;
+        self.next_seq += 1;
+        Ok(())
+    }
+}
+
+/// Receives converted buffers of one stream, in the order they were submitted
+pub struct ConversionReceiver {
+    results: Receiver<(u64, Vec<f32>)>,
+    next_seq: u64,
+    pending: BTreeMap<u64, Vec<f32>>,
+}
+
+impl ConversionReceiver {
+    /// Block until the next buffer in submission order is converted.
+    /// Returns `None` once the sender is dropped and all buffers were delivered.
+    pub fn recv(&mut self) -> Option<Vec<f32>> {
+        loop {
+            if let Some(buf) = self.pending.remove(&self.next_seq) {
+                self.next_seq += 1;
+                return Some(buf);
+            }
+            let (seq, buf) = self.results.recv().ok()?;
+            self.pending.insert(seq, buf);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Energy burst detector: finds stretches where the signal power rises a threshold
+//! above a slowly moving noise floor, the usual first stage for decoding short ISM
+//! band transmissions.
+use super::u8_to_power;
+
+/// Tuning of a `BurstDetector`. Lengths are in samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BurstDetectorConfig {
+    /// How far above the noise floor a sample must be to count as signal
+    pub threshold_db: f32,
+    /// Weight of each new sample in the noise floor average, e.g. 0.001
+    pub noise_alpha: f32,
+    /// Shorter bursts are ignored
+    pub min_len: usize,
+    /// Longer bursts (e.g. a continuous carrier) are dropped
+    pub max_len: usize,
+    /// Quiet samples tolerated inside a burst, so OOK gaps don't split it
+    pub max_gap: usize,
+}
+
+impl Default for BurstDetectorConfig {
+    fn default() -> Self {
+        BurstDetectorConfig {
+            threshold_db: 10.0,
+            noise_alpha: 0.001,
+            min_len: 100,
+            max_len: 2_400_000,
+            max_gap: 1000,
+        }
+    }
+}
+
+/// A detected burst
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Burst {
+    /// Index of the first sample above the threshold, counted from the detector's start
+    pub start_sample: u64,
+    /// Samples from the first to the last sample above the threshold
+    pub len: usize,
+    /// Highest instantaneous power in the burst (full scale ~1.0)
+    pub peak_power: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Active {
+    start: u64,
+    last_above: u64,
+    peak: f32,
+}
+
+/// Streaming burst detector; feed it consecutive blocks of samples
+#[derive(Debug, Clone)]
+pub struct BurstDetector {
+    config: BurstDetectorConfig,
+    threshold: f32,
+    noise_floor: Option<f32>,
+    active: Option<Active>,
+    sample: u64,
+    power: Vec<f32>,
+}
+
+impl BurstDetector {
+    pub fn new(config: BurstDetectorConfig) -> Self {
+        BurstDetector {
+            config,
+            threshold: 10f32.powf(config.threshold_db / 10.0),
+            noise_floor: None,
+            active: None,
+            sample: 0,
+            power: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &BurstDetectorConfig {
+        &self.config
+    }
+
+    /// Current noise floor estimate, if any samples were seen
+    pub fn noise_floor(&self) -> Option<f32> {
+        self.noise_floor
+    }
+
+    /// Process raw interleaved u8 I/Q, appending completed bursts to `out`
+    pub fn process(&mut self, raw: &[u8], out: &mut Vec<Burst>) {
+        let mut power = std::mem::take(&mut self.power);
+        power.clear();
+        u8_to_power(raw, &mut power);
+        self.process_power(&power, out);
+        self.power = power;
+    }
+
+    /// Process instantaneous power samples, appending completed bursts to `out`
+    pub fn process_power(&mut self, power: &[f32], out: &mut Vec<Burst>) {
+        for p in power.iter().copied() {
+            let floor = *self.noise_floor.get_or_insert(p);
+            let above = p > floor * self.threshold;
+            match self.active.as_mut() {
+                Some(active) => {
+                    if above {
+                        active.last_above = self.sample;
+                        active.peak = active.peak.max(p);
+                    }
+                    let len = (self.sample - active.start) as usize + 1;
+                    if len > self.config.max_len {
+                        // Too long to be a burst; let the floor adapt to it instead
+                        self.active = None;
+                    } else if self.sample - active.last_above > self.config.max_gap as u64 {
+                        let active = *active;
+                        self.active = None;
+                        self.finish(active, out);
+                    }
+                }
+                None if above => {
+                    self.active = Some(Active {
+                        start: self.sample,
+                        last_above: self.sample,
+                        peak: p,
+                    });
+                }
+                None => {}
+            }
+            if self.active.is_none() {
+                let alpha = self.config.noise_alpha;
+                self.noise_floor = Some(floor + (p - floor) * alpha);
+            }
+            self.sample += 1;
+        }
+    }
+
+    /// End any burst in progress, e.g. at the end of a recording
+    pub fn flush(&mut self, out: &mut Vec<Burst>) {
+        if let Some(active) = self.active.take() {
+            self.finish(active, out);
+        }
+    }
+
+    fn finish(&self, active: Active, out: &mut Vec<Burst>) {
+        let len = (active.last_above - active.start) as usize + 1;
+        if len >= self.config.min_len && len <= self.config.max_len {
+            out.push(Burst {
+                start_sample: active.start,
+                len,
+                peak_power: active.peak,
+            });
+        }
+    }
+}
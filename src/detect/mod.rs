@@ -0,0 +1,20 @@
+//! Signal detection on raw sample streams.
+pub mod burst;
+pub mod overload;
+pub mod slicer;
+pub use burst::{Burst, BurstDetector, BurstDetectorConfig};
+pub use overload::OverloadDetector;
+pub use slicer::{slice_fsk, slice_ook, SlicerConfig};
+
+use crate::convert::DEFAULT_DC_OFFSET;
+
+/// Instantaneous power of each raw interleaved u8 I/Q sample, full scale ~1.0,
+/// appending to `out`
+pub fn u8_to_power(raw: &[u8], out: &mut Vec<f32>) {
+    let scale = 1.0 / (DEFAULT_DC_OFFSET * DEFAULT_DC_OFFSET);
+    out.extend(raw.chunks_exact(2).map(|iq| {
+        let i = iq[0] as f32 - DEFAULT_DC_OFFSET;
+        let q = iq[1] as f32 - DEFAULT_DC_OFFSET;
+        (i * i + q * q) * scale
+    }));
+}
@@ -0,0 +1,60 @@
+//! ADC overload detection. Isolated 0/255 values happen on strong but legitimate
+//! signals; a run of consecutive clipped values means the gain is too high or a
+//! nearby transmitter is driving the front end into saturation.
+
+/// Consecutive clipped raw values that count as an overload by default
+pub const DEFAULT_OVERLOAD_RUN: usize = 8;
+
+/// Counts overloads in raw u8 I/Q; runs may span blocks
+#[derive(Debug, Clone)]
+pub struct OverloadDetector {
+    min_run: usize,
+    run: usize,
+    clipped: u64,
+    overloads: u64,
+}
+
+impl Default for OverloadDetector {
+    fn default() -> Self {
+        OverloadDetector::new(DEFAULT_OVERLOAD_RUN)
+    }
+}
+
+impl OverloadDetector {
+    /// Count an overload whenever `min_run` consecutive values are clipped
+    pub fn new(min_run: usize) -> Self {
+        OverloadDetector {
+            min_run: min_run.max(1),
+            run: 0,
+            clipped: 0,
+            overloads: 0,
+        }
+    }
+
+    /// Scan a block, returning the number of overloads that started in it
+    pub fn process(&mut self, raw: &[u8]) -> u64 {
+        let before = self.overloads;
+        for x in raw {
+            if *x == 0 || *x == 255 {
+                self.clipped += 1;
+                self.run += 1;
+                if self.run == self.min_run {
+                    self.overloads += 1;
+                }
+            } else {
+                self.run = 0;
+            }
+        }
+        self.overloads - before
+    }
+
+    /// Total clipped values seen
+    pub fn clipped(&self) -> u64 {
+        self.clipped
+    }
+
+    /// Total overloads seen
+    pub fn overloads(&self) -> u64 {
+        self.overloads
+    }
+}
@@ -0,0 +1,97 @@
+//! Symbol slicers turning a burst of raw samples into bits, for prototyping simple
+//! OOK/ASK and 2-FSK decoders (433/868 MHz sensors, remotes, ...).
+//!
+//! Pass the raw samples of one burst, e.g.
+//! `&raw[burst.start_sample as usize * 2..(burst.start_sample as usize + burst.len) * 2]`
+//! for a `Burst` found in `raw`. Bits are NRZ: one bit per symbol period, with clock
+//! recovery re-synchronizing on every level transition.
+use super::u8_to_power;
+use crate::convert::DEFAULT_DC_OFFSET;
+
+/// Symbol timing of the signal being sliced
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlicerConfig {
+    pub sample_rate: u32,
+    /// Symbols per second
+    pub baud: f32,
+}
+
+impl SlicerConfig {
+    pub fn samples_per_symbol(&self) -> f32 {
+        self.sample_rate as f32 / self.baud
+    }
+}
+
+/// OOK/ASK: carrier on is 1. The threshold is half the peak amplitude of the burst.
+pub fn slice_ook(raw: &[u8], config: SlicerConfig) -> Vec<bool> {
+    let mut power = Vec::with_capacity(raw.len() / 2);
+    u8_to_power(raw, &mut power);
+    let peak = power.iter().copied().fold(0.0, f32::max);
+    // Half the amplitude is a quarter of the power
+    let threshold = peak / 4.0;
+    let levels: Vec<bool> = power.iter().map(|p| *p > threshold).collect();
+    recover_bits(&levels, config.samples_per_symbol())
+}
+
+/// 2-FSK: the higher of the two tones is 1. The threshold is the mean instantaneous
+/// frequency of the burst, so the burst should be centered on the signal and contain
+/// a reasonable mix of both tones.
+pub fn slice_fsk(raw: &[u8], config: SlicerConfig) -> Vec<bool> {
+    let freq = instantaneous_freq(raw);
+    if freq.is_empty() {
+        return Vec::new();
+    }
+    let mean = freq.iter().sum::<f32>() / freq.len() as f32;
+    let levels: Vec<bool> = freq.iter().map(|f| *f > mean).collect();
+    recover_bits(&levels, config.samples_per_symbol())
+}
+
+/// Phase change between consecutive samples in radians/sample, one value fewer than
+/// there are samples
+pub fn instantaneous_freq(raw: &[u8]) -> Vec<f32> {
+    let iq: Vec<(f32, f32)> = raw
+        .chunks_exact(2)
+        .map(|s| {
+            (
+                s[0] as f32 - DEFAULT_DC_OFFSET,
+                s[1] as f32 - DEFAULT_DC_OFFSET,
+            )
+        })
+        .collect();
+    iq.windows(2)
+        .map(|w| {
+            let ((i0, q0), (i1, q1)) = (w[0], w[1]);
+            // arg(s1 * conj(s0))
+            (q1 * i0 - i1 * q0).atan2(i1 * i0 + q1 * q0)
+        })
+        .collect()
+}
+
+/// Sample a sliced level sequence once per symbol, in the middle of each symbol.
+/// The symbol clock restarts at every transition, which keeps it aligned despite
+/// small baud rate errors.
+pub fn recover_bits(levels: &[bool], samples_per_symbol: f32) -> Vec<bool> {
+    if samples_per_symbol < 1.0 {
+        return Vec::new();
+    }
+    let step = 1.0 / samples_per_symbol;
+    let mut bits = Vec::with_capacity((levels.len() as f32 * step) as usize + 1);
+    // Position inside the current symbol, 0.0 at its start
+    let mut t = 0.0f32;
+    let mut prev = match levels.first() {
+        Some(level) => *level,
+        None => return bits,
+    };
+    for level in levels.iter().copied() {
+        if level != prev {
+            t = 0.0;
+            prev = level;
+        }
+        let next = t + step;
+        if t < 0.5 && next >= 0.5 {
+            bits.push(level);
+        }
+        t = if next >= 1.0 { next - 1.0 } else { next };
+    }
+    bits
+}
@@ -1,225 +1,430 @@
+use std::borrow::Cow;
+use std::sync::RwLock;
 use std::time::Duration;
 
+/// Per-device workarounds applied while opening and initializing a dongle.
+///
+/// Flags can be combined with `|`, e.g.
+/// `DeviceQuirks::LONG_RESET_DELAY | DeviceQuirks::BIAS_TEE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DeviceQuirks(u32);
+
+impl DeviceQuirks {
+    pub const NONE: DeviceQuirks = DeviceQuirks(0);
+    /// Wait `QUIRK_RESET_DELAY` after resetting the device before talking to it again
+    pub const LONG_RESET_DELAY: DeviceQuirks = DeviceQuirks(1 << 0);
+    /// Board has a switchable bias tee on GPIO 0 (RTL-SDR Blog V3 and V4, which share
+    /// the generic ids and are told apart by product string in `quirks`), see
+    /// `RtlSdr::has_bias_tee`
+    pub const BIAS_TEE: DeviceQuirks = DeviceQuirks(1 << 2);
+
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub const fn contains(&self, other: DeviceQuirks) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: DeviceQuirks) -> DeviceQuirks {
+        DeviceQuirks(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitOr for DeviceQuirks {
+    type Output = DeviceQuirks;
+
+    fn bitor(self, rhs: DeviceQuirks) -> DeviceQuirks {
+        self.union(rhs)
+    }
+}
+
+/// Entry in the known device table
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UsbDeviceSignature {
     pub vid: u16,
     pub pid: u16,
-    pub description: &'static str,
+    /// Borrowed for the built-in table, owned for `register_known_device` entries
+    pub description: Cow<'static, str>,
+    /// Id of the tuner this device usually ships with (see `tuners::KNOWN_TUNERS`), if known
+    pub tuner: Option<&'static str>,
+    pub quirks: DeviceQuirks,
+}
+
+impl UsbDeviceSignature {
+    /// Look up a VID/PID pair in `KNOWN_DEVICES` and the devices added with
+    /// `register_known_device`
+    pub fn find(vid: u16, pid: u16) -> Option<UsbDeviceSignature> {
+        known_devices()
+            .into_iter()
+            .find(|dev| dev.vid == vid && dev.pid == pid)
+    }
+
+    /// Signature for a device that isn't in `KNOWN_DEVICES`
+    pub const fn unknown(vid: u16, pid: u16) -> UsbDeviceSignature {
+        UsbDeviceSignature {
+            vid,
+            pid,
+            description: Cow::Borrowed("Unknown RTL2832U device"),
+            tuner: None,
+            quirks: DeviceQuirks::NONE,
+        }
+    }
+}
+
+static REGISTERED_DEVICES: RwLock<Vec<UsbDeviceSignature>> = RwLock::new(Vec::new());
+
+/// Treat another VID/PID pair as a supported RTL2832U device, e.g. a rebadged dongle,
+/// so it is listed and can be opened by index or serial. Registering a pair again, or
+/// one already in `KNOWN_DEVICES`, replaces its description; the built-in tuner and
+/// quirks are kept.
+pub fn register_known_device(vid: u16, pid: u16, description: &str) {
+    let mut registered = REGISTERED_DEVICES
+        .write()
+        .unwrap_or_else(|e| e.into_inner());
+    registered.retain(|dev| dev.vid != vid || dev.pid != pid);
+    let builtin = KNOWN_DEVICES
+        .iter()
+        .find(|dev| dev.vid == vid && dev.pid == pid)
+        .cloned()
+        .unwrap_or_else(|| UsbDeviceSignature::unknown(vid, pid));
+    registered.push(UsbDeviceSignature {
+        description: Cow::Owned(description.to_string()),
+        ..builtin
+    });
 }
+
+/// `KNOWN_DEVICES` followed by the devices added with `register_known_device`, each
+/// VID/PID pair once. A registered entry takes the place of a built-in one.
+pub fn known_devices() -> Vec<UsbDeviceSignature> {
+    let registered = REGISTERED_DEVICES.read().unwrap_or_else(|e| e.into_inner());
+    let overrides = |dev: &UsbDeviceSignature| {
+        registered
+            .iter()
+            .find(|reg| reg.vid == dev.vid && reg.pid == dev.pid)
+            .cloned()
+    };
+    let mut devices: Vec<UsbDeviceSignature> = KNOWN_DEVICES
+        .iter()
+        .map(|dev| overrides(dev).unwrap_or_else(|| dev.clone()))
+        .collect();
+    devices.extend(
+        registered
+            .iter()
+            .filter(|reg| {
+                !KNOWN_DEVICES
+                    .iter()
+                    .any(|dev| dev.vid == reg.vid && dev.pid == reg.pid)
+            })
+            .cloned(),
+    );
+    devices
+}
+
 pub const KNOWN_DEVICES: &'static [UsbDeviceSignature; 42] = &[
     UsbDeviceSignature {
         vid: 0x0bda,
         pid: 0x2832,
-        description: "Generic RTL2832U",
+        description: Cow::Borrowed("Generic RTL2832U"),
+        tuner: None,
+        // Unbranded clones can take a while to come back after the reset check
+        quirks: DeviceQuirks::LONG_RESET_DELAY,
     },
     UsbDeviceSignature {
         vid: 0x0bda,
         pid: 0x2838,
-        description: "Generic RTL2832U OEM",
+        description: Cow::Borrowed("Generic RTL2832U OEM"),
+        tuner: None,
+        quirks: DeviceQuirks::LONG_RESET_DELAY,
     },
     UsbDeviceSignature {
         vid: 0x0413,
         pid: 0x6680,
-        description: "DigitalNow Quad DVB-T PCI-E card",
+        description: Cow::Borrowed("DigitalNow Quad DVB-T PCI-E card"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0413,
         pid: 0x6f0f,
-        description: "Leadtek WinFast DTV Dongle mini D",
+        description: Cow::Borrowed("Leadtek WinFast DTV Dongle mini D"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0458,
         pid: 0x707f,
-        description: "Genius TVGo DVB-T03 USB dongle (Ver. B)",
+        description: Cow::Borrowed("Genius TVGo DVB-T03 USB dongle (Ver. B)"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0ccd,
         pid: 0x00a9,
-        description: "Terratec Cinergy T Stick Black (rev 1)",
+        description: Cow::Borrowed("Terratec Cinergy T Stick Black (rev 1)"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0ccd,
         pid: 0x00b3,
-        description: "Terratec NOXON DAB/DAB+ USB dongle (rev 1)",
+        description: Cow::Borrowed("Terratec NOXON DAB/DAB+ USB dongle (rev 1)"),
+        tuner: Some("fc0013"),
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0ccd,
         pid: 0x00b4,
-        description: "Terratec Deutschlandradio DAB Stick",
+        description: Cow::Borrowed("Terratec Deutschlandradio DAB Stick"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0ccd,
         pid: 0x00b5,
-        description: "Terratec NOXON DAB Stick - Radio Energy",
+        description: Cow::Borrowed("Terratec NOXON DAB Stick - Radio Energy"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0ccd,
         pid: 0x00b7,
-        description: "Terratec Media Broadcast DAB Stick",
+        description: Cow::Borrowed("Terratec Media Broadcast DAB Stick"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0ccd,
         pid: 0x00b8,
-        description: "Terratec BR DAB Stick",
+        description: Cow::Borrowed("Terratec BR DAB Stick"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0ccd,
         pid: 0x00b9,
-        description: "Terratec WDR DAB Stick",
+        description: Cow::Borrowed("Terratec WDR DAB Stick"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0ccd,
         pid: 0x00c0,
-        description: "Terratec MuellerVerlag DAB Stick",
+        description: Cow::Borrowed("Terratec MuellerVerlag DAB Stick"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0ccd,
         pid: 0x00c6,
-        description: "Terratec Fraunhofer DAB Stick",
+        description: Cow::Borrowed("Terratec Fraunhofer DAB Stick"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0ccd,
         pid: 0x00d3,
-        description: "Terratec Cinergy T Stick RC (Rev.3)",
+        description: Cow::Borrowed("Terratec Cinergy T Stick RC (Rev.3)"),
+        tuner: Some("e4000"),
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0ccd,
         pid: 0x00d7,
-        description: "Terratec T Stick PLUS",
+        description: Cow::Borrowed("Terratec T Stick PLUS"),
+        tuner: Some("e4000"),
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x0ccd,
         pid: 0x00e0,
-        description: "Terratec NOXON DAB/DAB+ USB dongle (rev 2)",
+        description: Cow::Borrowed("Terratec NOXON DAB/DAB+ USB dongle (rev 2)"),
+        tuner: Some("fc0013"),
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1554,
         pid: 0x5020,
-        description: "PixelView PV-DT235U(RN)",
+        description: Cow::Borrowed("PixelView PV-DT235U(RN)"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x15f4,
         pid: 0x0131,
-        description: "Astrometa DVB-T/DVB-T2",
+        description: Cow::Borrowed("Astrometa DVB-T/DVB-T2"),
+        tuner: Some("r828d"),
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x15f4,
         pid: 0x0133,
-        description: "HanfTek DAB+FM+DVB-T",
+        description: Cow::Borrowed("HanfTek DAB+FM+DVB-T"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x185b,
         pid: 0x0620,
-        description: "Compro Videomate U620F",
+        description: Cow::Borrowed("Compro Videomate U620F"),
+        tuner: Some("e4000"),
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x185b,
         pid: 0x0650,
-        description: "Compro Videomate U650F",
+        description: Cow::Borrowed("Compro Videomate U650F"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x185b,
         pid: 0x0680,
-        description: "Compro Videomate U680F",
+        description: Cow::Borrowed("Compro Videomate U680F"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1b80,
         pid: 0xd393,
-        description: "GIGABYTE GT-U7300",
+        description: Cow::Borrowed("GIGABYTE GT-U7300"),
+        tuner: Some("fc0012"),
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1b80,
         pid: 0xd394,
-        description: "DIKOM USB-DVBT HD",
+        description: Cow::Borrowed("DIKOM USB-DVBT HD"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1b80,
         pid: 0xd395,
-        description: "Peak 102569AGPK",
+        description: Cow::Borrowed("Peak 102569AGPK"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1b80,
         pid: 0xd397,
-        description: "KWorld KW-UB450-T USB DVB-T Pico TV",
+        description: Cow::Borrowed("KWorld KW-UB450-T USB DVB-T Pico TV"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1b80,
         pid: 0xd398,
-        description: "Zaapa ZT-MINDVBZP",
+        description: Cow::Borrowed("Zaapa ZT-MINDVBZP"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1b80,
         pid: 0xd39d,
-        description: "SVEON STV20 DVB-T USB & FM",
+        description: Cow::Borrowed("SVEON STV20 DVB-T USB & FM"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1b80,
         pid: 0xd3a4,
-        description: "Twintech UT-40",
+        description: Cow::Borrowed("Twintech UT-40"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1b80,
         pid: 0xd3a8,
-        description: "ASUS U3100MINI_PLUS_V2",
+        description: Cow::Borrowed("ASUS U3100MINI_PLUS_V2"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1b80,
         pid: 0xd3af,
-        description: "SVEON STV27 DVB-T USB & FM",
+        description: Cow::Borrowed("SVEON STV27 DVB-T USB & FM"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1b80,
         pid: 0xd3b0,
-        description: "SVEON STV21 DVB-T USB & FM",
+        description: Cow::Borrowed("SVEON STV21 DVB-T USB & FM"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1d19,
         pid: 0x1101,
-        description: "Dexatek DK DVB-T Dongle (Logilink VG0002A)",
+        description: Cow::Borrowed("Dexatek DK DVB-T Dongle (Logilink VG0002A)"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1d19,
         pid: 0x1102,
-        description: "Dexatek DK DVB-T Dongle (MSI DigiVox mini II V3.0)",
+        description: Cow::Borrowed("Dexatek DK DVB-T Dongle (MSI DigiVox mini II V3.0)"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1d19,
         pid: 0x1103,
-        description: "Dexatek Technology Ltd. DK 5217 DVB-T Dongle",
+        description: Cow::Borrowed("Dexatek Technology Ltd. DK 5217 DVB-T Dongle"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1d19,
         pid: 0x1104,
-        description: "MSI DigiVox Micro HD",
+        description: Cow::Borrowed("MSI DigiVox Micro HD"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1f4d,
         pid: 0xa803,
-        description: "Sweex DVB-T USB",
+        description: Cow::Borrowed("Sweex DVB-T USB"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1f4d,
         pid: 0xb803,
-        description: "GTek T803",
+        description: Cow::Borrowed("GTek T803"),
+        tuner: Some("fc0012"),
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1f4d,
         pid: 0xc803,
-        description: "Lifeview LV5TDeluxe",
+        description: Cow::Borrowed("Lifeview LV5TDeluxe"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1f4d,
         pid: 0xd286,
-        description: "MyGica TD312",
+        description: Cow::Borrowed("MyGica TD312"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
     UsbDeviceSignature {
         vid: 0x1f4d,
         pid: 0xd803,
-        description: "PROlectrix DV107669",
+        description: Cow::Borrowed("PROlectrix DV107669"),
+        tuner: None,
+        quirks: DeviceQuirks::NONE,
     },
 ];
 
+/// Settle time used for devices with the `LONG_RESET_DELAY` quirk
+pub const QUIRK_RESET_DELAY: Duration = Duration::from_millis(500);
+
 pub const EEPROM_ADDR: u16 = 0xa0;
 pub const EEPROM_SIZE: usize = 256;
+pub const EEPROM_WRITE_DELAY: Duration = Duration::from_millis(5);
 
 // Blocks
 pub const BLOCK_DEMOD: u16 = 0;
@@ -260,3 +465,6 @@ pub const CTRL_IN: u8 =
 pub const CTRL_OUT: u8 =
     rusb::constants::LIBUSB_ENDPOINT_OUT | rusb::constants::LIBUSB_REQUEST_TYPE_VENDOR;
 pub const CTRL_TIMEOUT: Duration = Duration::from_millis(300);
+
+pub const DEFAULT_INTERFACE: u8 = 0;
+pub const DEFAULT_BULK_ENDPOINT: u8 = 0x81;
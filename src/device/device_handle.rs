@@ -1,14 +1,30 @@
+use std::sync::atomic::AtomicBool;
+#[cfg(feature = "testing")]
+use std::sync::Arc;
 use std::time::Duration;
 
 use crate::error::Result;
 use crate::error::RtlsdrError::RtlsdrErr;
 use rusb::{Context, UsbContext};
 
-use super::KNOWN_DEVICES;
+use super::{known_devices, UsbDeviceSignature};
+#[cfg(feature = "testing")]
+use crate::testing::FaultScript;
 
 #[derive(Debug)]
 pub struct DeviceHandle {
     handle: rusb::DeviceHandle<Context>,
+    signature: UsbDeviceSignature,
+    #[cfg(feature = "testing")]
+    faults: Option<Arc<FaultScript>>,
+}
+
+/// String descriptors of an open device
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsbStrings {
+    pub manufacturer: String,
+    pub product: String,
+    pub serial: String,
 }
 
 #[derive(Debug, Clone)]
@@ -16,48 +32,118 @@ pub struct KnownDevice<T: UsbContext> {
     pub name: String,
     pub serial: String,
     pub device: rusb::Device<T>,
+    pub signature: UsbDeviceSignature,
 }
 
 impl DeviceHandle {
+    fn new(handle: rusb::DeviceHandle<Context>, signature: UsbDeviceSignature) -> Self {
+        DeviceHandle {
+            handle,
+            signature,
+            #[cfg(feature = "testing")]
+            faults: None,
+        }
+    }
+
     pub fn open_by_index(index: usize) -> Result<Self> {
-        let mut context = Context::new()?;
-        let handle = DeviceHandle::open_device_by_index(&mut context, index)?;
-        Ok(DeviceHandle { handle: handle })
+        DeviceHandle::open_by_index_with_context(Context::new()?, index)
     }
 
     pub fn open_by_serial(serial: &str) -> Result<Self> {
-        let mut context = Context::new()?;
-        let handle = DeviceHandle::open_device_by_serial(&mut context, serial)?;
-        Ok(DeviceHandle { handle: handle })
+        DeviceHandle::open_by_serial_with_context(Context::new()?, serial)
+    }
+
+    pub fn open_by_index_with_context(mut context: Context, index: usize) -> Result<Self> {
+        let (handle, signature) = DeviceHandle::open_device_by_index(&mut context, index)?;
+        Ok(DeviceHandle::new(handle, signature))
+    }
+
+    pub fn open_by_serial_with_context(mut context: Context, serial: &str) -> Result<Self> {
+        let (handle, signature) = DeviceHandle::open_device_by_serial(&mut context, serial)?;
+        Ok(DeviceHandle::new(handle, signature))
+    }
+
+    /// Open the device at a USB bus number and device address
+    pub fn open_by_bus_address_with_context(
+        mut context: Context,
+        bus: u8,
+        address: u8,
+    ) -> Result<Self> {
+        let (handle, signature) = DeviceHandle::open_device_where(&mut context, |dev| {
+            dev.bus_number() == bus && dev.address() == address
+        })?;
+        Ok(DeviceHandle::new(handle, signature))
+    }
+
+    /// Open the device plugged into a physical port, given as the port numbers from the
+    /// root hub down (e.g. [1, 4, 2]). Unlike the address, this survives replugging.
+    pub fn open_by_port_path_with_context(
+        mut context: Context,
+        bus: u8,
+        ports: &[u8],
+    ) -> Result<Self> {
+        let (handle, signature) = DeviceHandle::open_device_where(&mut context, |dev| {
+            dev.bus_number() == bus && dev.port_numbers().is_ok_and(|p| p == ports)
+        })?;
+        Ok(DeviceHandle::new(handle, signature))
+    }
+
+    /// Open the first device with the given VID/PID whether or not it is a known
+    /// device. The caller is responsible for it actually being an RTL2832U.
+    pub fn open_unchecked_with_context(context: Context, vid: u16, pid: u16) -> Result<Self> {
+        for device in context.devices()?.iter() {
+            let desc = device.device_descriptor()?;
+            if desc.vendor_id() == vid && desc.product_id() == pid {
+                return DeviceHandle::from_rusb(device.open()?);
+            }
+        }
+        Err(RtlsdrErr(format!(
+            "No device with VID {:04x} PID {:04x} found",
+            vid, pid
+        )))
+    }
+
+    /// Wrap a handle that was opened outside this crate
+    pub fn from_rusb(handle: rusb::DeviceHandle<Context>) -> Result<Self> {
+        let desc = handle.device().device_descriptor()?;
+        let signature = match UsbDeviceSignature::find(desc.vendor_id(), desc.product_id()) {
+            Some(signature) => signature,
+            None => UsbDeviceSignature::unknown(desc.vendor_id(), desc.product_id()),
+        };
+        Ok(DeviceHandle::new(handle, signature))
     }
 
     pub fn filter_known_devices<T: UsbContext>(context: &mut T) -> Result<Vec<KnownDevice<T>>> {
         let devices = context.devices().map(|d| d)?;
+        let signatures = known_devices();
 
         let mut known_devices: Vec<KnownDevice<T>> = Vec::new();
 
         for device in devices.iter() {
             let device_desc = device.device_descriptor().map(|d| d)?;
-            for dev in KNOWN_DEVICES.iter() {
-                if device_desc.vendor_id() == dev.vid && device_desc.product_id() == dev.pid {
-                    let serial_index =
-                        if let Some(serial_index) = device_desc.serial_number_string_index() {
-                            let handle = device.open()?;
-                            handle
-                                .read_string_descriptor_ascii(serial_index)
-                                .unwrap_or_default()
-                        } else {
-                            "".to_string()
-                        };
-
-                    let known_device = KnownDevice {
-                        name: dev.description.to_string(),
-                        serial: serial_index,
-                        device: device.clone(),
+            // `known_devices` lists each VID/PID pair once, so the first match is the
+            // only one
+            if let Some(dev) = signatures.iter().find(|dev| {
+                device_desc.vendor_id() == dev.vid && device_desc.product_id() == dev.pid
+            }) {
+                let serial_index =
+                    if let Some(serial_index) = device_desc.serial_number_string_index() {
+                        let handle = device.open()?;
+                        handle
+                            .read_string_descriptor_ascii(serial_index)
+                            .unwrap_or_default()
+                    } else {
+                        "".to_string()
                     };
 
-                    known_devices.push(known_device);
-                }
+                let known_device = KnownDevice {
+                    name: dev.description.to_string(),
+                    serial: serial_index,
+                    device: device.clone(),
+                    signature: dev.clone(),
+                };
+
+                known_devices.push(known_device);
             }
         }
 
@@ -88,7 +174,7 @@ impl DeviceHandle {
     pub fn open_device_by_index<T: UsbContext>(
         context: &mut T,
         index: usize,
-    ) -> Result<rusb::DeviceHandle<T>> {
+    ) -> Result<(rusb::DeviceHandle<T>, UsbDeviceSignature)> {
         let devices = DeviceHandle::filter_known_devices(context)?;
         DeviceHandle::print_known_devices(devices.clone());
 
@@ -96,7 +182,7 @@ impl DeviceHandle {
         if devices.len() > index {
             let device = devices.get(index).unwrap();
             let handle = device.device.open()?;
-            return Ok(handle);
+            return Ok((handle, device.signature.clone()));
         }
 
         Err(RtlsdrErr(format!("No device found")))
@@ -105,22 +191,64 @@ impl DeviceHandle {
     pub fn open_device_by_serial<T: UsbContext>(
         context: &mut T,
         serial: &str,
-    ) -> Result<rusb::DeviceHandle<T>> {
+    ) -> Result<(rusb::DeviceHandle<T>, UsbDeviceSignature)> {
         let devices = DeviceHandle::filter_known_devices(context)?;
         DeviceHandle::print_known_devices(devices.clone());
 
         for device in devices.iter() {
             if device.serial == serial {
-                return Ok(device.device.open()?);
+                return Ok((device.device.open()?, device.signature.clone()));
             }
         }
 
         Err(RtlsdrErr(format!("No device found")))
     }
 
+    fn open_device_where<T: UsbContext, F: Fn(&rusb::Device<T>) -> bool>(
+        context: &mut T,
+        matches: F,
+    ) -> Result<(rusb::DeviceHandle<T>, UsbDeviceSignature)> {
+        let devices = DeviceHandle::filter_known_devices(context)?;
+        match devices.iter().find(|dev| matches(&dev.device)) {
+            Some(device) => Ok((device.device.open()?, device.signature.clone())),
+            None => Err(RtlsdrErr("No device found".to_string())),
+        }
+    }
+
+    /// Known device table entry this handle was matched against
+    pub fn signature(&self) -> &UsbDeviceSignature {
+        &self.signature
+    }
+
+    /// USB product string, which RTL2832U dongles take from their EEPROM
+    pub fn product_string(&self) -> Option<String> {
+        let desc = self.handle.device().device_descriptor().ok()?;
+        self.handle.read_product_string_ascii(&desc).ok()
+    }
+
+    /// Manufacturer, product and serial string descriptors; empty where the device
+    /// has none
+    pub fn usb_strings(&self) -> Result<UsbStrings> {
+        let desc = self.handle.device().device_descriptor()?;
+        let read = |index: Option<u8>| -> Result<String> {
+            match index {
+                Some(index) => Ok(self.handle.read_string_descriptor_ascii(index)?),
+                None => Ok(String::new()),
+            }
+        };
+        Ok(UsbStrings {
+            manufacturer: read(desc.manufacturer_string_index())?,
+            product: read(desc.product_string_index())?,
+            serial: read(desc.serial_number_string_index())?,
+        })
+    }
+
     pub fn claim_interface(&mut self, iface: u8) -> Result<()> {
         Ok(self.handle.claim_interface(iface)?)
     }
+    pub fn release_interface(&mut self, iface: u8) -> Result<()> {
+        Ok(self.handle.release_interface(iface)?)
+    }
     pub fn reset(&mut self) -> Result<()> {
         Ok(self.handle.reset()?)
     }
@@ -134,6 +262,10 @@ impl DeviceHandle {
         buf: &mut [u8],
         timeout: Duration,
     ) -> Result<usize> {
+        #[cfg(feature = "testing")]
+        if let Some(faults) = &self.faults {
+            faults.control()?;
+        }
         Ok(self
             .handle
             .read_control(request_type, request, value, index, buf, timeout)?)
@@ -148,12 +280,70 @@ impl DeviceHandle {
         buf: &[u8],
         timeout: Duration,
     ) -> Result<usize> {
+        #[cfg(feature = "testing")]
+        if let Some(faults) = &self.faults {
+            faults.control()?;
+        }
         Ok(self
             .handle
             .write_control(request_type, request, value, index, buf, timeout)?)
     }
 
     pub fn read_bulk(&self, endpoint: u8, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        #[cfg(feature = "testing")]
+        if let Some(faults) = &self.faults {
+            return faults.bulk(buf, |buf| {
+                Ok(self.handle.read_bulk(endpoint, buf, timeout)?)
+            });
+        }
         Ok(self.handle.read_bulk(endpoint, buf, timeout)?)
     }
+
+    /// Queued bulk reads, see `transfer::read_async`
+    pub fn read_bulk_async<F: FnMut(&mut [u8]) -> bool>(
+        &self,
+        endpoint: u8,
+        buf_num: usize,
+        buf_len: usize,
+        timeout: Duration,
+        cancel: &AtomicBool,
+        callback: F,
+    ) -> Result<()> {
+        #[cfg(feature = "testing")]
+        if let Some(faults) = &self.faults {
+            return faults.bulk_async(
+                |callback| {
+                    super::transfer::read_async(
+                        &self.handle,
+                        endpoint,
+                        buf_num,
+                        buf_len,
+                        timeout,
+                        cancel,
+                        callback,
+                    )
+                },
+                callback,
+            );
+        }
+        super::transfer::read_async(
+            &self.handle,
+            endpoint,
+            buf_num,
+            buf_len,
+            timeout,
+            cancel,
+            callback,
+        )
+    }
+
+    /// Run transfers through `script`, see the `testing` module
+    #[cfg(feature = "testing")]
+    pub fn set_fault_script(&mut self, script: Option<Arc<FaultScript>>) {
+        self.faults = script;
+    }
+
+    pub fn context(&self) -> &Context {
+        self.handle.context()
+    }
 }
@@ -0,0 +1,209 @@
+//! Description of attached devices and where they sit on the USB bus.
+use rusb::{Speed, UsbContext};
+
+use super::device_handle::KnownDevice;
+
+// Bulk throughput a USB 2.0 link sustains in practice, well under the 60 MB/s signalling rate
+const USB2_USABLE_BYTES_PER_SEC: u64 = 35_000_000;
+// Same for a full speed (USB 1.1) link
+pub(crate) const USB1_USABLE_BYTES_PER_SEC: u64 = 1_000_000;
+
+/// A hub between a device and its root port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HubInfo {
+    pub bus: u8,
+    pub address: u8,
+    pub speed: Speed,
+}
+
+/// A supported device attached to the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// Index used by `RtlSdr::open_by_index`
+    pub index: usize,
+    pub name: String,
+    pub serial: String,
+    pub vid: u16,
+    pub pid: u16,
+    pub bus: u8,
+    pub address: u8,
+    /// Ports from the root hub down to the device, e.g. [1, 4, 2]
+    pub port_path: Vec<u8>,
+    /// Hubs between the device and the root hub, nearest first
+    pub hub_chain: Vec<HubInfo>,
+    /// Negotiated link speed
+    pub speed: Speed,
+    /// Max packet size of the sample endpoint (the default 0x81, or the one set with
+    /// `OpenOptions::bulk_endpoint` for `OpenOptions::enumerate`), if the descriptor
+    /// could be read
+    pub max_packet_size: Option<u16>,
+}
+
+impl DeviceInfo {
+    pub fn from_known<T: UsbContext>(
+        index: usize,
+        known: &KnownDevice<T>,
+        bulk_endpoint: u8,
+    ) -> DeviceInfo {
+        let device = &known.device;
+        let mut hub_chain = Vec::new();
+        let mut parent = device.get_parent();
+        while let Some(hub) = parent {
+            // The root hub has no parent and isn't a shared link
+            if hub.get_parent().is_none() {
+                break;
+            }
+            hub_chain.push(HubInfo {
+                bus: hub.bus_number(),
+                address: hub.address(),
+                speed: hub.speed(),
+            });
+            parent = hub.get_parent();
+        }
+        let max_packet_size = device.active_config_descriptor().ok().and_then(|config| {
+            config
+                .interfaces()
+                .flat_map(|iface| iface.descriptors())
+                .flat_map(|desc| desc.endpoint_descriptors())
+                .find(|ep| ep.address() == bulk_endpoint)
+                .map(|ep| ep.max_packet_size())
+        });
+        DeviceInfo {
+            index,
+            name: known.name.clone(),
+            serial: known.serial.clone(),
+            vid: known.signature.vid,
+            pid: known.signature.pid,
+            bus: device.bus_number(),
+            address: device.address(),
+            port_path: device.port_numbers().unwrap_or_default(),
+            hub_chain,
+            speed: device.speed(),
+            max_packet_size,
+        }
+    }
+}
+
+/// Check whether `devices`, each streaming at `sample_rate`, fit through the USB links
+/// they share. Returns (and logs) a warning for each device on a link slower than high
+/// speed, for each hub whose dongles together need more than USB 2.0 sustains, and
+/// for each bus (root hub) where that holds for dongles plugged into different ports.
+pub fn usb_bandwidth_warnings(devices: &[DeviceInfo], sample_rate: u32) -> Vec<String> {
+    let per_device = sample_rate as u64 * 2;
+    let mut warnings = Vec::new();
+    for dev in devices {
+        if matches!(dev.speed, Speed::Low | Speed::Full) && per_device > USB1_USABLE_BYTES_PER_SEC {
+            warnings.push(format!(
+                "Device {} ({}) is connected at {:?} speed and can't sustain {} S/s",
+                dev.index, dev.name, dev.speed, sample_rate
+            ));
+        }
+    }
+    // Every hub on a device's path is shared by all dongles below it
+    let mut hubs: Vec<(HubInfo, Vec<usize>)> = Vec::new();
+    for dev in devices {
+        for hub in &dev.hub_chain {
+            match hubs
+                .iter_mut()
+                .find(|(h, _)| h.bus == hub.bus && h.address == hub.address)
+            {
+                Some((_, members)) => members.push(dev.index),
+                None => hubs.push((*hub, vec![dev.index])),
+            }
+        }
+    }
+    let mut overloaded: Vec<Vec<usize>> = Vec::new();
+    for (hub, members) in hubs {
+        let total = per_device * members.len() as u64;
+        let limit = match hub.speed {
+            Speed::Low | Speed::Full => USB1_USABLE_BYTES_PER_SEC,
+            _ => USB2_USABLE_BYTES_PER_SEC,
+        };
+        if members.len() > 1 && total > limit {
+            warnings.push(format!(
+                "Devices {:?} share the hub at bus {} address {}: {} MB/s needed, about {} MB/s available",
+                members,
+                hub.bus,
+                hub.address,
+                total / 1_000_000,
+                limit / 1_000_000
+            ));
+            overloaded.push(members);
+        }
+    }
+    // Dongles on root ports have no hub in their chain but still share the bus
+    let mut buses: Vec<(u8, Vec<usize>)> = Vec::new();
+    for dev in devices {
+        match buses.iter_mut().find(|(bus, _)| *bus == dev.bus) {
+            Some((_, members)) => members.push(dev.index),
+            None => buses.push((dev.bus, vec![dev.index])),
+        }
+    }
+    for (bus, members) in buses {
+        let total = per_device * members.len() as u64;
+        // Already reported for a hub holding all of them
+        if members.len() > 1 && total > USB2_USABLE_BYTES_PER_SEC && !overloaded.contains(&members)
+        {
+            warnings.push(format!(
+                "Devices {:?} share bus {}: {} MB/s needed, about {} MB/s available",
+                members,
+                bus,
+                total / 1_000_000,
+                USB2_USABLE_BYTES_PER_SEC / 1_000_000
+            ));
+        }
+    }
+    for warning in &warnings {
+        warn!("{}", warning);
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dongle(index: usize, bus: u8, hub_chain: Vec<HubInfo>) -> DeviceInfo {
+        DeviceInfo {
+            index,
+            name: "Generic RTL2832U".to_string(),
+            serial: String::new(),
+            vid: 0x0bda,
+            pid: 0x2838,
+            bus,
+            address: index as u8 + 2,
+            port_path: vec![index as u8 + 1],
+            hub_chain,
+            speed: Speed::High,
+            max_packet_size: Some(512),
+        }
+    }
+
+    #[test]
+    fn root_port_dongles_share_their_bus() {
+        // 3.2 MS/s is 6.4 MB/s each: six fit a bus, seven don't
+        let crowded: Vec<_> = (0..7).map(|i| dongle(i, 1, Vec::new())).collect();
+        let warnings = usb_bandwidth_warnings(&crowded, 3_200_000);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("share bus 1"));
+
+        // The same dongles split over two buses are fine
+        let split: Vec<_> = (0..7)
+            .map(|i| dongle(i, 1 + i as u8 % 2, Vec::new()))
+            .collect();
+        assert!(usb_bandwidth_warnings(&split, 3_200_000).is_empty());
+    }
+
+    #[test]
+    fn hub_overload_is_not_repeated_for_its_bus() {
+        let hub = HubInfo {
+            bus: 1,
+            address: 9,
+            speed: Speed::High,
+        };
+        let devices: Vec<_> = (0..7).map(|i| dongle(i, 1, vec![hub])).collect();
+        let warnings = usb_bandwidth_warnings(&devices, 3_200_000);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("share the hub at bus 1 address 9"));
+    }
+}
@@ -1,6 +1,14 @@
 pub mod constants;
 pub use constants::*;
 pub mod device_handle;
+pub mod info;
+pub mod quirks;
+pub mod sequence;
+pub mod transfer;
+pub use info::{usb_bandwidth_warnings, DeviceInfo, HubInfo};
+pub use quirks::{register_quirks, AppliedQuirks, DemodReg, QuirkEntry};
+pub use sequence::{set_sequence, RegSpace, RegWrite, Sequence};
+pub use transfer::AsyncCanceller;
 // #[cfg(test)]
 // mod mock_device_handle;
 
@@ -10,30 +18,117 @@ use device_handle::DeviceHandle;
 // use mock_device_handle::MockDeviceHandle as DeviceHandle;
 
 use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::platform;
 use byteorder::{ByteOrder, LittleEndian};
 /// Low-level io functions for interfacing with rusb(libusb)
 use log::{error, info};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use std::time::Duration;
 
 // #[cfg(test)]
 // mod device_test;
 
+/// Interface, endpoint and timeouts used to talk to the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsbConfig {
+    pub interface: u8,
+    pub bulk_endpoint: u8,
+    pub ctrl_timeout: Duration,
+    /// Zero waits forever
+    pub bulk_timeout: Duration,
+}
+
+impl Default for UsbConfig {
+    fn default() -> Self {
+        UsbConfig {
+            interface: DEFAULT_INTERFACE,
+            bulk_endpoint: DEFAULT_BULK_ENDPOINT,
+            ctrl_timeout: CTRL_TIMEOUT,
+            bulk_timeout: Duration::ZERO,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Device {
     pub handle: DeviceHandle,
+    pub usb: UsbConfig,
+    pub quirks: AppliedQuirks,
+    // Set by an `AsyncCanceller` to stop `read_async`
+    async_cancel: Arc<AtomicBool>,
 }
 
 impl Device {
+    fn with_handle(handle: DeviceHandle) -> Device {
+        let product = handle.product_string();
+        let quirks = quirks::resolve(handle.signature(), product.as_deref());
+        Device {
+            handle,
+            usb: UsbConfig::default(),
+            quirks,
+            async_cancel: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
     pub fn new_by_index(index: usize) -> Result<Device> {
-        Ok(Device {
-            handle: DeviceHandle::open_by_index(index)?,
-        })
+        Ok(Device::with_handle(DeviceHandle::open_by_index(index)?))
     }
 
     pub fn new_by_serial(serial: &str) -> Result<Device> {
-        Ok(Device {
-            handle: DeviceHandle::open_by_serial(serial)?,
-        })
+        Ok(Device::with_handle(DeviceHandle::open_by_serial(serial)?))
+    }
+
+    pub fn new_by_index_with_context(context: rusb::Context, index: usize) -> Result<Device> {
+        Ok(Device::with_handle(
+            DeviceHandle::open_by_index_with_context(context, index)?,
+        ))
+    }
+
+    pub fn new_by_serial_with_context(context: rusb::Context, serial: &str) -> Result<Device> {
+        Ok(Device::with_handle(
+            DeviceHandle::open_by_serial_with_context(context, serial)?,
+        ))
+    }
+
+    pub fn new_by_bus_address_with_context(
+        context: rusb::Context,
+        bus: u8,
+        address: u8,
+    ) -> Result<Device> {
+        Ok(Device::with_handle(
+            DeviceHandle::open_by_bus_address_with_context(context, bus, address)?,
+        ))
+    }
+
+    pub fn new_by_port_path_with_context(
+        context: rusb::Context,
+        bus: u8,
+        ports: &[u8],
+    ) -> Result<Device> {
+        Ok(Device::with_handle(
+            DeviceHandle::open_by_port_path_with_context(context, bus, ports)?,
+        ))
+    }
+
+    pub fn new_unchecked_with_context(
+        context: rusb::Context,
+        vid: u16,
+        pid: u16,
+    ) -> Result<Device> {
+        Ok(Device::with_handle(
+            DeviceHandle::open_unchecked_with_context(context, vid, pid)?,
+        ))
+    }
+
+    /// Add a VID/PID pair to the supported devices, see `constants::register_known_device`
+    pub fn register_known_device(vid: u16, pid: u16, description: &str) {
+        register_known_device(vid, pid, description);
+    }
+
+    pub fn from_rusb_handle(handle: rusb::DeviceHandle<rusb::Context>) -> Result<Device> {
+        Ok(Device::with_handle(DeviceHandle::from_rusb(handle)?))
     }
 
     pub fn list_and_print_known_devices() -> Result<()> {
@@ -42,8 +137,46 @@ impl Device {
         Ok(())
     }
 
+    /// Supported devices attached to the host, in `open_by_index` order
+    pub fn enumerate() -> Result<Vec<DeviceInfo>> {
+        Self::enumerate_with(&UsbConfig::default())
+    }
+
+    /// `enumerate`, describing the sample endpoint `usb` selects
+    pub fn enumerate_with(usb: &UsbConfig) -> Result<Vec<DeviceInfo>> {
+        let mut context = rusb::Context::new()?;
+        let devices = DeviceHandle::filter_known_devices(&mut context)?;
+        Ok(devices
+            .iter()
+            .enumerate()
+            .map(|(index, dev)| DeviceInfo::from_known(index, dev, usb.bulk_endpoint))
+            .collect())
+    }
+
+    /// Index `open_by_index` would use for the device with this serial number
+    pub fn index_by_serial(serial: &str) -> Result<usize> {
+        let mut context = rusb::Context::new()?;
+        DeviceHandle::filter_known_devices(&mut context)?
+            .iter()
+            .position(|dev| dev.serial == serial)
+            .ok_or_else(|| RtlsdrErr(format!("No device with serial {}", serial)))
+    }
+
+    /// Known device table entry for this device
+    pub fn signature(&self) -> &UsbDeviceSignature {
+        self.handle.signature()
+    }
+
     pub fn claim_interface(&mut self, iface: u8) -> Result<()> {
-        Ok(self.handle.claim_interface(iface)?)
+        self.handle.claim_interface(iface)
+    }
+
+    pub fn release_interface(&mut self, iface: u8) -> Result<()> {
+        self.handle.release_interface(iface)
+    }
+
+    pub fn reset(&mut self) -> Result<()> {
+        self.handle.reset()
     }
 
     pub fn test_write(&mut self) -> Result<()> {
@@ -52,6 +185,10 @@ impl Device {
         if len == 0 {
             info!("Resetting device...");
             self.handle.reset()?;
+            if self.quirks.quirks.contains(DeviceQuirks::LONG_RESET_DELAY) {
+                info!("Waiting {:?} for device to settle", self.quirks.reset_delay);
+                platform::sleep(self.quirks.reset_delay);
+            }
         }
         Ok(())
     }
@@ -67,8 +204,14 @@ impl Device {
         assert!(len == 1 || len == 2);
         let mut data: [u8; 2] = [0, 0];
         let index: u16 = block << 8;
-        self.handle
-            .read_control(CTRL_IN, 0, addr, index, &mut data[..len], CTRL_TIMEOUT)?;
+        self.handle.read_control(
+            CTRL_IN,
+            0,
+            addr,
+            index,
+            &mut data[..len],
+            self.usb.ctrl_timeout,
+        )?;
         // Read registers as little endian, but write as big; not sure why
         Ok(LittleEndian::read_u16(&data))
     }
@@ -80,9 +223,8 @@ impl Device {
         let data_slice = if len == 1 { &data[1..2] } else { &data };
         let index = (block << 8) | 0x10;
         // info!("write_reg addr: {:x} index: {:x} data: {:x?} data slice: {}", addr, index, data, data_slice.len());
-        Ok(self
-            .handle
-            .write_control(CTRL_OUT, 0, addr, index, data_slice, CTRL_TIMEOUT)?)
+        self.handle
+            .write_control(CTRL_OUT, 0, addr, index, data_slice, self.usb.ctrl_timeout)
     }
 
     /// Only supports u8 reads
@@ -95,7 +237,7 @@ impl Device {
             (addr << 8) | 0x20,
             index,
             &mut data,
-            CTRL_TIMEOUT,
+            self.usb.ctrl_timeout,
         ) {
             Ok(n) => {
                 // info!("demod_read_reg got {} bytes: [{:#02x}, {:#02x}] value: {:x}", n, data[0], data[1], BigEndian::read_u16(&data));
@@ -121,20 +263,23 @@ impl Device {
         let data: [u8; 2] = val.to_be_bytes();
         let data_slice = if len == 1 { &data[1..2] } else { &data };
 
-        let bytes =
-            match self
-                .handle
-                .write_control(CTRL_OUT, 0, addr, index, data_slice, CTRL_TIMEOUT)
-            {
-                Ok(n) => n,
-                Err(e) => {
-                    error!(
-                        "demod_write_reg failed: {} page: {:#02x} addr: {:#02x} val: {:#02x}",
-                        e, page, addr, val
-                    );
-                    0
-                }
-            };
+        let bytes = match self.handle.write_control(
+            CTRL_OUT,
+            0,
+            addr,
+            index,
+            data_slice,
+            self.usb.ctrl_timeout,
+        ) {
+            Ok(n) => n,
+            Err(e) => {
+                error!(
+                    "demod_write_reg failed: {} page: {:#02x} addr: {:#02x} val: {:#02x}",
+                    e, page, addr, val
+                );
+                0
+            }
+        };
 
         self.demod_read_reg(0x0a, 0x1)?;
 
@@ -142,18 +287,72 @@ impl Device {
     }
 
     pub fn bulk_transfer(&self, buf: &mut [u8]) -> Result<usize> {
-        Ok(self.handle.read_bulk(0x81, buf, Duration::ZERO)?)
+        self.handle
+            .read_bulk(self.usb.bulk_endpoint, buf, self.usb.bulk_timeout)
+    }
+
+    /// Read with `buf_num` transfers of `buf_len` bytes in flight (0 for
+    /// `transfer::DEFAULT_BUF_NUM`), handing each filled buffer to `callback` until it
+    /// returns `false` or the read is cancelled through `async_canceller`
+    pub fn read_async<F: FnMut(&mut [u8]) -> bool>(
+        &self,
+        callback: F,
+        buf_num: usize,
+        buf_len: usize,
+    ) -> Result<()> {
+        self.handle.read_bulk_async(
+            self.usb.bulk_endpoint,
+            buf_num,
+            buf_len,
+            self.usb.bulk_timeout,
+            &self.async_cancel,
+            callback,
+        )
+    }
+
+    /// Handle that stops `read_async` from another thread
+    pub fn async_canceller(&self) -> AsyncCanceller {
+        AsyncCanceller::new(self.async_cancel.clone(), self.handle.context().clone())
     }
 
-    pub fn read_eeprom(&self, data: &[u8], offset: u8, len: usize) -> Result<usize> {
+    pub fn read_eeprom(&self, data: &mut [u8], offset: u8, len: usize) -> Result<usize> {
         assert!((len + offset as usize) <= EEPROM_SIZE);
         self.write_array(BLOCK_IIC, EEPROM_ADDR, &[offset], 1)?;
-        for i in 0..len {
-            self.read_array(BLOCK_IIC, EEPROM_ADDR, &mut [data[i]], 1)?;
+        for byte in data.iter_mut().take(len) {
+            let mut val = [0_u8];
+            self.read_array(BLOCK_IIC, EEPROM_ADDR, &mut val, 1)?;
+            *byte = val[0];
         }
         Ok(len)
     }
 
+    /// Write bytes to the EEPROM, skipping bytes that already hold the right value
+    pub fn write_eeprom(&self, data: &[u8], offset: u8) -> Result<usize> {
+        if data.len() + offset as usize > EEPROM_SIZE {
+            return Err(RtlsdrErr(format!(
+                "EEPROM write of {} bytes at {:#04x} exceeds EEPROM size",
+                data.len(),
+                offset
+            )));
+        }
+        for (i, val) in data.iter().enumerate() {
+            let addr = offset + i as u8;
+            let mut current = [0_u8];
+            self.write_array(BLOCK_IIC, EEPROM_ADDR, &[addr], 1)?;
+            self.read_array(BLOCK_IIC, EEPROM_ADDR, &mut current, 1)?;
+            if current[0] == *val {
+                continue;
+            }
+            let len = self.write_array(BLOCK_IIC, EEPROM_ADDR, &[addr, *val], 2)?;
+            if len != 2 {
+                return Err(RtlsdrErr(format!("EEPROM write failed at {:#04x}", addr)));
+            }
+            // Some EEPROMs (e.g. ATC 240LC02) need a delay between writes
+            platform::sleep(EEPROM_WRITE_DELAY);
+        }
+        Ok(data.len())
+    }
+
     pub fn i2c_read_reg(&self, i2c_addr: u8, reg: u8) -> Result<u8> {
         let addr: u16 = i2c_addr.into();
         let reg: [u8; 1] = [reg];
@@ -169,7 +368,7 @@ impl Device {
     }
 
     pub fn i2c_write(&self, i2c_addr: u16, buffer: &[u8]) -> Result<usize> {
-        Ok(self.write_array(BLOCK_IIC, i2c_addr, buffer, buffer.len())?)
+        self.write_array(BLOCK_IIC, i2c_addr, buffer, buffer.len())
     }
 
     pub fn i2c_read(&self, i2c_addr: u16, buffer: &mut [u8], len: u8) -> Result<usize> {
@@ -178,15 +377,13 @@ impl Device {
 
     pub fn read_array(&self, block: u16, addr: u16, arr: &mut [u8], _len: u8) -> Result<usize> {
         let index: u16 = block << 8;
-        Ok(self
-            .handle
-            .read_control(CTRL_IN, 0, addr, index, arr, CTRL_TIMEOUT)?)
+        self.handle
+            .read_control(CTRL_IN, 0, addr, index, arr, self.usb.ctrl_timeout)
     }
 
     pub fn write_array(&self, block: u16, addr: u16, arr: &[u8], len: usize) -> Result<usize> {
         let index: u16 = (block << 8) | 0x10;
-        Ok(self
-            .handle
-            .write_control(CTRL_OUT, 0, addr, index, &arr[..len], CTRL_TIMEOUT)?)
+        self.handle
+            .write_control(CTRL_OUT, 0, addr, index, &arr[..len], self.usb.ctrl_timeout)
     }
 }
@@ -0,0 +1,150 @@
+//! Runtime-extensible workarounds for problematic clone dongles.
+//!
+//! The `quirks` column of `KNOWN_DEVICES` covers whole VID/PID pairs. `BUILTIN_QUIRKS`
+//! adds flags for boards recognized by the product string in their EEPROM (many share
+//! the generic 0bda:2838 ids). Entries registered at runtime can do the same, stretch
+//! the reset delay, and write extra demod registers after the standard baseband init.
+use std::sync::RwLock;
+use std::time::Duration;
+
+use super::{DeviceQuirks, UsbDeviceSignature, QUIRK_RESET_DELAY};
+
+/// Demod register written after baseband init, as in `Device::demod_write_reg`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DemodReg {
+    pub page: u16,
+    pub addr: u16,
+    pub val: u16,
+    /// 1 or 2 bytes
+    pub len: usize,
+}
+
+/// Workarounds for the devices an entry matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuirkEntry {
+    pub vid: u16,
+    pub pid: u16,
+    /// Only match devices whose USB product string (stored in the EEPROM) is this
+    pub product: Option<String>,
+    pub quirks: DeviceQuirks,
+    /// Settle time after a reset for `LONG_RESET_DELAY`, instead of `QUIRK_RESET_DELAY`
+    pub reset_delay: Option<Duration>,
+    pub demod_regs: Vec<DemodReg>,
+}
+
+impl QuirkEntry {
+    pub fn new(vid: u16, pid: u16, quirks: DeviceQuirks) -> Self {
+        QuirkEntry {
+            vid,
+            pid,
+            product: None,
+            quirks,
+            reset_delay: None,
+            demod_regs: Vec::new(),
+        }
+    }
+
+    fn matches(&self, vid: u16, pid: u16, product: Option<&str>) -> bool {
+        self.vid == vid
+            && self.pid == pid
+            && match &self.product {
+                Some(expected) => product == Some(expected.as_str()),
+                None => true,
+            }
+    }
+}
+
+/// Quirks in effect for one open device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedQuirks {
+    pub quirks: DeviceQuirks,
+    pub reset_delay: Duration,
+    pub demod_regs: Vec<DemodReg>,
+}
+
+impl Default for AppliedQuirks {
+    fn default() -> Self {
+        AppliedQuirks {
+            quirks: DeviceQuirks::NONE,
+            reset_delay: QUIRK_RESET_DELAY,
+            demod_regs: Vec::new(),
+        }
+    }
+}
+
+/// Shipped entries for boards identified by product string: (vid, pid, product, quirks)
+pub const BUILTIN_QUIRKS: &[(u16, u16, &str, DeviceQuirks)] = &[
+    (0x0bda, 0x2838, "Blog V3", DeviceQuirks::BIAS_TEE),
+    (0x0bda, 0x2838, "Blog V4", DeviceQuirks::BIAS_TEE),
+];
+
+static REGISTERED: RwLock<Vec<QuirkEntry>> = RwLock::new(Vec::new());
+
+/// Add an entry, applied to devices opened from now on. Later entries extend earlier
+/// ones: flags and registers accumulate, the last reset delay wins.
+pub fn register_quirks(entry: QuirkEntry) {
+    REGISTERED
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(entry);
+}
+
+/// Entries added with `register_quirks`
+pub fn registered_quirks() -> Vec<QuirkEntry> {
+    REGISTERED.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Combine the known device table entry with any matching built-in and registered
+/// entries
+pub fn resolve(signature: &UsbDeviceSignature, product: Option<&str>) -> AppliedQuirks {
+    let mut applied = AppliedQuirks {
+        quirks: signature.quirks,
+        ..AppliedQuirks::default()
+    };
+    for (_, _, _, quirks) in BUILTIN_QUIRKS.iter().filter(|(vid, pid, name, _)| {
+        *vid == signature.vid && *pid == signature.pid && product == Some(*name)
+    }) {
+        applied.quirks = applied.quirks | *quirks;
+    }
+    let registered = REGISTERED.read().unwrap_or_else(|e| e.into_inner());
+    for entry in registered
+        .iter()
+        .filter(|entry| entry.matches(signature.vid, signature.pid, product))
+    {
+        applied.quirks = applied.quirks | entry.quirks;
+        if let Some(delay) = entry.reset_delay {
+            applied.reset_delay = delay;
+        }
+        applied.demod_regs.extend_from_slice(&entry.demod_regs);
+    }
+    applied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::constants::KNOWN_DEVICES;
+
+    fn generic_oem() -> UsbDeviceSignature {
+        UsbDeviceSignature::find(0x0bda, 0x2838).unwrap()
+    }
+
+    #[test]
+    fn blog_dongles_get_bias_tee() {
+        let applied = resolve(&generic_oem(), Some("Blog V3"));
+        assert!(applied.quirks.contains(DeviceQuirks::BIAS_TEE));
+        assert!(applied.quirks.contains(DeviceQuirks::LONG_RESET_DELAY));
+
+        let applied = resolve(&generic_oem(), Some("RTL2838UHIDIR"));
+        assert!(!applied.quirks.contains(DeviceQuirks::BIAS_TEE));
+    }
+
+    #[test]
+    fn builtin_entries_name_known_devices() {
+        for (vid, pid, _, _) in BUILTIN_QUIRKS {
+            assert!(KNOWN_DEVICES
+                .iter()
+                .any(|dev| dev.vid == *vid && dev.pid == *pid));
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Register sequences the driver writes during init, tuning and standby, kept as data
+//! so they can be inspected, extended or replaced at runtime to try out register
+//! tweaks without forking the crate. The R820T's band-dependent tuning values (tracking
+//! filter, RF mux, crystal load) come from its band table rather than a sequence.
+//!
+//! A replacement applies to devices opened from then on. To extend a sequence, take
+//! the current one with `sequence`, push the extra writes and pass it back to
+//! `set_sequence`.
+use std::sync::RwLock;
+use std::time::Duration;
+
+use super::{
+    Device, BLOCK_SYS, BLOCK_USB, DEMOD_CTL, DEMOD_CTL_1, USB_EPA_CTL, USB_EPA_MAXPKT, USB_SYSCTL,
+};
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::platform;
+use crate::tuners::r820t;
+
+/// Register space a `RegWrite` goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegSpace {
+    /// One of the `BLOCK_*` register blocks, as in `Device::write_reg`
+    Block(u16),
+    /// A demod register page, as in `Device::demod_write_reg`
+    Demod(u16),
+    /// A register of the tuner chip, written over I2C by the tuner driver so its
+    /// register cache stays in sync. Only valid in the tuner's own sequences.
+    Tuner,
+}
+
+/// One step of a register sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegWrite {
+    pub space: RegSpace,
+    pub addr: u16,
+    pub val: u16,
+    /// Bits of `val` to write; the others keep their current value. `FULL_MASK`
+    /// writes the whole register without reading it first.
+    pub mask: u16,
+    /// 1 or 2 bytes
+    pub len: usize,
+    /// Wait after the write
+    pub delay: Duration,
+}
+
+/// Mask of a `RegWrite` that replaces the whole register
+pub const FULL_MASK: u16 = 0xffff;
+
+impl RegWrite {
+    pub const fn block(block: u16, addr: u16, val: u16, len: usize) -> Self {
+        RegWrite {
+            space: RegSpace::Block(block),
+            addr,
+            val,
+            mask: FULL_MASK,
+            len,
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub const fn demod(page: u16, addr: u16, val: u16, len: usize) -> Self {
+        RegWrite {
+            space: RegSpace::Demod(page),
+            addr,
+            val,
+            mask: FULL_MASK,
+            len,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// 8-bit tuner register
+    pub const fn tuner(reg: u16, val: u16) -> Self {
+        RegWrite {
+            space: RegSpace::Tuner,
+            addr: reg,
+            val,
+            mask: FULL_MASK,
+            len: 1,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Only write the bits in `mask`
+    pub const fn masked(mut self, mask: u16) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    /// Wait for `delay` after the write
+    pub const fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// Sequences that can be replaced with `set_sequence`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sequence {
+    /// USB setup, demod power-on and reset, and cleared DDC and IF registers, written
+    /// after the interface is claimed. The FIR filter is loaded right after it.
+    Baseband,
+    /// The demod's SDR mode, written once the FIR filter is loaded. The quirk
+    /// registers follow it.
+    SdrMode,
+    /// Demod setup for low-IF tuners (R82XX), written after the tuner is detected
+    /// and again when leaving zero-IF mode
+    LowIf,
+    /// R820T register file loaded at tuner init, before the filter calibration
+    R820tInit,
+    /// R820T writes made on every retune after the band-dependent ones
+    R820tTune,
+    /// R820T writes that put the tuner in standby when the device is closed
+    R820tStandby,
+}
+
+const BASEBAND: &[RegWrite] = &[
+    // Initialize USB
+    RegWrite::block(BLOCK_USB, USB_SYSCTL, 0x09, 1),
+    RegWrite::block(BLOCK_USB, USB_EPA_MAXPKT, 0x0002, 2),
+    RegWrite::block(BLOCK_USB, USB_EPA_CTL, 0x1002, 2),
+    // Power-on demod
+    RegWrite::block(BLOCK_SYS, DEMOD_CTL_1, 0x22, 1),
+    RegWrite::block(BLOCK_SYS, DEMOD_CTL, 0xe8, 1),
+    // Reset demod (bit 3, soft_rst)
+    RegWrite::demod(1, 0x01, 0x14, 1),
+    RegWrite::demod(1, 0x01, 0x10, 1),
+    // Disable spectrum inversion and adjust channel rejection
+    RegWrite::demod(1, 0x15, 0x00, 1),
+    RegWrite::demod(1, 0x16, 0x00, 2),
+    // Clear DDC shift and IF registers
+    RegWrite::demod(1, 0x16, 0x00, 1),
+    RegWrite::demod(1, 0x17, 0x00, 1),
+    RegWrite::demod(1, 0x18, 0x00, 1),
+    RegWrite::demod(1, 0x19, 0x00, 1),
+    RegWrite::demod(1, 0x1a, 0x00, 1),
+];
+
+const SDR_MODE: &[RegWrite] = &[
+    // Enable SDR mode, disable DAGC (bit 5)
+    RegWrite::demod(0, 0x19, 0x05, 1),
+    // Init FSM state-holding register
+    RegWrite::demod(1, 0x93, 0xf0, 1),
+    RegWrite::demod(1, 0x94, 0x0f, 1),
+    // Disable AGC (en_dagc, bit 0) (seems to have no effect)
+    RegWrite::demod(1, 0x11, 0x00, 1),
+    // Disable RF and IF AGC loop
+    RegWrite::demod(1, 0x04, 0x00, 1),
+    // Disable PID filter
+    RegWrite::demod(0, 0x61, 0x60, 1),
+    // opt_adc_iq = 0, default ADC_I/ADC_Q datapath
+    RegWrite::demod(0, 0x06, 0x80, 1),
+    // Enable Zero-IF mode, DC cancellation, and IQ estimation/compensation
+    RegWrite::demod(1, 0xb1, 0x1b, 1),
+    // Disable 4.096 MHz clock output on pin TP_CK0
+    RegWrite::demod(0, 0x0d, 0x83, 1),
+];
+
+const LOW_IF: &[RegWrite] = &[
+    // Only enable in-phase ADC input
+    RegWrite::demod(0, 0x08, 0x4d, 1),
+    // Disable Zero-IF mode
+    RegWrite::demod(1, 0xb1, 0x1a, 1),
+];
+
+impl Sequence {
+    /// The sequence as shipped, ignoring replacements
+    pub fn default_writes(self) -> &'static [RegWrite] {
+        match self {
+            Sequence::Baseband => BASEBAND,
+            Sequence::SdrMode => SDR_MODE,
+            Sequence::LowIf => LOW_IF,
+            Sequence::R820tInit => r820t::INIT_SEQUENCE,
+            Sequence::R820tTune => r820t::TUNE_SEQUENCE,
+            Sequence::R820tStandby => r820t::STANDBY_SEQUENCE,
+        }
+    }
+}
+
+static OVERRIDES: RwLock<Vec<(Sequence, Vec<RegWrite>)>> = RwLock::new(Vec::new());
+
+/// Replace `seq` for devices opened from now on; `None` restores the default
+pub fn set_sequence(seq: Sequence, writes: Option<Vec<RegWrite>>) {
+    let mut overrides = OVERRIDES.write().unwrap_or_else(|e| e.into_inner());
+    overrides.retain(|(s, _)| *s != seq);
+    if let Some(writes) = writes {
+        info!(
+            "Replacing {:?} register sequence ({} writes)",
+            seq,
+            writes.len()
+        );
+        overrides.push((seq, writes));
+    }
+}
+
+/// The writes `seq` currently consists of, replaced or not
+pub fn sequence(seq: Sequence) -> Vec<RegWrite> {
+    OVERRIDES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|(s, _)| *s == seq)
+        .map(|(_, writes)| writes.clone())
+        .unwrap_or_else(|| seq.default_writes().to_vec())
+}
+
+impl Device {
+    /// Write `writes` in order
+    pub fn write_sequence(&self, writes: &[RegWrite]) -> Result<()> {
+        for write in writes {
+            let val = if write.mask == FULL_MASK {
+                write.val
+            } else {
+                let current = match write.space {
+                    RegSpace::Block(block) => self.read_reg(block, write.addr, write.len)?,
+                    RegSpace::Demod(_) if write.len != 1 => {
+                        return Err(RtlsdrErr(format!(
+                            "Masked write to 2-byte demod register {:#04x} isn't supported",
+                            write.addr
+                        )));
+                    }
+                    RegSpace::Demod(page) => self.demod_read_reg(page, write.addr)?,
+                    RegSpace::Tuner => return Err(tuner_write_err(write)),
+                };
+                (current & !write.mask) | (write.val & write.mask)
+            };
+            match write.space {
+                RegSpace::Block(block) => self.write_reg(block, write.addr, val, write.len)?,
+                RegSpace::Demod(page) => self.demod_write_reg(page, write.addr, val, write.len)?,
+                RegSpace::Tuner => return Err(tuner_write_err(write)),
+            };
+            if !write.delay.is_zero() {
+                platform::sleep(write.delay);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn tuner_write_err(write: &RegWrite) -> crate::error::RtlsdrError {
+    RtlsdrErr(format!(
+        "Tuner register {:#04x} can only be written from a tuner sequence",
+        write.addr
+    ))
+}
@@ -0,0 +1,263 @@
+//! Asynchronous bulk reads with several transfers queued at once, so the device always
+//! has a buffer to fill while the previous one is being processed. A single blocking
+//! `read_bulk` leaves the endpoint idle between calls, and at higher sample rates the
+//! FIFO overflows as soon as processing stalls. rusb only wraps libusb's synchronous
+//! API, so this goes through libusb1-sys.
+use std::collections::VecDeque;
+use std::ffi::{c_int, c_uint, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use libusb1_sys::constants::{
+    LIBUSB_ERROR_INTERRUPTED, LIBUSB_TRANSFER_CANCELLED, LIBUSB_TRANSFER_COMPLETED,
+    LIBUSB_TRANSFER_NO_DEVICE, LIBUSB_TRANSFER_TIMED_OUT,
+};
+use libusb1_sys::{
+    libusb_alloc_transfer, libusb_cancel_transfer, libusb_context, libusb_device_handle,
+    libusb_fill_bulk_transfer, libusb_free_transfer, libusb_handle_events_completed,
+    libusb_interrupt_event_handler, libusb_submit_transfer, libusb_transfer,
+};
+use rusb::{Context, UsbContext};
+
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+
+/// Transfers queued when `read_async` is given 0, as in librtlsdr
+pub const DEFAULT_BUF_NUM: usize = 15;
+
+/// Stops a running `read_async` of one device, from any thread
+#[derive(Debug, Clone)]
+pub struct AsyncCanceller {
+    cancel: Arc<AtomicBool>,
+    context: Context,
+}
+
+impl AsyncCanceller {
+    pub(crate) fn new(cancel: Arc<AtomicBool>, context: Context) -> Self {
+        AsyncCanceller { cancel, context }
+    }
+
+    /// Cancel the transfers in flight and make `read_async` return once they are
+    /// reaped. Does nothing if no read is running.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::SeqCst);
+        // SAFETY: the context is kept alive by this handle; waking the event loop
+        // is allowed from any thread
+        unsafe { libusb_interrupt_event_handler(self.context.as_raw()) };
+    }
+}
+
+// Transfers finished by libusb, in completion order, waiting to be handed to the
+// callback. Only touched on the thread running the event loop.
+type Completed = VecDeque<*mut libusb_transfer>;
+
+extern "system" fn transfer_done(transfer: *mut libusb_transfer) {
+    // SAFETY: user_data is the `Completed` queue owned by the `Transfers` that
+    // submitted this transfer, and libusb calls this from `handle_events` on the
+    // thread that owns it
+    unsafe {
+        let completed = &mut *((*transfer).user_data as *mut Completed);
+        completed.push_back(transfer);
+    }
+}
+
+// Transfers and their buffers. Dropping cancels whatever is still in flight and waits
+// for it, so libusb never writes into a freed buffer, even if the callback panics.
+struct Transfers {
+    ctx: *mut libusb_context,
+    transfers: Vec<*mut libusb_transfer>,
+    buffers: Vec<Vec<u8>>,
+    in_flight: Vec<bool>,
+    completed: Box<Completed>,
+}
+
+impl Transfers {
+    fn new(
+        handle: *mut libusb_device_handle,
+        ctx: *mut libusb_context,
+        endpoint: u8,
+        buf_num: usize,
+        buf_len: usize,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let mut transfers = Transfers {
+            ctx,
+            transfers: Vec::with_capacity(buf_num),
+            buffers: Vec::with_capacity(buf_num),
+            in_flight: vec![false; buf_num],
+            completed: Box::new(VecDeque::with_capacity(buf_num)),
+        };
+        let user_data = &mut *transfers.completed as *mut Completed as *mut c_void;
+        for _ in 0..buf_num {
+            let mut buf = vec![0; buf_len];
+            // SAFETY: a transfer without isochronous packets, filled before use
+            let transfer = unsafe { libusb_alloc_transfer(0) };
+            if transfer.is_null() {
+                return Err(RtlsdrErr("Unable to allocate USB transfer".to_string()));
+            }
+            // SAFETY: the transfer was just allocated; the buffer lives in `buffers`
+            // and its heap allocation doesn't move when the Vec is moved
+            unsafe {
+                libusb_fill_bulk_transfer(
+                    transfer,
+                    handle,
+                    endpoint,
+                    buf.as_mut_ptr(),
+                    buf_len as c_int,
+                    transfer_done,
+                    user_data,
+                    timeout.as_millis() as c_uint,
+                );
+            }
+            transfers.transfers.push(transfer);
+            transfers.buffers.push(buf);
+        }
+        Ok(transfers)
+    }
+
+    fn submit(&mut self, index: usize) -> Result<()> {
+        // SAFETY: the transfer is filled and not in flight
+        let r = unsafe { libusb_submit_transfer(self.transfers[index]) };
+        if r < 0 {
+            return Err(RtlsdrErr(format!(
+                "Submitting USB transfer failed: libusb error {}",
+                r
+            )));
+        }
+        self.in_flight[index] = true;
+        Ok(())
+    }
+
+    // Wait for libusb to complete at least one transfer
+    fn handle_events(&mut self) -> Result<()> {
+        // SAFETY: the context outlives the transfers, see `read_async`
+        let r = unsafe { libusb_handle_events_completed(self.ctx, ptr::null_mut()) };
+        if r < 0 && r != LIBUSB_ERROR_INTERRUPTED {
+            return Err(RtlsdrErr(format!(
+                "Handling USB events failed: libusb error {}",
+                r
+            )));
+        }
+        Ok(())
+    }
+
+    // Next completed transfer, no longer in flight
+    fn pop_completed(&mut self) -> Option<usize> {
+        let transfer = self.completed.pop_front()?;
+        let index = self.transfers.iter().position(|t| *t == transfer)?;
+        self.in_flight[index] = false;
+        Some(index)
+    }
+}
+
+impl Drop for Transfers {
+    fn drop(&mut self) {
+        for (transfer, in_flight) in self.transfers.iter().zip(self.in_flight.iter()) {
+            if *in_flight {
+                // SAFETY: the transfer is in flight; a failed cancel means it is
+                // completing anyway
+                unsafe { libusb_cancel_transfer(*transfer) };
+            }
+        }
+        while self.in_flight.iter().any(|f| *f) {
+            while self.pop_completed().is_some() {}
+            if !self.in_flight.iter().any(|f| *f) {
+                break;
+            }
+            if let Err(e) = self.handle_events() {
+                // libusb may still write into the buffers, so leak rather than free
+                error!("Unable to reap cancelled USB transfers: {}", e);
+                std::mem::forget(std::mem::take(&mut self.buffers));
+                return;
+            }
+        }
+        for transfer in self.transfers.iter() {
+            // SAFETY: no transfer is in flight any more
+            unsafe { libusb_free_transfer(*transfer) };
+        }
+    }
+}
+
+/// Read from `endpoint` with `buf_num` transfers of `buf_len` bytes queued, passing
+/// each filled buffer to `callback` in order until it returns `false` or `cancel` is
+/// set through an `AsyncCanceller`. A transfer error or a disconnect ends the read
+/// with an error.
+pub(crate) fn read_async<F: FnMut(&mut [u8]) -> bool>(
+    handle: &rusb::DeviceHandle<Context>,
+    endpoint: u8,
+    buf_num: usize,
+    buf_len: usize,
+    timeout: Duration,
+    cancel: &AtomicBool,
+    mut callback: F,
+) -> Result<()> {
+    let buf_num = if buf_num == 0 {
+        DEFAULT_BUF_NUM
+    } else {
+        buf_num
+    };
+    if buf_len == 0 || !buf_len.is_multiple_of(512) {
+        return Err(RtlsdrErr(format!(
+            "Buffer length {} is not a nonzero multiple of 512",
+            buf_len
+        )));
+    }
+    let mut transfers = Transfers::new(
+        handle.as_raw(),
+        handle.context().as_raw(),
+        endpoint,
+        buf_num,
+        buf_len,
+        timeout,
+    )?;
+    cancel.store(false, Ordering::SeqCst);
+    for index in 0..buf_num {
+        transfers.submit(index)?;
+    }
+    debug!(
+        "Reading with {} transfers of {} bytes in flight",
+        buf_num, buf_len
+    );
+    loop {
+        if cancel.load(Ordering::SeqCst) {
+            // Dropping `transfers` cancels and reaps the rest
+            debug!("Async read cancelled");
+            return Ok(());
+        }
+        while let Some(index) = transfers.pop_completed() {
+            // SAFETY: the transfer has completed, so libusb no longer touches it
+            let (status, len) = unsafe {
+                let transfer = &*transfers.transfers[index];
+                (transfer.status, transfer.actual_length as usize)
+            };
+            match status {
+                LIBUSB_TRANSFER_COMPLETED => {
+                    if !callback(&mut transfers.buffers[index][..len])
+                        || cancel.load(Ordering::SeqCst)
+                    {
+                        return Ok(());
+                    }
+                }
+                LIBUSB_TRANSFER_TIMED_OUT => {
+                    return Err(RtlsdrErr("Bulk transfer timed out".to_string()));
+                }
+                LIBUSB_TRANSFER_NO_DEVICE => {
+                    return Err(RtlsdrErr("Device disconnected".to_string()));
+                }
+                LIBUSB_TRANSFER_CANCELLED => {
+                    return Err(RtlsdrErr("Bulk transfer cancelled".to_string()));
+                }
+                status => {
+                    return Err(RtlsdrErr(format!(
+                        "Bulk transfer failed with status {}",
+                        status
+                    )));
+                }
+            }
+            transfers.submit(index)?;
+        }
+        transfers.handle_events()?;
+    }
+}
@@ -0,0 +1,99 @@
+//! Frequency accuracy check against a known reference carrier (a signal generator, a
+//! GPSDO-locked beacon, a broadcast carrier with a published frequency).
+//!
+//! The carrier is received a quarter of the sample rate away from the tuned frequency,
+//! clear of the DC spike, and its position is measured from an averaged spectrum with
+//! Gaussian peak interpolation, which resolves a small fraction of a bin.
+use super::fft::{fft, hann};
+use crate::convert::DEFAULT_DC_OFFSET;
+
+/// Result of a frequency accuracy check
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FreqAccuracy {
+    /// Frequency of the reference carrier, in Hz
+    pub reference_freq: u32,
+    /// How far the carrier appeared from where it should, in Hz
+    pub error_hz: f64,
+    /// Frequency error remaining after the current correction, in parts per million.
+    /// Add it to `get_freq_correction` to calibrate.
+    pub error_ppm: f64,
+    /// Carrier level above the median spectrum level, in dB
+    pub snr_db: f32,
+}
+
+/// FFT length used by `RtlSdr::measure_freq_accuracy`
+pub const DEFAULT_FFT_LEN: usize = 1 << 16;
+/// Spectra averaged by `RtlSdr::measure_freq_accuracy`
+pub const DEFAULT_FRAMES: usize = 16;
+
+// Bins around DC skipped when searching for the carrier
+const DC_GUARD_BINS: usize = 4;
+
+/// Frequency of the strongest carrier in raw interleaved u8 I/Q, in Hz relative to the
+/// tuned frequency, and its level above the median spectrum level in dB. Averages
+/// `fft_len`-point spectra (a power of two) over the whole buffer; `None` if the buffer
+/// holds less than one.
+pub fn find_carrier(raw: &[u8], sample_rate: u32, fft_len: usize) -> Option<(f64, f32)> {
+    let window = hann(fft_len);
+    let mut power = vec![0.0f32; fft_len];
+    let mut re = vec![0.0f32; fft_len];
+    let mut im = vec![0.0f32; fft_len];
+    let mut frames = 0;
+    for frame in raw.chunks_exact(fft_len * 2) {
+        for (i, iq) in frame.chunks_exact(2).enumerate() {
+            re[i] = (iq[0] as f32 - DEFAULT_DC_OFFSET) * window[i];
+            im[i] = (iq[1] as f32 - DEFAULT_DC_OFFSET) * window[i];
+        }
+        fft(&mut re, &mut im);
+        for (p, (r, i)) in power.iter_mut().zip(re.iter().zip(im.iter())) {
+            *p += r * r + i * i;
+        }
+        frames += 1;
+    }
+    if frames == 0 {
+        return None;
+    }
+    let (peak, _) = power
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i > DC_GUARD_BINS && *i < fft_len - DC_GUARD_BINS)
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+    // Gaussian interpolation: a parabola through the log power of the peak and neighbours
+    let log = |i: usize| power[i].max(f32::MIN_POSITIVE).ln() as f64;
+    let (l, c, r) = (log(peak - 1), log(peak), log(peak + 1));
+    let denom = l - 2.0 * c + r;
+    let frac = if denom != 0.0 {
+        0.5 * (l - r) / denom
+    } else {
+        0.0
+    };
+
+    let mut bin = peak as f64 + frac;
+    if bin >= fft_len as f64 / 2.0 {
+        bin -= fft_len as f64;
+    }
+    let mut sorted = power.clone();
+    sorted.sort_unstable_by(f32::total_cmp);
+    let median = sorted[fft_len / 2].max(f32::MIN_POSITIVE);
+    let snr_db = 10.0 * (power[peak] / median).log10();
+    Some((bin * sample_rate as f64 / fft_len as f64, snr_db))
+}
+
+/// Compare a carrier found with `find_carrier` against where it should be. `tuned_freq`
+/// is the frequency the device was tuned to while capturing.
+pub fn freq_accuracy(
+    reference_freq: u32,
+    tuned_freq: u32,
+    measured_offset: f64,
+    snr_db: f32,
+) -> FreqAccuracy {
+    let expected = reference_freq as f64 - tuned_freq as f64;
+    let error_hz = measured_offset - expected;
+    FreqAccuracy {
+        reference_freq,
+        error_hz,
+        // A fast LO puts the carrier low, and vice versa
+        error_ppm: -error_hz / tuned_freq as f64 * 1e6,
+        snr_db,
+    }
+}
@@ -0,0 +1,88 @@
+//! Low-pass filtering and decimation of complex samples by an integer factor.
+use core::f32::consts::PI;
+
+// Filter taps per output sample; more gives a steeper transition band
+const TAPS_PER_FACTOR: usize = 8;
+// Cutoff as a fraction of the output sample rate, leaving room for the transition
+// band below the output Nyquist frequency
+const CUTOFF: f32 = 0.4;
+
+/// Windowed-sinc FIR decimator for interleaved I/Q. Only the kept outputs are
+/// computed, and input left over at the end of a block carries over to the next.
+#[derive(Debug, Clone)]
+pub struct FirDecimator {
+    factor: usize,
+    taps: Vec<f32>,
+    // Interleaved I/Q not yet consumed, including the filter's history
+    history: Vec<f32>,
+}
+
+impl FirDecimator {
+    /// `factor` of 0 is treated as 1 (no filtering)
+    pub fn new(factor: usize) -> Self {
+        let factor = factor.max(1);
+        let taps = if factor == 1 {
+            vec![1.0]
+        } else {
+            lowpass(TAPS_PER_FACTOR * factor + 1, CUTOFF / factor as f32)
+        };
+        FirDecimator {
+            factor,
+            taps,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    /// Filter and decimate interleaved I/Q, appending to `out`
+    pub fn process(&mut self, iq: &[f32], out: &mut Vec<f32>) {
+        if self.factor == 1 {
+            out.extend_from_slice(iq);
+            return;
+        }
+        self.history.extend_from_slice(iq);
+        let len = self.taps.len();
+        let samples = self.history.len() / 2;
+        let mut start = 0;
+        while start + len <= samples {
+            let window = &self.history[start * 2..(start + len) * 2];
+            let (mut i, mut q) = (0.0, 0.0);
+            for (tap, sample) in self.taps.iter().zip(window.chunks_exact(2)) {
+                i += tap * sample[0];
+                q += tap * sample[1];
+            }
+            out.push(i);
+            out.push(q);
+            start += self.factor;
+        }
+        self.history.drain(..start * 2);
+    }
+
+    /// Forget buffered input, e.g. after a retune
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+}
+
+// Hamming-windowed sinc with unity DC gain, `cutoff` in cycles per sample
+fn lowpass(len: usize, cutoff: f32) -> Vec<f32> {
+    let mid = (len - 1) as f32 / 2.0;
+    let mut taps: Vec<f32> = (0..len)
+        .map(|n| {
+            let x = n as f32 - mid;
+            let sinc = if x == 0.0 {
+                2.0 * cutoff
+            } else {
+                (2.0 * PI * cutoff * x).sin() / (PI * x)
+            };
+            let window = 0.54 - 0.46 * (2.0 * PI * n as f32 / (len - 1) as f32).cos();
+            sinc * window
+        })
+        .collect();
+    let gain: f32 = taps.iter().sum();
+    taps.iter_mut().for_each(|t| *t /= gain);
+    taps
+}
@@ -0,0 +1,20 @@
+//! Spurs from the dongle's own crystal: harmonics of the 28.8 MHz reference show up
+//! as fixed carriers at every multiple of the clock and confuse scanners and detectors.
+
+/// Harmonics of `clock` (Hz) that fall inside the band of a capture at `center` Hz and
+/// `rate` samples/s, in ascending order
+pub fn clock_harmonics(center: u64, rate: u32, clock: u32) -> Vec<u64> {
+    if clock == 0 {
+        return Vec::new();
+    }
+    let clock = clock as u64;
+    let half = rate as u64 / 2;
+    let low = center.saturating_sub(half);
+    let high = center + half;
+    let first = low.div_ceil(clock).max(1);
+    (first..)
+        .map(|n| n * clock)
+        .take_while(|freq| *freq <= high)
+        .filter(|freq| freq.abs_diff(center) < half)
+        .collect()
+}
@@ -0,0 +1,12 @@
+//! Filtering and measurements on sample streams.
+pub mod beat;
+pub mod decimate;
+pub mod fft;
+pub mod harmonics;
+pub mod notch;
+pub mod xcorr;
+pub use beat::{find_carrier, FreqAccuracy};
+pub use decimate::FirDecimator;
+pub use harmonics::clock_harmonics;
+pub use notch::{Notch, NotchBank};
+pub use xcorr::{measure_offset, SampleOffset};
@@ -0,0 +1,89 @@
+//! Narrow notches for suppressing fixed spurs ("birdies"), such as USB clock
+//! harmonics, that show up at known absolute frequencies.
+use core::f32::consts::PI;
+
+/// Default -3 dB width of a notch
+pub const DEFAULT_NOTCH_WIDTH: u32 = 1_000;
+
+/// Single complex notch: `H(z) = (1 - e^jw z^-1) / (1 - r e^jw z^-1)`.
+/// Only the spur's side of the spectrum is affected.
+#[derive(Debug, Clone)]
+pub struct Notch {
+    // e^jw
+    rot: (f32, f32),
+    r: f32,
+    prev_in: (f32, f32),
+    prev_out: (f32, f32),
+}
+
+impl Notch {
+    /// Notch at `offset` Hz from the center of a `rate` Hz capture
+    pub fn new(offset: i64, rate: u32, width: u32) -> Self {
+        let w = 2.0 * PI * offset as f32 / rate as f32;
+        let r = (1.0 - PI * width as f32 / rate as f32).clamp(0.0, 0.9999);
+        Notch {
+            rot: (w.cos(), w.sin()),
+            r,
+            prev_in: (0.0, 0.0),
+            prev_out: (0.0, 0.0),
+        }
+    }
+
+    /// Filter interleaved I/Q in place
+    pub fn process(&mut self, iq: &mut [f32]) {
+        let (c, s) = self.rot;
+        for sample in iq.chunks_exact_mut(2) {
+            let x = (sample[0], sample[1]);
+            // e^jw * x[n-1] and e^jw * y[n-1]
+            let zx = (
+                c * self.prev_in.0 - s * self.prev_in.1,
+                c * self.prev_in.1 + s * self.prev_in.0,
+            );
+            let zy = (
+                c * self.prev_out.0 - s * self.prev_out.1,
+                c * self.prev_out.1 + s * self.prev_out.0,
+            );
+            let y = (x.0 - zx.0 + self.r * zy.0, x.1 - zx.1 + self.r * zy.1);
+            self.prev_in = x;
+            self.prev_out = y;
+            sample[0] = y.0;
+            sample[1] = y.1;
+        }
+    }
+}
+
+/// The notches for a list of spurs that fall inside the current capture band
+#[derive(Debug, Clone, Default)]
+pub struct NotchBank {
+    notches: Vec<Notch>,
+}
+
+impl NotchBank {
+    /// Build notches for every spur (absolute Hz) within the band of a capture at
+    /// `center` Hz and `rate` samples/s. Spurs outside the band are skipped.
+    pub fn new(center: u64, rate: u32, spurs: &[u64], width: u32) -> Self {
+        let half = rate as i64 / 2;
+        let notches = spurs
+            .iter()
+            .map(|spur| *spur as i64 - center as i64)
+            .filter(|offset| offset.abs() < half)
+            .map(|offset| Notch::new(offset, rate, width))
+            .collect();
+        NotchBank { notches }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notches.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.notches.len()
+    }
+
+    /// Filter interleaved I/Q in place
+    pub fn process(&mut self, iq: &mut [f32]) {
+        for notch in self.notches.iter_mut() {
+            notch.process(iq);
+        }
+    }
+}
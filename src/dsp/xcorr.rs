@@ -0,0 +1,96 @@
+//! Relative sample offset between two devices receiving the same signal.
+//!
+//! Correlates the magnitude envelopes of short snapshots, which is insensitive to the
+//! devices' different LO frequencies and phases. Needs a strong signal with amplitude
+//! variation (bursts, modulation); a constant carrier gives no usable peak.
+use crate::convert::magnitude::approx_magnitude;
+use crate::convert::DEFAULT_DC_OFFSET;
+
+/// Result of `measure_offset`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleOffset {
+    /// Samples by which `b` lags `a`, with sub-sample interpolation
+    pub offset: f64,
+    /// Normalized correlation at the peak, 0.0 to 1.0
+    pub peak: f32,
+    /// Change in offset over the snapshot, in parts per million of elapsed samples
+    pub drift_ppm: f64,
+}
+
+fn envelope(raw: &[u8]) -> Vec<f32> {
+    let env: Vec<f32> = raw
+        .chunks_exact(2)
+        .map(|iq| {
+            approx_magnitude(
+                iq[0] as f32 - DEFAULT_DC_OFFSET,
+                iq[1] as f32 - DEFAULT_DC_OFFSET,
+            )
+        })
+        .collect();
+    let mean = env.iter().sum::<f32>() / env.len().max(1) as f32;
+    env.into_iter().map(|x| x - mean).collect()
+}
+
+// Best lag of `b` against `a` within +-max_lag, with the normalized peak
+fn best_lag(a: &[f32], b: &[f32], max_lag: usize) -> Option<(f64, f32)> {
+    let max_lag = max_lag as isize;
+    let n = a.len().min(b.len()) as isize;
+    if n <= 2 * max_lag {
+        return None;
+    }
+    let corr = |lag: isize| -> f32 {
+        let (mut sum, mut ea, mut eb) = (0.0f32, 0.0f32, 0.0f32);
+        for i in max_lag..n - max_lag {
+            let x = a[i as usize];
+            let y = b[(i + lag) as usize];
+            sum += x * y;
+            ea += x * x;
+            eb += y * y;
+        }
+        let norm = (ea * eb).sqrt();
+        if norm > 0.0 {
+            sum / norm
+        } else {
+            0.0
+        }
+    };
+    let values: Vec<f32> = (-max_lag..=max_lag).map(corr).collect();
+    let (idx, peak) = values
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by(|x, y| x.1.total_cmp(&y.1))?;
+    // Parabolic interpolation around the peak
+    let mut frac = 0.0;
+    if idx > 0 && idx + 1 < values.len() {
+        let (l, c, r) = (values[idx - 1], values[idx], values[idx + 1]);
+        let denom = l - 2.0 * c + r;
+        if denom != 0.0 {
+            frac = 0.5 * (l - r) / denom;
+        }
+    }
+    Some((idx as f64 - max_lag as f64 + frac as f64, peak))
+}
+
+/// Measure how many samples `b` lags `a`, searching +-`max_lag` samples. Both are raw
+/// interleaved u8 I/Q captured at the same rate. Returns `None` if the snapshots are too
+/// short for the search range.
+pub fn measure_offset(a: &[u8], b: &[u8], max_lag: usize) -> Option<SampleOffset> {
+    let a = envelope(a);
+    let b = envelope(b);
+    let (offset, peak) = best_lag(&a, &b, max_lag)?;
+    // Drift from the offsets of the first and second half
+    let half = a.len().min(b.len()) / 2;
+    let drift_ppm = match (
+        best_lag(&a[..half], &b[..half], max_lag),
+        best_lag(&a[half..], &b[half..], max_lag),
+    ) {
+        (Some((first, _)), Some((second, _))) => (second - first) / half as f64 * 1e6,
+        _ => 0.0,
+    };
+    Some(SampleOffset {
+        offset,
+        peak,
+        drift_ppm,
+    })
+}
@@ -0,0 +1,207 @@
+//! Layout of the RTL2832U configuration EEPROM.
+//!
+//! The first 9 bytes hold the USB IDs and config flags, followed by the manufacturer,
+//! product and serial USB string descriptors. Everything after the strings is unused
+//! by the chip; the upper half is used here as a small user data area protected by a
+//! CRC, e.g. for inventory tags or calibration values.
+use crate::device::EEPROM_SIZE;
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+
+/// Offset of the first USB string descriptor
+pub const STR_OFFSET: usize = 0x09;
+const SIGNATURE: [u8; 2] = [0x28, 0x32];
+const HAVE_SERIAL: u8 = 0xa5;
+const REMOTE_WAKEUP: u8 = 0x01;
+const ENABLE_IR: u8 = 0x02;
+/// Start of the user data area
+pub const USER_DATA_OFFSET: usize = 0x80;
+const USER_MAGIC: [u8; 2] = *b"RU";
+const USER_VERSION: u8 = 1;
+const USER_HEADER_LEN: usize = 6;
+/// Largest payload that fits in the user data area
+pub const USER_DATA_MAX_LEN: usize = EEPROM_SIZE - USER_DATA_OFFSET - USER_HEADER_LEN;
+
+/// Device configuration stored in the EEPROM header and strings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EepromInfo {
+    pub vid: u16,
+    pub pid: u16,
+    pub have_serial: bool,
+    pub remote_wakeup: bool,
+    pub enable_ir: bool,
+    pub manufacturer: String,
+    pub product: String,
+    pub serial: String,
+}
+
+impl EepromInfo {
+    /// Parse an EEPROM image read from offset 0
+    pub fn parse(eeprom: &[u8]) -> Result<EepromInfo> {
+        if eeprom.len() < STR_OFFSET || eeprom[0..2] != SIGNATURE {
+            return Err(RtlsdrErr("No valid RTL2832U EEPROM header".to_string()));
+        }
+        let mut strings = Vec::with_capacity(3);
+        let mut pos = STR_OFFSET;
+        for _ in 0..3 {
+            let (string, next) = read_string(eeprom, pos)?;
+            strings.push(string);
+            pos = next;
+        }
+        let serial = strings.pop().unwrap_or_default();
+        let product = strings.pop().unwrap_or_default();
+        let manufacturer = strings.pop().unwrap_or_default();
+        Ok(EepromInfo {
+            vid: u16::from_le_bytes([eeprom[2], eeprom[3]]),
+            pid: u16::from_le_bytes([eeprom[4], eeprom[5]]),
+            have_serial: eeprom[6] == HAVE_SERIAL,
+            remote_wakeup: eeprom[7] & REMOTE_WAKEUP != 0,
+            enable_ir: eeprom[7] & ENABLE_IR != 0,
+            manufacturer,
+            product,
+            serial,
+        })
+    }
+}
+
+/// Config flag changes for `apply_flags`; `None` leaves a flag as it is.
+///
+/// The flag bits have extra meaning to this driver on read: a cleared IR bit forces
+/// the bias tee on and a set remote wakeup bit forces direct sampling, as in the
+/// RTL-SDR Blog driver.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EepromFlags {
+    pub enable_ir: Option<bool>,
+    pub remote_wakeup: Option<bool>,
+}
+
+/// A byte that differs between two EEPROM images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EepromChange {
+    pub offset: usize,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// Bytes that differ between `old` and `new`
+pub fn diff(old: &[u8], new: &[u8]) -> Vec<EepromChange> {
+    old.iter()
+        .zip(new.iter())
+        .enumerate()
+        .filter(|(_, (o, n))| o != n)
+        .map(|(offset, (o, n))| EepromChange {
+            offset,
+            old: *o,
+            new: *n,
+        })
+        .collect()
+}
+
+/// Apply `flags` to an EEPROM image read from offset 0, returning the new image and
+/// what changed. Fails if the image doesn't hold a valid header and string table, so
+/// a blank or foreign EEPROM isn't modified.
+pub fn apply_flags(eeprom: &[u8], flags: EepromFlags) -> Result<(Vec<u8>, Vec<EepromChange>)> {
+    EepromInfo::parse(eeprom)?;
+    let mut image = eeprom.to_vec();
+    let set = |byte: &mut u8, bit: u8, on: Option<bool>| match on {
+        Some(true) => *byte |= bit,
+        Some(false) => *byte &= !bit,
+        None => {}
+    };
+    set(&mut image[7], ENABLE_IR, flags.enable_ir);
+    set(&mut image[7], REMOTE_WAKEUP, flags.remote_wakeup);
+    let changes = diff(eeprom, &image);
+    Ok((image, changes))
+}
+
+// USB string descriptor at `pos`: length, type 0x03, UTF-16LE text.
+// Returns the string and the offset of the next descriptor.
+fn read_string(eeprom: &[u8], pos: usize) -> Result<(String, usize)> {
+    let len = *eeprom
+        .get(pos)
+        .ok_or_else(|| RtlsdrErr("EEPROM string table is truncated".to_string()))?
+        as usize;
+    if len < 2 || pos + len > eeprom.len() || eeprom[pos + 1] != 0x03 {
+        return Err(RtlsdrErr(format!("Invalid EEPROM string at {:#04x}", pos)));
+    }
+    let units: Vec<u16> = eeprom[pos + 2..pos + len]
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    Ok((String::from_utf16_lossy(&units), pos + len))
+}
+
+/// End of the three USB string descriptors in an EEPROM image
+pub fn strings_end(eeprom: &[u8]) -> usize {
+    let mut pos = STR_OFFSET;
+    for _ in 0..3 {
+        if pos + 1 >= eeprom.len() || eeprom[pos + 1] != 0x03 {
+            break;
+        }
+        pos += eeprom[pos] as usize;
+    }
+    pos
+}
+
+/// Build the user data area for `data`
+pub fn encode_user_data(data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() > USER_DATA_MAX_LEN {
+        return Err(RtlsdrErr(format!(
+            "User data is {} bytes, at most {} fit in the EEPROM",
+            data.len(),
+            USER_DATA_MAX_LEN
+        )));
+    }
+    let crc = crc16(data);
+    let mut area = Vec::with_capacity(USER_HEADER_LEN + data.len());
+    area.extend_from_slice(&USER_MAGIC);
+    area.push(USER_VERSION);
+    area.push(data.len() as u8);
+    area.extend_from_slice(&crc.to_le_bytes());
+    area.extend_from_slice(data);
+    Ok(area)
+}
+
+/// Extract user data from a full EEPROM image.
+/// Returns `None` if no user data was ever written.
+pub fn decode_user_data(eeprom: &[u8]) -> Result<Option<Vec<u8>>> {
+    if eeprom.len() < EEPROM_SIZE {
+        return Err(RtlsdrErr("EEPROM image is too short".to_string()));
+    }
+    let area = &eeprom[USER_DATA_OFFSET..];
+    if area[0..2] != USER_MAGIC {
+        return Ok(None);
+    }
+    if area[2] != USER_VERSION {
+        return Err(RtlsdrErr(format!(
+            "Unsupported user data version {}",
+            area[2]
+        )));
+    }
+    let len = area[3] as usize;
+    if len > USER_DATA_MAX_LEN {
+        return Err(RtlsdrErr(format!("Corrupt user data length {}", len)));
+    }
+    let crc = u16::from_le_bytes([area[4], area[5]]);
+    let data = &area[USER_HEADER_LEN..USER_HEADER_LEN + len];
+    if crc16(data) != crc {
+        return Err(RtlsdrErr("User data checksum mismatch".to_string()));
+    }
+    Ok(Some(data.to_vec()))
+}
+
+/// CRC-16/CCITT-FALSE
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xffff;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
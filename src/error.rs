@@ -1,4 +1,7 @@
+use std::path::Path;
 use std::{fmt, result};
+
+use crate::DirectSampleMode;
 // use std::error::Error;
 
 /// A result of a function that may return a `Error`.
@@ -37,5 +40,85 @@ macro_rules! define_errcodes {
 define_errcodes![
     RtlsdrError =>
     Usb : rusb::Error,
-    RtlsdrErr: String
+    RtlsdrErr: String,
+    InitFailed: InitError,
+    FreqOutOfRange: FreqRangeError,
+    InvalidSampleRate: SampleRateError
 ];
+
+/// An I/O error on the file at `path`, reported as "<path>: <error>"
+pub(crate) fn io_err(path: &Path, e: std::io::Error) -> RtlsdrError {
+    RtlsdrError::RtlsdrErr(format!("{}: {}", path.display(), e))
+}
+
+/// A center frequency that can't be received in the current direct sampling mode
+#[derive(Debug)]
+pub struct FreqRangeError {
+    pub freq: u32,
+    pub min_freq: u32,
+    pub max_freq: u32,
+    /// Closest frequency that can be tuned in this mode, e.g. the edge of a gap
+    /// between a tuner's bands
+    pub nearest: u32,
+    pub direct_sampling: DirectSampleMode,
+}
+
+impl fmt::Display for FreqRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Frequency {} Hz is outside {}..={} Hz with direct sampling {:?}",
+            self.freq, self.min_freq, self.max_freq, self.direct_sampling
+        )?;
+        if (self.min_freq..=self.max_freq).contains(&self.freq) {
+            write!(
+                f,
+                " (in a gap between bands, nearest is {} Hz)",
+                self.nearest
+            )?;
+        } else if self.direct_sampling == DirectSampleMode::Off && self.freq < self.min_freq {
+            write!(f, " (HF reception needs direct sampling)")?;
+        } else if self.direct_sampling != DirectSampleMode::Off && self.freq > self.max_freq {
+            write!(f, " (direct sampling only covers HF)")?;
+        }
+        Ok(())
+    }
+}
+
+/// A sample rate the RTL2832 resampler can't produce, see `RtlSdr::valid_sample_rates`
+#[derive(Debug)]
+pub struct SampleRateError {
+    pub rate: u32,
+    /// Closest supported rate
+    pub nearest: u32,
+}
+
+impl fmt::Display for SampleRateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid sample rate: {} Hz (nearest supported is {} Hz)",
+            self.rate, self.nearest
+        )
+    }
+}
+
+/// Failures of every attempt of a retried device initialization, in order
+#[derive(Debug)]
+pub struct InitError {
+    pub attempts: Vec<RtlsdrError>,
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Device initialization failed after {} attempts",
+            self.attempts.len()
+        )?;
+        for (i, err) in self.attempts.iter().enumerate() {
+            write!(f, "; attempt {}: {}", i + 1, err)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+//! Mapping of requested gains onto the discrete steps a tuner supports.
+//! All gains are in tenths of a dB, like `Tuner::get_gains`.
+
+/// How a requested gain is applied to the tuner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GainStrategy {
+    /// Use the closest step in the tuner's gain table
+    Nearest(i32),
+    /// Alternate between the two table steps surrounding the requested gain so
+    /// the time-averaged gain matches the request. The gain is switched once per
+    /// block read through `Read` or per call to `RtlSdr::advance_gain_dither`.
+    Interpolated(i32),
+    /// Measure the signal at the current frequency and sample rate with a few gain
+    /// steps, then hold the highest gain that doesn't clip the ADC
+    AutoOnOpen,
+}
+
+/// Gain steps tried by `GainStrategy::AutoOnOpen`
+pub const AUTO_GAIN_STEPS: usize = 6;
+/// Largest fraction of clipped (0 or 255) raw values accepted by `GainStrategy::AutoOnOpen`
+pub const AUTO_GAIN_MAX_CLIP: f32 = 1e-4;
+
+/// Fraction of raw u8 values at either end of the ADC range
+pub fn clip_fraction(raw: &[u8]) -> f32 {
+    if raw.is_empty() {
+        return 0.0;
+    }
+    let clipped = raw.iter().filter(|x| **x == 0 || **x == 255).count();
+    clipped as f32 / raw.len() as f32
+}
+
+/// Up to `steps` gains spread evenly over the table, highest first
+pub fn auto_gain_candidates(gains: &[i32], steps: usize) -> Vec<i32> {
+    let mut sorted = gains.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    if sorted.len() <= steps || steps < 2 {
+        sorted.reverse();
+        return sorted;
+    }
+    let last = sorted.len() - 1;
+    let mut candidates: Vec<i32> = (0..steps)
+        .map(|i| sorted[last - i * last / (steps - 1)])
+        .collect();
+    candidates.dedup();
+    candidates
+}
+
+/// Closest entry in `gains` to `target`, or `None` if the table is empty
+pub fn nearest(gains: &[i32], target: i32) -> Option<i32> {
+    gains
+        .iter()
+        .copied()
+        .min_by_key(|gain| (gain - target).abs())
+}
+
+/// Temporal dither between two adjacent gain steps
+#[derive(Debug, Clone)]
+pub struct GainDither {
+    low: i32,
+    high: i32,
+    // Fraction of blocks that should use `high`
+    high_fraction: f32,
+    acc: f32,
+}
+
+impl GainDither {
+    /// Build a dither for `target` from the gain table, clamping to the table's range.
+    /// Returns `None` if the table is empty.
+    pub fn new(gains: &[i32], target: i32) -> Option<GainDither> {
+        let low = gains.iter().copied().filter(|g| *g <= target).max();
+        let high = gains.iter().copied().filter(|g| *g >= target).min();
+        let (low, high) = match (low, high) {
+            (Some(low), Some(high)) => (low, high),
+            (Some(low), None) => (low, low),
+            (None, Some(high)) => (high, high),
+            (None, None) => return None,
+        };
+        let high_fraction = if high == low {
+            0.0
+        } else {
+            (target - low) as f32 / (high - low) as f32
+        };
+        Some(GainDither {
+            low,
+            high,
+            high_fraction,
+            acc: 0.0,
+        })
+    }
+
+    /// True if the target falls exactly on a table step (or outside the table)
+    pub fn is_fixed(&self) -> bool {
+        self.low == self.high
+    }
+
+    /// Gain to use for the next block
+    pub fn next_gain(&mut self) -> i32 {
+        // First-order error accumulator spreads the high steps evenly over time
+        self.acc += self.high_fraction;
+        if self.acc >= 1.0 {
+            self.acc -= 1.0;
+            self.high
+        } else {
+            self.low
+        }
+    }
+
+    /// Long-term average gain produced by this dither
+    pub fn average_gain(&self) -> f32 {
+        self.low as f32 + (self.high - self.low) as f32 * self.high_fraction
+    }
+}
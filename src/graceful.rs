@@ -0,0 +1,119 @@
+//! Ctrl-C and termination signal handling for applications and daemons, enabled
+//! with the `graceful` feature.
+//!
+//! `install` catches SIGINT, SIGTERM and SIGHUP (Ctrl-C and console close on
+//! Windows) once per process. The first signal asks the application to stop; a second
+//! one exits immediately, for when a teardown hangs. `run_until_signal` ties this to a
+//! `Pipeline` so recordings are finalized and the device is powered down on Ctrl-C.
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::pipeline::Pipeline;
+
+// Exit status of a process killed by SIGINT, used when a second signal arrives
+const FORCED_EXIT_CODE: i32 = 130;
+// How often `run_until_signal` checks whether the pipeline stopped by itself
+const PIPELINE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static SIGNAL: OnceLock<ShutdownSignal> = OnceLock::new();
+
+/// Set once a termination signal has arrived. Cheap to clone.
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    state: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl ShutdownSignal {
+    fn new() -> Self {
+        ShutdownSignal {
+            state: Arc::new((Mutex::new(false), Condvar::new())),
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        *self.state.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Block until a signal arrives
+    pub fn wait(&self) {
+        let (flag, cond) = &*self.state;
+        let mut set = flag.lock().unwrap_or_else(|e| e.into_inner());
+        while !*set {
+            set = cond.wait(set).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Block until a signal arrives or `timeout` passes. Returns whether a signal
+    /// arrived.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let (flag, cond) = &*self.state;
+        let set = flag.lock().unwrap_or_else(|e| e.into_inner());
+        let (set, _) = cond
+            .wait_timeout_while(set, timeout, |set| !*set)
+            .unwrap_or_else(|e| e.into_inner());
+        *set
+    }
+
+    /// Raise the signal from code, e.g. when a decoder decides it is done. Raising it
+    /// again has no effect.
+    pub fn trigger(&self) {
+        self.raise();
+    }
+
+    // Set the signal, returning whether it was already set
+    fn raise(&self) -> bool {
+        let (flag, cond) = &*self.state;
+        let mut set = flag.lock().unwrap_or_else(|e| e.into_inner());
+        if *set {
+            return true;
+        }
+        *set = true;
+        info!("Shutdown requested");
+        cond.notify_all();
+        false
+    }
+}
+
+/// Install the signal handlers, or return the signal of an earlier `install`
+pub fn install() -> Result<ShutdownSignal> {
+    if let Some(signal) = SIGNAL.get() {
+        return Ok(signal.clone());
+    }
+    let signal = ShutdownSignal::new();
+    let handler = signal.clone();
+    ctrlc::set_handler(move || {
+        if handler.raise() {
+            warn!("Second shutdown signal, exiting immediately");
+            std::process::exit(FORCED_EXIT_CODE);
+        }
+    })
+    .map_err(|e| RtlsdrErr(format!("Failed to install signal handler: {}", e)))?;
+    // Only one set_handler call can succeed, so this is the signal the handler raises
+    Ok(SIGNAL.get_or_init(|| signal).clone())
+}
+
+/// Install the signal handlers, then run `pipeline` until a signal arrives or it
+/// stops by itself, and shut it down in order
+pub fn run_until_signal(pipeline: Pipeline) -> Result<()> {
+    let signal = install()?;
+    while !signal.wait_timeout(PIPELINE_POLL_INTERVAL) && !pipeline.is_finished() {}
+    pipeline.shutdown().wait()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trigger_is_idempotent() {
+        let signal = ShutdownSignal::new();
+        assert!(!signal.wait_timeout(Duration::ZERO));
+        signal.trigger();
+        // A second trigger from code must not exit the process
+        signal.trigger();
+        assert!(signal.is_set());
+        assert!(signal.wait_timeout(Duration::ZERO));
+    }
+}
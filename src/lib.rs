@@ -1,59 +1,254 @@
 //! # rtlsdr Library
 //! Library for interfacing with an RTL-SDR device.
+//!
+//! ## Threads
+//! `RtlSdr` is `Send`, so it can be moved into another thread or a tokio task, but not
+//! `Sync`: configuration needs `&mut self`. To retune from one thread while another
+//! reads, use `RtlSdr::split`, whose `Controller` and `Reader` halves are both `Send`
+//! and `Sync`. `Device` is `Send` and `Sync`. These guarantees are checked at compile
+//! time below.
 
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod convert;
+pub mod detect;
 pub mod device;
+pub mod dsp;
+pub mod eeprom;
 pub mod error;
+pub mod gain;
+#[cfg(feature = "graceful")]
+pub mod graceful;
+pub mod lowlevel;
+#[cfg(feature = "node")]
+mod node;
+pub mod observer;
+pub mod options;
+pub mod pipeline;
+pub mod platform;
+pub mod provenance;
+#[cfg(feature = "python")]
+mod python;
+pub mod record;
 pub mod rtlsdr;
+pub mod split;
+pub mod stream;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod tuners;
 #[macro_use]
 extern crate log;
 
 use core::fmt;
-use std::{io::Read, time::Duration};
+use std::{io::Read, str::FromStr, time::Duration};
 
 use device::Device;
 use error::Result;
-use rtlsdr::RtlSdr as Sdr;
-use tokio::io::AsyncRead;
+use error::RtlsdrError::RtlsdrErr;
+use gain::{GainDither, GainStrategy};
+use observer::{ConfigEvent, ConfigObserver};
+pub use options::{OpenOptions, Requirements};
+pub use provenance::build_info;
+use rtlsdr::{RtlSdr as Sdr, BIAS_TEE_GPIO};
+
+// Compile-time checks of the thread safety promised in the crate docs
+const _: () = {
+    const fn send<T: Send>() {}
+    const fn send_sync<T: Send + Sync>() {}
+    send::<RtlSdr>();
+    send::<stream::Reader<'static>>();
+    send::<stream::ResumingReader>();
+    send::<stream::SampleStream>();
+    send::<pipeline::Pipeline>();
+    send_sync::<device::Device>();
+    send_sync::<split::Controller>();
+    send_sync::<split::Reader>();
+    send_sync::<OpenOptions>();
+};
 
 pub const DEFAULT_BUF_LENGTH: usize = 16 * 16384;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TunerGain {
     Auto,
     Manual(i32),
 }
 
-// implement fmt::Display for TunerGain
-
+// Canonical form, parsed back by `FromStr`: "auto" or the gain in dB, e.g. "28.0"
 impl fmt::Display for TunerGain {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            TunerGain::Auto => write!(f, "Auto"),
-            TunerGain::Manual(gain) => write!(f, "Manual({})", gain),
+            TunerGain::Auto => write!(f, "auto"),
+            TunerGain::Manual(gain) => write!(f, "{:.1}", *gain as f32 / 10.0),
+        }
+    }
+}
+
+/// Parses "auto" (or "agc") and gains in dB such as "28" or "28.0", as given on
+/// command lines and in config files
+impl FromStr for TunerGain {
+    type Err = error::RtlsdrError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("auto") || s.eq_ignore_ascii_case("agc") {
+            return Ok(TunerGain::Auto);
+        }
+        match s.parse::<f32>() {
+            Ok(db) if db.is_finite() => Ok(TunerGain::Manual((db * 10.0).round() as i32)),
+            _ => Err(RtlsdrErr(format!(
+                "Invalid gain \"{}\": expected \"auto\" or a gain in dB",
+                s
+            ))),
         }
     }
 }
 
+impl TryFrom<&str> for TunerGain {
+    type Error = error::RtlsdrError;
+
+    fn try_from(s: &str) -> Result<Self> {
+        s.parse()
+    }
+}
+
 impl From<i32> for TunerGain {
     fn from(gain: i32) -> Self {
         TunerGain::Manual(gain)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DirectSampleMode {
     Off,
     On,
     OnSwap, // Swap I and Q ADC, allowing to select between two inputs
 }
 
+// Canonical form, parsed back by `FromStr`: "off", or the ADC sampled ("i" or "q")
+impl fmt::Display for DirectSampleMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DirectSampleMode::Off => write!(f, "off"),
+            DirectSampleMode::On => write!(f, "i"),
+            DirectSampleMode::OnSwap => write!(f, "q"),
+        }
+    }
+}
+
+/// Parses "off"/"i"/"q", as well as the numbers of `rtl_sdr -D` (0, 1, 2)
+impl FromStr for DirectSampleMode {
+    type Err = error::RtlsdrError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" | "0" => Ok(DirectSampleMode::Off),
+            "i" | "on" | "1" => Ok(DirectSampleMode::On),
+            "q" | "swap" | "2" => Ok(DirectSampleMode::OnSwap),
+            _ => Err(RtlsdrErr(format!(
+                "Invalid direct sampling mode \"{}\": expected off, i or q",
+                s.trim()
+            ))),
+        }
+    }
+}
+
+impl TryFrom<&str> for DirectSampleMode {
+    type Error = error::RtlsdrError;
+
+    fn try_from(s: &str) -> Result<Self> {
+        s.parse()
+    }
+}
+
+/// How the tuner output is brought to baseband
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IfMode {
+    /// Tuner delivers a single low IF (R820T: 3.57/4.57 MHz) that the demod mixes
+    /// down; no DC spike, but images must be rejected
+    #[default]
+    LowIf,
+    /// Tuner delivers I/Q at zero IF to both ADCs; no images, but a DC spike at the
+    /// center frequency
+    ZeroIf,
+}
+
+/// Signal path feeding the RTL2832 ADCs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdcInput {
+    /// I and Q from the tuner (direct sampling off)
+    Tuner,
+    /// Direct sampling from the I ADC input
+    DirectI,
+    /// Direct sampling from the Q ADC input
+    DirectQ,
+}
+
+impl From<DirectSampleMode> for AdcInput {
+    fn from(mode: DirectSampleMode) -> Self {
+        match mode {
+            DirectSampleMode::Off => AdcInput::Tuner,
+            DirectSampleMode::On => AdcInput::DirectI,
+            DirectSampleMode::OnSwap => AdcInput::DirectQ,
+        }
+    }
+}
+
+impl From<AdcInput> for DirectSampleMode {
+    fn from(input: AdcInput) -> Self {
+        match input {
+            AdcInput::Tuner => DirectSampleMode::Off,
+            AdcInput::DirectI => DirectSampleMode::On,
+            AdcInput::DirectQ => DirectSampleMode::OnSwap,
+        }
+    }
+}
+
+/// What `set_center_freq` does with a frequency the current direct sampling mode can't receive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FreqPolicy {
+    /// Return `RtlsdrError::FreqOutOfRange`
+    #[default]
+    Reject,
+    /// Enable direct sampling (`DirectSampleMode::On`) for HF or disable it for frequencies
+    /// above the HF range, then tune
+    AutoSwitch,
+    /// Program the frequency anyway
+    Ignore,
+}
+
+fn nearest_or_err(gains: &[i32], target: i32) -> Result<i32> {
+    gain::nearest(gains, target).ok_or_else(|| RtlsdrErr("Tuner has no gain table".to_string()))
+}
+
+fn in_ranges(ranges: &[(u32, u32)], freq: u32) -> bool {
+    ranges
+        .iter()
+        .any(|(min, max)| (*min..=*max).contains(&freq))
+}
+
+// Received frequency for a tuned frequency behind a converter with LO `offset`
+pub(crate) fn rf_freq(tuned: u32, offset: i64) -> u64 {
+    (tuned as i64 + offset).max(0) as u64
+}
+
 pub struct RtlSdr {
     sdr: Sdr,
+    freq_policy: FreqPolicy,
+    birdies: Vec<u32>,
+    gain_dither: Option<GainDither>,
+    converter_offset: i64,
+    observers: Vec<ConfigObserver>,
 }
 
 impl Read for RtlSdr {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Err(e) = self.advance_gain_dither() {
+            return Err(std::io::Error::other(format!(
+                "Error updating gain dither: {:?}",
+                e
+            )));
+        }
         match self.read_sync(buf) {
             Ok(len) => Ok(len),
             Err(e) => Err(std::io::Error::new(
@@ -62,50 +257,194 @@ impl Read for RtlSdr {
             )),
         }
     }
+
+    fn read_vectored(&mut self, bufs: &mut [std::io::IoSliceMut<'_>]) -> std::io::Result<usize> {
+        if let Err(e) = self.advance_gain_dither() {
+            return Err(std::io::Error::other(format!(
+                "Error updating gain dither: {:?}",
+                e
+            )));
+        }
+        self.read_sync_vectored(bufs)
+            .map_err(|e| std::io::Error::other(format!("Error reading from device: {:?}", e)))
+    }
+}
+
+// Deprecated: the blocking `AsyncRead` impls below go away in the next release
+#[cfg(any(feature = "async-tokio", feature = "futures-io"))]
+fn warn_blocking_async_read() {
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    WARNED.call_once(|| {
+        warn!("AsyncRead for RtlSdr blocks the executor and is deprecated, use RtlSdr::byte_stream")
+    });
 }
 
-impl AsyncRead for RtlSdr {
+/// Deprecated, to be removed in the next release: each read blocks the executor for a
+/// whole USB transfer. Use `RtlSdr::byte_stream`, whose `ByteStream` implements
+/// `AsyncRead` without blocking.
+#[cfg(feature = "async-tokio")]
+impl tokio::io::AsyncRead for RtlSdr {
     fn poll_read(
         self: std::pin::Pin<&mut Self>,
         _cx: &mut std::task::Context,
         buf: &mut tokio::io::ReadBuf,
     ) -> std::task::Poll<std::io::Result<()>> {
+        warn_blocking_async_read();
         let mut buffer = vec![0; buf.remaining()];
         match self.read_sync(&mut buffer) {
             Ok(len) => {
                 buf.put_slice(&buffer[..len]);
                 std::task::Poll::Ready(Ok(()))
             }
-            Err(e) => std::task::Poll::Ready(Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Error reading from device: {:?}", e),
-            ))),
+            Err(e) => std::task::Poll::Ready(Err(std::io::Error::other(format!(
+                "Error reading from device: {:?}",
+                e
+            )))),
         }
     }
 }
 
+/// Deprecated, to be removed in the next release: each read blocks the executor for a
+/// whole USB transfer. Use `RtlSdr::byte_stream`, whose `ByteStream` implements
+/// `futures_io::AsyncRead` without blocking.
+#[cfg(feature = "futures-io")]
+impl futures_io::AsyncRead for RtlSdr {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context,
+        buf: &mut [u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        warn_blocking_async_read();
+        std::task::Poll::Ready(
+            self.read_sync(buf)
+                .map_err(|e| std::io::Error::other(format!("Error reading from device: {:?}", e))),
+        )
+    }
+}
+
 impl RtlSdr {
+    fn from_sdr(sdr: Sdr) -> RtlSdr {
+        RtlSdr {
+            sdr,
+            freq_policy: FreqPolicy::default(),
+            birdies: Vec::new(),
+            gain_dither: None,
+            converter_offset: 0,
+            observers: Vec::new(),
+        }
+    }
+
+    /// Register a callback that is invoked after every configuration change.
+    /// Gain steps of an interpolated gain are not reported.
+    pub fn add_config_observer<F: Fn(&ConfigEvent) + Send + 'static>(&mut self, observer: F) {
+        self.observers.push(Box::new(observer));
+    }
+
+    fn notify(&self, event: ConfigEvent) {
+        for observer in self.observers.iter() {
+            observer(&event);
+        }
+    }
+
+    /// Collect the device configuration, then `open()` the device with it applied
+    pub fn builder() -> OpenOptions {
+        OpenOptions::new()
+    }
+
+    /// Open a device with the default `OpenOptions`
     pub fn open_by_index(index: usize) -> Result<RtlSdr> {
-        let dev = Device::new_by_index(index)?;
-        let mut sdr = Sdr::new(dev);
-        sdr.init()?;
-        Ok(RtlSdr { sdr: sdr })
+        OpenOptions::new().open_by_index(index)
     }
 
+    /// Open a device with the default `OpenOptions`
     pub fn open_by_serial(serial: &str) -> Result<RtlSdr> {
-        let dev = Device::new_by_serial(serial)?;
-        let mut sdr = Sdr::new(dev);
-        sdr.init()?;
-        Ok(RtlSdr { sdr: sdr })
+        OpenOptions::new().open_by_serial(serial)
+    }
+
+    /// Open the first unused device meeting `req` with the default `OpenOptions`
+    pub fn open_matching(req: Requirements) -> Result<RtlSdr> {
+        OpenOptions::new().open_matching(&req)
+    }
+
+    /// Open a device through an application-owned libusb context, so several devices
+    /// (and other USB users) share one context and its event handling. `rusb::Context`
+    /// is reference counted; pass a clone.
+    pub fn open_by_index_with_context(context: rusb::Context, index: usize) -> Result<RtlSdr> {
+        OpenOptions::new().context(context).open_by_index(index)
+    }
+
+    /// Open a device by serial through an application-owned libusb context
+    pub fn open_by_serial_with_context(context: rusb::Context, serial: &str) -> Result<RtlSdr> {
+        OpenOptions::new().context(context).open_by_serial(serial)
+    }
+
+    /// Open a device with the default `OpenOptions`
+    pub fn open_by_bus_address(bus: u8, address: u8) -> Result<RtlSdr> {
+        OpenOptions::new().open_by_bus_address(bus, address)
+    }
+
+    /// Open a device with the default `OpenOptions`
+    pub fn open_by_port_path(bus: u8, ports: &[u8]) -> Result<RtlSdr> {
+        OpenOptions::new().open_by_port_path(bus, ports)
+    }
+
+    /// Open a device by VID/PID, bypassing the known device table, with the default
+    /// `OpenOptions`
+    pub fn open_unchecked(vid: u16, pid: u16) -> Result<RtlSdr> {
+        OpenOptions::new().open_unchecked(vid, pid)
+    }
+
+    /// Take over a device handle opened by the application, e.g. after a custom
+    /// permission flow, and initialize it with the default `OpenOptions`
+    pub fn from_rusb_handle(handle: rusb::DeviceHandle<rusb::Context>) -> Result<RtlSdr> {
+        OpenOptions::new().open_rusb_handle(handle)
+    }
+
+    /// Open a device from an already opened usbfs file descriptor (e.g. from Android's
+    /// USB host API) with the default `OpenOptions`
+    ///
+    /// # Safety
+    /// See `OpenOptions::open_by_fd`: `fd` must stay open while the `RtlSdr` exists.
+    #[cfg(unix)]
+    pub unsafe fn open_by_fd(fd: std::os::unix::io::RawFd) -> Result<RtlSdr> {
+        OpenOptions::new().open_by_fd(fd)
+    }
+
+    /// Library, host and device details to store with a recording
+    pub fn provenance(&self) -> provenance::Provenance {
+        provenance::Provenance::collect(self)
+    }
+
+    /// Manufacturer, product and serial strings from the USB descriptors
+    pub fn get_usb_strings(&self) -> Result<device::device_handle::UsbStrings> {
+        self.sdr.get_usb_strings()
+    }
+
+    /// How long each stage of opening this device took
+    pub fn init_timing(&self) -> rtlsdr::InitTiming {
+        *self.sdr.init_timing()
+    }
+
+    /// Supported devices attached to the host, for building a device picker
+    pub fn enumerate() -> Result<Vec<device::DeviceInfo>> {
+        Device::enumerate()
+    }
+
+    /// Resolve a serial number to the index used by `open_by_index`
+    pub fn get_index_by_serial(serial: &str) -> Result<usize> {
+        Device::index_by_serial(serial)
     }
 
     pub fn list_and_print_known_devices() -> Result<()> {
         Device::list_and_print_known_devices()
     }
 
-    pub fn close(&mut self) -> Result<()> {
-        // TODO: wait until async is inactive
-        Ok(self.sdr.deinit_baseband()?)
+    /// Power down the tuner and demod and release the device. Dropping an `RtlSdr`
+    /// does the same; `close` is for callers that want to see the error.
+    pub fn close(mut self) -> Result<()> {
+        // No `read_async` can still be running: it borrows the device, and it reaps
+        // all of its transfers, cancelled or not, before returning
+        self.sdr.close()
     }
     pub fn reset_buffer(&self) -> Result<()> {
         self.sdr.reset_buffer()
@@ -113,40 +452,707 @@ impl RtlSdr {
     pub fn read_sync(&self, buf: &mut [u8]) -> Result<usize> {
         self.sdr.read_sync(buf)
     }
+    /// Fail the transfers `script` names from now on, or stop with `None`, to test
+    /// recovery from timeouts, short reads and unplugging. See `testing`.
+    #[cfg(feature = "testing")]
+    pub fn inject_faults(&mut self, script: Option<std::sync::Arc<testing::FaultScript>>) {
+        self.sdr.inject_faults(script)
+    }
+    /// Stream samples to `callback` with `buf_num` bulk transfers of `buf_len` bytes
+    /// queued at once (0 for 15 transfers, as librtlsdr), until the callback returns
+    /// `false` or `cancel_async` is called. Unlike `read_sync`, the device keeps filling
+    /// the queued buffers while the callback runs, so a slow callback doesn't drop
+    /// samples right away. `buf_len` must be a multiple of 512. Call `reset_buffer`
+    /// first, as with `read_sync`.
+    pub fn read_async<F: FnMut(&[u8]) -> bool>(
+        &self,
+        callback: F,
+        buf_num: usize,
+        buf_len: usize,
+    ) -> Result<()> {
+        self.sdr.read_async(callback, buf_num, buf_len)
+    }
+    /// Stop a running `read_async`, e.g. from inside its callback. The transfers in
+    /// flight are cancelled and `read_async` returns `Ok` once they are reaped.
+    pub fn cancel_async(&self) {
+        self.sdr.async_canceller().cancel()
+    }
+    /// Handle for calling `cancel_async` from another thread
+    pub fn async_canceller(&self) -> device::AsyncCanceller {
+        self.sdr.async_canceller()
+    }
+    /// Fill several buffers in order, one bulk transfer each, without an intermediate
+    /// copy. Like `read_sync`, each buffer should be a multiple of 512 bytes. Stops at
+    /// the first short transfer and returns the total number of bytes read.
+    pub fn read_sync_vectored(&self, bufs: &mut [std::io::IoSliceMut<'_>]) -> Result<usize> {
+        let mut total = 0;
+        for buf in bufs.iter_mut().filter(|buf| !buf.is_empty()) {
+            let len = self.sdr.read_sync(buf)?;
+            total += len;
+            if len < buf.len() {
+                break;
+            }
+        }
+        Ok(total)
+    }
+    /// Read and discard samples for `duration`, e.g. to let the tuner settle after a
+    /// retune without resetting the FIFO. Returns the number of bytes discarded.
+    pub fn flush(&self, duration: Duration) -> Result<usize> {
+        let mut buf = vec![0; DEFAULT_BUF_LENGTH];
+        let end = platform::now() + duration;
+        let mut discarded = 0;
+        while platform::now() < end {
+            discarded += self.sdr.read_sync(&mut buf)?;
+        }
+        Ok(discarded)
+    }
+    /// Reset the buffer and discard samples for `duration` while the tuner and
+    /// crystal warm up, e.g. after a long idle period. Returns the bytes discarded.
+    pub fn warm_up(&self, duration: Duration) -> Result<usize> {
+        self.reset_buffer()?;
+        self.flush(duration)
+    }
+    /// USB sample FIFO registers, for recovery procedures that need more than a
+    /// blind `reset_buffer`
+    pub fn get_fifo_status(&self) -> Result<rtlsdr::FifoStatus> {
+        self.sdr.get_fifo_status()
+    }
+    /// Write a register sequence to the open device, e.g. to try a tweak before
+    /// adding it to the init sequences with `device::set_sequence`. Nothing keeps
+    /// track of the changed registers; later calls may overwrite them.
+    pub fn write_sequence(&self, writes: &[device::RegWrite]) -> Result<()> {
+        self.sdr.device().write_sequence(writes)
+    }
+    /// Raw bulk transfers with caller-managed buffers and timeouts, see `lowlevel`
+    pub fn bulk_reader(&self) -> lowlevel::BulkReader<'_> {
+        lowlevel::BulkReader::new(self)
+    }
+    /// Start reading with a `Reader`, which can be paused and resumed
+    pub fn reader(&mut self) -> Result<stream::Reader<'_>> {
+        stream::Reader::new(self)
+    }
+    /// Split into a `Controller` for tuning and a `Reader` for samples that can be
+    /// used from different threads. `split::unsplit` reverses it.
+    pub fn split(self) -> (split::Controller, split::Reader) {
+        split::split(self)
+    }
+    /// Read on a dedicated thread with USB transfers kept queued, delivering buffers of
+    /// `buf_len` bytes through a channel of `queue_len` entries (0 for 16). If the
+    /// consumer falls behind, buffers are dropped rather than stalling the device.
+    /// `BackgroundStream::stop` returns the device.
+    pub fn start_stream(
+        self,
+        queue_len: usize,
+        buf_len: usize,
+    ) -> Result<stream::BackgroundStream> {
+        stream::BackgroundStream::start(self, queue_len, buf_len)
+    }
+    /// Hand the device to a reader thread and stream its raw buffers of `buf_len`
+    /// bytes as a `futures_core::Stream`, reading up to `queue_len` buffers (0 for 8)
+    /// ahead. The stream is also the device's `AsyncRead`: reads wait on the thread
+    /// instead of blocking the executor. `ByteStream::stop` returns the device.
+    #[cfg(any(feature = "async-tokio", feature = "futures-io"))]
+    pub fn byte_stream(self, queue_len: usize, buf_len: usize) -> Result<stream::ByteStream> {
+        stream::ByteStream::start(self, queue_len, buf_len)
+    }
+    /// Hand the device to a `ResumingReader`, which reopens it by serial and restores
+    /// its configuration after host suspend or USB re-enumeration
+    pub fn resuming_reader(self) -> Result<stream::ResumingReader> {
+        stream::ResumingReader::new(self)
+    }
+    /// Stream |IQ| magnitudes averaged over `decimation` samples, for consumers that only
+    /// need power. Call `reset_buffer` first, as with `read_sync`.
+    pub fn magnitude_stream(&self, decimation: usize) -> stream::MagnitudeStream<'_> {
+        stream::MagnitudeStream::new(self, decimation)
+    }
+    /// Read complex samples at several sample rates at once, e.g. full rate for a
+    /// decoder next to an audio-rate tap. Add taps with `MultiRateReader::add_tap`.
+    pub fn multi_rate_reader(&self) -> stream::MultiRateReader<'_> {
+        stream::MultiRateReader::new(self)
+    }
+    /// Capture with a noise source on GPIO `pin` toggled every `blocks_per_state` USB
+    /// blocks, starting off. Call `reset_buffer` first, as with `read_sync`.
+    pub fn dicke_switch(
+        &self,
+        pin: u8,
+        blocks_per_state: usize,
+    ) -> Result<stream::DickeSwitch<'_>> {
+        stream::DickeSwitch::new(self, pin, blocks_per_state)
+    }
+    /// Capture with a GPS PPS signal on GPIO `pin` (switched to an input), tagging
+    /// blocks with its edges. Call `reset_buffer` first, as with `read_sync`.
+    pub fn pps_tagger(&self, pin: u8) -> Result<stream::PpsTagger<'_>> {
+        stream::PpsTagger::new(self, pin)
+    }
+    /// Integrate band power over fixed windows, optionally alternating with a
+    /// GPIO-switched reference. Call `reset_buffer` first, as with `read_sync`.
+    pub fn radiometer(&self, config: stream::RadiometerConfig) -> Result<stream::Radiometer<'_>> {
+        stream::Radiometer::new(self, config)
+    }
+    /// Log average and peak power of a list of channels to `out`, one record per
+    /// channel and window. Retunes the device; set the sample rate and gain first.
+    pub fn channel_power_logger<W: std::io::Write>(
+        &mut self,
+        config: stream::ChannelPowerConfig,
+        format: stream::PowerFormat,
+        out: W,
+    ) -> Result<stream::ChannelPowerLogger<'_, W>> {
+        stream::ChannelPowerLogger::new(self, config, format, out)
+    }
+    /// Average the power spectrum around `center_freq` while retuning by a random
+    /// offset between blocks and shifting it back digitally, so spurs of the dongle
+    /// itself average out instead of building up. Retunes the device; set the sample
+    /// rate and gain first.
+    pub fn dithered_spectrum(
+        &mut self,
+        center_freq: u32,
+        config: stream::FreqDitherConfig,
+    ) -> Result<stream::DitheredSpectrum<'_>> {
+        stream::DitheredSpectrum::new(self, center_freq, config)
+    }
     pub fn get_center_freq(&self) -> u32 {
         self.sdr.get_center_freq()
     }
+    pub fn set_freq_policy(&mut self, policy: FreqPolicy) {
+        self.freq_policy = policy;
+    }
+    pub fn get_freq_policy(&self) -> FreqPolicy {
+        self.freq_policy
+    }
     pub fn set_center_freq(&mut self, freq: u32) -> Result<()> {
-        self.sdr.set_center_freq(freq)
+        self.check_freq_range(freq)?;
+        let previous = self.get_center_freq_hz();
+        self.sdr.set_center_freq(freq)?;
+        self.notify_center_freq(previous);
+        Ok(())
+    }
+    /// Frequency of an up- or downconverter's LO to add to the tuned frequency, in
+    /// Hz: positive for a downconverter (10 GHz received at 250 MHz with a 9.75 GHz
+    /// LO), negative for an upconverter. Used by the `_hz` frequency methods.
+    pub fn set_converter_offset(&mut self, offset: i64) {
+        let previous = self.get_center_freq_hz();
+        self.converter_offset = offset;
+        self.notify_center_freq(previous);
+    }
+    pub fn get_converter_offset(&self) -> i64 {
+        self.converter_offset
+    }
+    /// Tune to `freq` in Hz ahead of the converter set with `set_converter_offset`,
+    /// without the 4.29 GHz limit of `set_center_freq`
+    pub fn set_center_freq_hz(&mut self, freq: u64) -> Result<()> {
+        let tuned = freq as i128 - self.converter_offset as i128;
+        let tuned = u32::try_from(tuned).map_err(|_| {
+            RtlsdrErr(format!(
+                "{} Hz is {} Hz after the converter offset, which can't be tuned",
+                freq, tuned
+            ))
+        })?;
+        self.set_center_freq(tuned)
+    }
+    /// Center frequency in Hz ahead of the converter, see `set_center_freq_hz`
+    pub fn get_center_freq_hz(&self) -> u64 {
+        rf_freq(self.sdr.get_center_freq(), self.converter_offset)
+    }
+    // Tell observers about a change of the received frequency from `previous`
+    fn notify_center_freq(&self, previous: u64) {
+        let new = self.get_center_freq_hz();
+        if previous != new {
+            self.notify(ConfigEvent::CenterFreq { previous, new });
+        }
+    }
+    // Apply the frequency policy before tuning to `freq`
+    fn check_freq_range(&mut self, freq: u32) -> Result<()> {
+        if self.freq_policy == FreqPolicy::Ignore {
+            return Ok(());
+        }
+        let mode = self.sdr.get_direct_sampling();
+        let ranges = self.sdr.get_freq_ranges(mode)?;
+        if in_ranges(&ranges, freq) {
+            return Ok(());
+        }
+        if self.freq_policy == FreqPolicy::AutoSwitch {
+            let other = match mode {
+                DirectSampleMode::Off => DirectSampleMode::On,
+                DirectSampleMode::On | DirectSampleMode::OnSwap => DirectSampleMode::Off,
+            };
+            if in_ranges(&self.sdr.get_freq_ranges(other)?, freq) {
+                info!("Switching direct sampling to {:?} for {} Hz", other, freq);
+                return self.set_direct_sampling(other);
+            }
+        }
+        let (min_freq, max_freq) = self.sdr.get_freq_range(mode)?;
+        let nearest = ranges
+            .iter()
+            .map(|(min, max)| freq.clamp(*min, *max))
+            .min_by_key(|f| f.abs_diff(freq))
+            .unwrap_or(freq);
+        Err(error::FreqRangeError {
+            freq,
+            min_freq,
+            max_freq,
+            nearest,
+            direct_sampling: mode,
+        }
+        .into())
+    }
+    /// Tuner type and the frequency ranges it can tune without direct sampling
+    pub fn tuner_capabilities(&self) -> Result<tuners::TunerCapabilities> {
+        self.sdr.tuner_capabilities()
+    }
+    /// Register a known spur of this device, in Hz
+    pub fn add_birdie(&mut self, freq: u32) {
+        if !self.birdies.contains(&freq) {
+            self.birdies.push(freq);
+        }
+    }
+    pub fn remove_birdie(&mut self, freq: u32) {
+        self.birdies.retain(|f| *f != freq);
+    }
+    pub fn birdies(&self) -> &[u32] {
+        &self.birdies
+    }
+    /// Notches for the registered birdies inside the current capture band, to apply to
+    /// converted samples. Build a new bank after retuning or changing the sample rate.
+    pub fn notch_bank(&self, width: u32) -> dsp::NotchBank {
+        let spurs: Vec<u64> = self.birdies.iter().map(|f| *f as u64).collect();
+        dsp::NotchBank::new(
+            self.get_center_freq() as u64,
+            self.get_sample_rate(),
+            &spurs,
+            width,
+        )
+    }
+    /// Harmonics of the crystal clock inside the current capture band, in Hz
+    pub fn clock_harmonics(&self) -> Vec<u64> {
+        dsp::clock_harmonics(
+            self.get_center_freq() as u64,
+            self.get_sample_rate(),
+            self.sdr.get_xtal_freq(),
+        )
+    }
+    /// Like `notch_bank`, but for the crystal harmonics in the current band in addition
+    /// to the registered birdies
+    pub fn notch_bank_with_harmonics(&self, width: u32) -> dsp::NotchBank {
+        let mut spurs: Vec<u64> = self.birdies.iter().map(|f| *f as u64).collect();
+        spurs.extend(self.clock_harmonics());
+        spurs.sort_unstable();
+        spurs.dedup();
+        dsp::NotchBank::new(
+            self.get_center_freq() as u64,
+            self.get_sample_rate(),
+            &spurs,
+            width,
+        )
+    }
+    /// Track the frequency error against a reference carrier at `reference_freq` while
+    /// the crystal warms up, to find out when narrowband reception becomes reliable.
+    /// Each measurement retunes, as `measure_freq_accuracy`.
+    pub fn settle_tracker(
+        &mut self,
+        reference_freq: u32,
+        config: stream::SettleConfig,
+    ) -> stream::SettleTracker<'_> {
+        stream::SettleTracker::new(self, reference_freq, config)
+    }
+    /// Tune next to a known reference carrier at `reference_freq`, measure where it
+    /// appears and report the frequency error. The previous center frequency is
+    /// restored afterwards. Gain should be set so the carrier is strong but not clipping.
+    pub fn measure_freq_accuracy(&mut self, reference_freq: u32) -> Result<dsp::FreqAccuracy> {
+        let previous = self.get_center_freq();
+        let tuned = reference_freq.saturating_sub(self.get_sample_rate() / 4);
+        let mut raw = vec![0; dsp::beat::DEFAULT_FFT_LEN * 2 * dsp::beat::DEFAULT_FRAMES];
+        let captured = self.set_center_freq(tuned).and_then(|_| {
+            self.reset_buffer()?;
+            // Let the tuner settle before capturing
+            self.flush(Duration::from_millis(100))?;
+            let mut filled = 0;
+            while filled < raw.len() {
+                filled += self.read_sync(&mut raw[filled..])?;
+            }
+            Ok(())
+        });
+        // Retune even if the capture failed
+        let restored = self.set_center_freq(previous);
+        captured?;
+        restored?;
+
+        let (offset, snr_db) =
+            dsp::find_carrier(&raw, self.get_sample_rate(), dsp::beat::DEFAULT_FFT_LEN)
+                .ok_or_else(|| RtlsdrErr("Capture too short to measure".to_string()))?;
+        Ok(dsp::beat::freq_accuracy(
+            reference_freq,
+            tuned,
+            offset,
+            snr_db,
+        ))
+    }
+    /// Choose between zero-IF and low-IF operation. Only tuners that can output I/Q at
+    /// zero IF support `IfMode::ZeroIf`; the R820T family is low-IF only.
+    pub fn set_if_mode(&mut self, mode: IfMode) -> Result<()> {
+        self.sdr.set_if_mode(mode)
+    }
+    pub fn get_if_mode(&self) -> IfMode {
+        self.sdr.get_if_mode()
+    }
+    /// Tuner chip probed during init, to decide which gain ranges and direct sampling
+    /// strategies apply
+    pub fn get_tuner_type(&self) -> tuners::TunerType {
+        self.sdr.get_tuner_type()
     }
     pub fn get_tuner_gains(&self) -> Result<Vec<i32>> {
         self.sdr.get_tuner_gains()
     }
     pub fn set_tuner_gain(&mut self, gain: TunerGain) -> Result<()> {
-        self.sdr.set_tuner_gain(gain)
+        self.gain_dither = None;
+        let previous = self.sdr.get_tuner_gain();
+        self.sdr.set_tuner_gain(gain)?;
+        if previous != gain {
+            self.notify(ConfigEvent::TunerGain {
+                previous,
+                new: gain,
+            });
+        }
+        Ok(())
+    }
+    /// Load a custom gain partition table for the tuner (see `tuners::gain_table`
+    /// for the file format) and reapply the current manual gain with it
+    pub fn load_gain_table<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<()> {
+        let table = tuners::GainTable::load(path)?;
+        self.sdr.set_gain_table(Some(table))
+    }
+    /// Set (or with `None`, remove) a custom gain partition table
+    pub fn set_gain_table(&mut self, table: Option<tuners::GainTable>) -> Result<()> {
+        self.sdr.set_gain_table(table)
+    }
+    /// Turn the tuner's hardware AGC (R820T: LNA and mixer) on or off without changing
+    /// the gain mode, e.g. to run LNA AGC with a fixed manual gain. Stays in effect
+    /// across `set_tuner_gain` calls; automatic gain always runs the AGC.
+    pub fn set_tuner_agc(&mut self, on: bool) -> Result<()> {
+        self.sdr.set_tuner_agc(on)
+    }
+    /// Set the LNA gain index (0-15) directly, with LNA AGC off, for linearity tuning
+    /// beyond what the combined gain table offers. The next `set_tuner_gain`
+    /// replaces it.
+    pub fn set_lna_gain(&mut self, index: u8) -> Result<()> {
+        self.sdr.set_stage_gain(tuners::GainStage::Lna, index)
+    }
+    /// Set the mixer gain index (0-15) directly, with mixer AGC off. The next
+    /// `set_tuner_gain` replaces it.
+    pub fn set_mixer_gain(&mut self, index: u8) -> Result<()> {
+        self.sdr.set_stage_gain(tuners::GainStage::Mixer, index)
+    }
+    /// Set the VGA gain index (0-15) directly. Kept across `set_tuner_gain` calls,
+    /// like `set_tuner_if_gain`.
+    pub fn set_vga_gain(&mut self, index: u8) -> Result<()> {
+        self.sdr.set_stage_gain(tuners::GainStage::Vga, index)
+    }
+    /// Set one of the tuner's IF gain stages (numbered from 1) to the nearest
+    /// supported gain, in tenths of a dB, for fine sensitivity tuning alongside the
+    /// overall tuner gain. The R820T family has a single stage, its VGA.
+    pub fn set_tuner_if_gain(&mut self, stage: u8, gain: i32) -> Result<()> {
+        self.sdr.set_tuner_if_gain(stage, gain)
+    }
+    pub fn get_tuner_agc(&self) -> bool {
+        self.sdr.get_tuner_agc()
+    }
+    /// Turn the RTL2832's digital AGC, which scales samples after the ADC, on or off
+    pub fn set_rtl_agc(&mut self, on: bool) -> Result<()> {
+        self.sdr.set_rtl_agc(on)
+    }
+    pub fn get_rtl_agc(&self) -> bool {
+        self.sdr.get_rtl_agc()
+    }
+    /// Tune the digital AGC loop, e.g. slow it down so it doesn't pump on bursty
+    /// signals. See `rtlsdr::DigitalAgc` for the safe ranges.
+    pub fn set_digital_agc(&self, params: rtlsdr::DigitalAgc) -> Result<()> {
+        self.sdr.set_digital_agc(params)
+    }
+    pub fn get_digital_agc(&self) -> Result<rtlsdr::DigitalAgc> {
+        self.sdr.get_digital_agc()
+    }
+    /// Supported gain closest to `db` dB, in tenths of a dB as taken by
+    /// `set_tuner_gain`
+    pub fn nearest_gain(&self, db: f32) -> Result<i32> {
+        let gains = self.sdr.get_tuner_gains()?;
+        nearest_or_err(&gains, (db * 10.0).round() as i32)
+    }
+    /// Set a manual gain, using `strategy` to map it onto the tuner's gain table
+    pub fn set_gain_strategy(&mut self, strategy: GainStrategy) -> Result<()> {
+        let gains = self.sdr.get_tuner_gains()?;
+        match strategy {
+            GainStrategy::Nearest(target) => {
+                let gain = nearest_or_err(&gains, target)?;
+                self.set_tuner_gain(TunerGain::Manual(gain))
+            }
+            GainStrategy::Interpolated(target) => {
+                let mut dither = GainDither::new(&gains, target)
+                    .ok_or_else(|| RtlsdrErr("Tuner has no gain table".to_string()))?;
+                if dither.is_fixed() {
+                    return self.set_tuner_gain(TunerGain::Manual(dither.next_gain()));
+                }
+                info!(
+                    "Dithering gain for an average of {} (requested {})",
+                    dither.average_gain(),
+                    target
+                );
+                self.gain_dither = Some(dither);
+                self.advance_gain_dither()
+            }
+            GainStrategy::AutoOnOpen => {
+                let previous = self.sdr.get_tuner_gain();
+                let measured = self.measure_auto_gain(&gains);
+                // The steps bypass the observers; go back so the setter reports the
+                // net change
+                self.sdr.set_tuner_gain(previous)?;
+                let gain = measured?;
+                info!("Selected gain {} without ADC clipping", gain);
+                self.set_tuner_gain(TunerGain::Manual(gain))
+            }
+        }
+    }
+    // Highest of a few gain steps whose samples don't clip
+    fn measure_auto_gain(&mut self, gains: &[i32]) -> Result<i32> {
+        if self.sdr.get_sample_rate() == 0 {
+            return Err(RtlsdrErr(
+                "Set a sample rate before selecting the gain automatically".to_string(),
+            ));
+        }
+        let candidates = gain::auto_gain_candidates(gains, gain::AUTO_GAIN_STEPS);
+        let lowest = *candidates
+            .last()
+            .ok_or_else(|| RtlsdrErr("Tuner has no gain table".to_string()))?;
+        let mut buf = vec![0; DEFAULT_BUF_LENGTH];
+        for gain in candidates {
+            self.sdr.set_tuner_gain(TunerGain::Manual(gain))?;
+            self.sdr.reset_buffer()?;
+            // The first block still contains samples from before the gain change
+            self.sdr.read_sync(&mut buf)?;
+            let len = self.sdr.read_sync(&mut buf)?;
+            let clip = gain::clip_fraction(&buf[..len]);
+            debug!("Gain {}: {:.5} of samples clipped", gain, clip);
+            if clip <= gain::AUTO_GAIN_MAX_CLIP {
+                return Ok(gain);
+            }
+        }
+        Ok(lowest)
+    }
+    /// Apply the next step of an interpolated gain, if one is active
+    pub fn advance_gain_dither(&mut self) -> Result<()> {
+        let gain = match self.gain_dither.as_mut() {
+            Some(dither) => dither.next_gain(),
+            None => return Ok(()),
+        };
+        self.sdr.set_tuner_gain(TunerGain::Manual(gain))
     }
     pub fn get_freq_correction(&self) -> i32 {
         self.sdr.get_freq_correction()
     }
     pub fn set_freq_correction(&mut self, ppm: i32) -> Result<()> {
-        self.sdr.set_freq_correction(ppm)
+        let previous = self.sdr.get_freq_correction();
+        self.sdr.set_freq_correction(ppm)?;
+        if previous != ppm {
+            self.notify(ConfigEvent::FreqCorrection { previous, new: ppm });
+        }
+        Ok(())
+    }
+    /// RTL2832 and tuner crystal frequencies in Hz, with the frequency correction
+    /// applied
+    pub fn get_xtal_freq(&self) -> (u32, u32) {
+        (self.sdr.get_xtal_freq(), self.sdr.get_tuner_xtal_freq())
+    }
+    /// Set the actual crystal frequencies of a modified dongle (TCXO, external
+    /// clock), in Hz. A `rtl_freq` of 0 keeps the current one; a `tuner_freq` of 0
+    /// means the tuner runs off the RTL2832's clock. Sample rate and tuning are
+    /// recalculated from the new values.
+    pub fn set_xtal_freq(&mut self, rtl_freq: u32, tuner_freq: u32) -> Result<()> {
+        let previous = self.sdr.get_sample_rate();
+        self.sdr.set_xtal_freq(rtl_freq, tuner_freq)?;
+        let new = self.sdr.get_sample_rate();
+        if previous != new {
+            self.notify(ConfigEvent::SampleRate { previous, new });
+        }
+        Ok(())
+    }
+    /// Sample rates `set_sample_rate` accepts, in Hz, as inclusive ranges. Rates
+    /// outside them fail with `RtlsdrError::InvalidSampleRate`.
+    pub fn valid_sample_rates() -> &'static [(u32, u32)] {
+        &rtlsdr::SAMPLE_RATE_RANGES
     }
+    /// Achieved sample rate, rounded to the nearest Hz
     pub fn get_sample_rate(&self) -> u32 {
         self.sdr.get_sample_rate()
     }
-    pub fn set_sample_rate(&mut self, rate: u32) -> Result<()> {
-        self.sdr.set_sample_rate(rate)
+    /// Achieved sample rate in Hz, for resamplers that need to compensate for the
+    /// difference to the requested one
+    pub fn get_exact_sample_rate(&self) -> f64 {
+        self.sdr.get_exact_sample_rate()
     }
+    /// Program the achievable rate closest to `rate` and return it. The difference
+    /// is logged as a warning when there is one.
+    pub fn set_sample_rate(&mut self, rate: u32) -> Result<rtlsdr::AchievedRate> {
+        let previous = self.sdr.get_sample_rate();
+        let previous_bw = self.sdr.get_tuner_bandwidth()?;
+        let achieved = self.sdr.set_sample_rate(rate)?;
+        let new = self.sdr.get_sample_rate();
+        if previous != new {
+            self.notify(ConfigEvent::SampleRate { previous, new });
+        }
+        self.notify_bandwidth(previous_bw)?;
+        Ok(achieved)
+    }
+    /// Set the tuner's IF filter bandwidth in Hz. 0 (the default) makes the filter
+    /// follow the sample rate, re-programming it on every `set_sample_rate`.
     pub fn set_tuner_bandwidth(&mut self, bw: u32) -> Result<()> {
-        self.sdr.set_tuner_bandwidth(bw)
+        let previous = self.sdr.get_tuner_bandwidth()?;
+        self.sdr.set_tuner_bandwidth(bw)?;
+        self.notify_bandwidth(previous)
+    }
+    fn notify_bandwidth(&self, previous: u32) -> Result<()> {
+        let new = self.sdr.get_tuner_bandwidth()?;
+        if previous != new {
+            self.notify(ConfigEvent::TunerBandwidth { previous, new });
+        }
+        Ok(())
+    }
+    /// Whether the IF filter bandwidth follows the sample rate
+    pub fn is_tuner_bandwidth_auto(&self) -> bool {
+        self.sdr.is_tuner_bandwidth_auto()
+    }
+    /// IF filter bandwidth in effect, in Hz: the requested one rounded to a
+    /// supported setting
+    pub fn get_tuner_bandwidth(&self) -> Result<u32> {
+        self.sdr.get_tuner_bandwidth()
+    }
+    /// IF filter bandwidths the tuner supports, in Hz, ascending
+    pub fn get_tuner_bandwidths(&self) -> Result<Vec<u32>> {
+        self.sdr.get_tuner_bandwidths()
+    }
+    pub fn read_eeprom(&self, offset: u8, len: usize) -> Result<Vec<u8>> {
+        self.sdr.read_eeprom(offset, len)
+    }
+    pub fn write_eeprom(&self, data: &[u8], offset: u8) -> Result<usize> {
+        self.sdr.write_eeprom(data, offset)
+    }
+    /// Change the IR endpoint and remote wakeup flags in the EEPROM, returning the bytes
+    /// that change. With `dry_run` nothing is written. Takes effect after replugging.
+    pub fn set_eeprom_flags(
+        &self,
+        flags: eeprom::EepromFlags,
+        dry_run: bool,
+    ) -> Result<Vec<eeprom::EepromChange>> {
+        let image = self.sdr.read_eeprom(0, device::EEPROM_SIZE)?;
+        let (new, changes) = eeprom::apply_flags(&image, flags)?;
+        if dry_run || changes.is_empty() {
+            return Ok(changes);
+        }
+        for change in changes.iter() {
+            self.sdr.write_eeprom(&[change.new], change.offset as u8)?;
+        }
+        // Verify what actually landed in the EEPROM
+        let written = self.sdr.read_eeprom(0, device::EEPROM_SIZE)?;
+        if !eeprom::diff(&written, &new).is_empty() {
+            return Err(RtlsdrErr("EEPROM flag verification failed".to_string()));
+        }
+        Ok(changes)
+    }
+    /// Read the user data stored with `write_user_data`, or `None` if there is none
+    pub fn read_user_data(&self) -> Result<Option<Vec<u8>>> {
+        let image = self.sdr.read_eeprom(0, device::EEPROM_SIZE)?;
+        eeprom::decode_user_data(&image)
+    }
+    /// Store up to `eeprom::USER_DATA_MAX_LEN` bytes in the unused part of the EEPROM
+    pub fn write_user_data(&self, data: &[u8]) -> Result<()> {
+        let image = self.sdr.read_eeprom(0, device::EEPROM_SIZE)?;
+        let strings_end = eeprom::strings_end(&image);
+        if strings_end > eeprom::USER_DATA_OFFSET {
+            return Err(RtlsdrErr(format!(
+                "USB strings extend to {:#04x}, overlapping the user data area",
+                strings_end
+            )));
+        }
+        let area = eeprom::encode_user_data(data)?;
+        self.sdr
+            .write_eeprom(&area, eeprom::USER_DATA_OFFSET as u8)?;
+        // Verify what actually landed in the EEPROM
+        let image = self.sdr.read_eeprom(0, device::EEPROM_SIZE)?;
+        match eeprom::decode_user_data(&image)? {
+            Some(stored) if stored == data => Ok(()),
+            _ => Err(RtlsdrErr("User data verification failed".to_string())),
+        }
     }
     pub fn set_testmode(&mut self, on: bool) -> Result<()> {
         self.sdr.set_testmode(on)
     }
+    /// Direct sampling mode last set, without a register read; see `get_adc_input`
+    /// for the demodulator's own view
+    pub fn get_direct_sampling(&self) -> DirectSampleMode {
+        self.sdr.get_direct_sampling()
+    }
     pub fn set_direct_sampling(&mut self, mode: DirectSampleMode) -> Result<()> {
-        self.sdr.set_direct_sampling(mode)
+        let previous = self.sdr.get_direct_sampling();
+        self.sdr.set_direct_sampling(mode)?;
+        let new = self.sdr.get_direct_sampling();
+        if previous != new {
+            self.notify(ConfigEvent::DirectSampling { previous, new });
+        }
+        Ok(())
     }
+    /// Select the ADC input; equivalent to the matching `DirectSampleMode`
+    pub fn set_adc_input(&mut self, input: AdcInput) -> Result<()> {
+        self.set_direct_sampling(input.into())
+    }
+    /// ADC input currently selected, read back from the demodulator
+    pub fn get_adc_input(&self) -> Result<AdcInput> {
+        self.sdr.get_adc_input()
+    }
+    /// Mirror the spectrum in hardware, e.g. behind an inverting downconverter or
+    /// for a decoder that expects the other sideband orientation
+    pub fn set_spectrum_inversion(&mut self, on: bool) -> Result<()> {
+        self.sdr.set_spectrum_inversion(on)
+    }
+    pub fn get_spectrum_inversion(&self) -> bool {
+        self.sdr.get_spectrum_inversion()
+    }
+    /// Swap I and Q in the samples returned by `read_sync` and the readers built on
+    /// it. Done in software; `bulk_reader` returns the unswapped samples.
+    pub fn set_iq_swap(&mut self, on: bool) {
+        self.sdr.set_iq_swap(on)
+    }
+    pub fn get_iq_swap(&self) -> bool {
+        self.sdr.get_iq_swap()
+    }
+    /// Whether the board is known to have a switchable bias tee
+    pub fn has_bias_tee(&self) -> bool {
+        self.sdr.has_bias_tee()
+    }
+
     pub fn set_bias_tee(&self, on: bool) -> Result<()> {
-        self.sdr.set_bias_tee(on)
+        self.set_bias_tee_gpio(BIAS_TEE_GPIO, on)
+    }
+    /// Switch a bias tee wired to another GPIO than the usual 0, as on some custom
+    /// boards
+    pub fn set_bias_tee_gpio(&self, pin: u8, on: bool) -> Result<()> {
+        let previous = self.sdr.get_gpio(pin)?;
+        self.sdr.set_bias_tee_gpio(pin, on)?;
+        let new = self.sdr.get_gpio(pin)?;
+        if previous != new {
+            self.notify(ConfigEvent::BiasTee { pin, previous, new });
+        }
+        Ok(())
+    }
+    /// Whether the bias tee on GPIO 0 is on. Read back from the device, so it also
+    /// reflects an EEPROM override.
+    pub fn get_bias_tee(&self) -> Result<bool> {
+        self.sdr.get_bias_tee()
+    }
+    /// Drive a GPIO pin as an output. Observers see the level read back afterwards.
+    pub fn set_gpio(&self, pin: u8, on: bool) -> Result<()> {
+        let previous = self.sdr.get_gpio(pin)?;
+        self.sdr.set_gpio_pin(pin, on)?;
+        let new = self.sdr.get_gpio(pin)?;
+        if previous != new {
+            self.notify(ConfigEvent::Gpio { pin, previous, new });
+        }
+        Ok(())
     }
 }
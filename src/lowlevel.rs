@@ -0,0 +1,62 @@
+//! Direct access to the sample endpoint for experiments with custom streaming
+//! strategies. Open, initialize and tune the device as usual, then read through
+//! `RtlSdr::bulk_reader`: no buffering, chunking, gain dithering or statistics are
+//! involved, and the caller owns every buffer.
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::RtlSdr;
+
+/// Raw bulk transfers from the sample endpoint, created by `RtlSdr::bulk_reader`
+pub struct BulkReader<'a> {
+    sdr: &'a RtlSdr,
+    timeout: Duration,
+}
+
+impl<'a> BulkReader<'a> {
+    pub(crate) fn new(sdr: &'a RtlSdr) -> Self {
+        BulkReader {
+            sdr,
+            timeout: sdr.sdr.device().usb.bulk_timeout,
+        }
+    }
+
+    /// Address of the bulk IN endpoint samples arrive on
+    pub fn endpoint(&self) -> u8 {
+        self.sdr.sdr.device().usb.bulk_endpoint
+    }
+
+    /// Timeout used by `read`. Zero waits forever, as in libusb.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Flush the device FIFO, e.g. before the first transfer of a new strategy
+    pub fn reset_fifo(&self) -> Result<()> {
+        self.sdr.reset_buffer()
+    }
+
+    /// One bulk transfer into `buf`, returning how many bytes arrived. Use a length
+    /// that is a multiple of 512, the endpoint's packet size. A timeout is reported as
+    /// `RtlsdrError::Usb(rusb::Error::Timeout)` and the bytes of that transfer are lost.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        self.read_timeout(buf, self.timeout)
+    }
+
+    /// `read` with a timeout for this transfer only
+    pub fn read_timeout(&self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        let device = self.sdr.sdr.device();
+        device
+            .handle
+            .read_bulk(device.usb.bulk_endpoint, buf, timeout)
+    }
+
+    /// The device being read, e.g. to query its settings
+    pub fn sdr(&self) -> &RtlSdr {
+        self.sdr
+    }
+}
@@ -0,0 +1,284 @@
+//! Node.js bindings, enabled with the `node` feature. `node/index.js` loads the built
+//! library and adds async iteration over sample buffers.
+//!
+//! Reads and setting changes run on the libuv thread pool and return Promises, so
+//! they don't block the event loop while a read holds the device. Getters return the
+//! values cached when the device was opened or last changed.
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use napi::bindgen_prelude::{AsyncTask, Buffer};
+use napi::{Env, Error, Task};
+use napi_derive::napi;
+
+use crate::error::RtlsdrError;
+use crate::{RtlSdr, TunerGain};
+
+// Bulk reads must be a multiple of the USB packet size
+const READ_GRANULARITY: usize = 512;
+
+fn js_err(e: RtlsdrError) -> Error {
+    Error::from_reason(e.to_string())
+}
+
+// Shared device slot, emptied by `close`
+type SharedSdr = Arc<Mutex<Option<RtlSdr>>>;
+
+// Work on the device slot for a `DeviceTask`
+type SlotFn = Box<dyn FnOnce(&mut Option<RtlSdr>) -> napi::Result<()> + Send>;
+
+// Settings the getters return without waiting for the device lock
+#[derive(Debug, Clone, Copy, Default)]
+struct Cached {
+    center_freq: u32,
+    sample_rate: u32,
+}
+
+impl Cached {
+    fn of(sdr: &RtlSdr) -> Self {
+        Cached {
+            center_freq: sdr.get_center_freq(),
+            sample_rate: sdr.get_sample_rate(),
+        }
+    }
+}
+
+// Locked device that is known to still be open
+struct OpenSdr<'a>(MutexGuard<'a, Option<RtlSdr>>);
+
+impl Deref for OpenSdr<'_> {
+    type Target = RtlSdr;
+
+    fn deref(&self) -> &RtlSdr {
+        self.0.as_ref().expect("checked in lock")
+    }
+}
+
+impl DerefMut for OpenSdr<'_> {
+    fn deref_mut(&mut self) -> &mut RtlSdr {
+        self.0.as_mut().expect("checked in lock")
+    }
+}
+
+fn lock_slot(sdr: &Mutex<Option<RtlSdr>>) -> napi::Result<MutexGuard<'_, Option<RtlSdr>>> {
+    sdr.lock()
+        .map_err(|_| Error::from_reason("Device lock poisoned by an earlier failure"))
+}
+
+fn lock(sdr: &Mutex<Option<RtlSdr>>) -> napi::Result<OpenSdr<'_>> {
+    let guard = lock_slot(sdr)?;
+    if guard.is_none() {
+        return Err(Error::from_reason("Device is closed"));
+    }
+    Ok(OpenSdr(guard))
+}
+
+/// A supported device attached to the host
+#[napi(object)]
+pub struct DeviceEntry {
+    pub index: u32,
+    pub name: String,
+    pub serial: String,
+    pub vid: u32,
+    pub pid: u32,
+}
+
+/// Supported devices in the order used by `RtlSdr.open`
+#[napi]
+pub fn list_devices() -> napi::Result<Vec<DeviceEntry>> {
+    let devices = RtlSdr::enumerate().map_err(js_err)?;
+    Ok(devices
+        .into_iter()
+        .map(|dev| DeviceEntry {
+            index: dev.index as u32,
+            name: dev.name,
+            serial: dev.serial,
+            vid: dev.vid as u32,
+            pid: dev.pid as u32,
+        })
+        .collect())
+}
+
+/// An open RTL-SDR device
+#[napi(js_name = "RtlSdr")]
+pub struct NodeRtlSdr {
+    sdr: SharedSdr,
+    cached: Arc<Mutex<Cached>>,
+    // Supported gains in dB, fixed by the tuner
+    gains: Vec<f64>,
+}
+
+impl NodeRtlSdr {
+    fn new(sdr: RtlSdr) -> napi::Result<Self> {
+        let gains = sdr.get_tuner_gains().map_err(js_err)?;
+        Ok(NodeRtlSdr {
+            cached: Arc::new(Mutex::new(Cached::of(&sdr))),
+            gains: gains.iter().map(|g| *g as f64 / 10.0).collect(),
+            sdr: Arc::new(Mutex::new(Some(sdr))),
+        })
+    }
+
+    fn cached(&self) -> Cached {
+        *self.cached.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Run `apply` on the open device off the JS thread
+    fn change<F>(&self, apply: F) -> AsyncTask<DeviceTask>
+    where
+        F: FnOnce(&mut RtlSdr) -> crate::error::Result<()> + Send + 'static,
+    {
+        self.task(Box::new(move |slot| match slot.as_mut() {
+            Some(sdr) => apply(sdr).map_err(js_err),
+            None => Err(Error::from_reason("Device is closed")),
+        }))
+    }
+
+    fn task(&self, apply: SlotFn) -> AsyncTask<DeviceTask> {
+        AsyncTask::new(DeviceTask {
+            sdr: self.sdr.clone(),
+            cached: self.cached.clone(),
+            apply: Some(apply),
+        })
+    }
+}
+
+#[napi]
+impl NodeRtlSdr {
+    #[napi(factory)]
+    pub fn open(index: u32) -> napi::Result<Self> {
+        Self::new(RtlSdr::open_by_index(index as usize).map_err(js_err)?)
+    }
+
+    #[napi(factory)]
+    pub fn open_by_serial(serial: String) -> napi::Result<Self> {
+        Self::new(RtlSdr::open_by_serial(&serial).map_err(js_err)?)
+    }
+
+    /// Close the device once a pending read has finished
+    #[napi]
+    pub fn close(&self) -> AsyncTask<DeviceTask> {
+        self.task(Box::new(|slot| match slot.take() {
+            Some(sdr) => sdr.close().map_err(js_err),
+            None => Ok(()),
+        }))
+    }
+
+    #[napi(getter)]
+    pub fn center_freq(&self) -> u32 {
+        self.cached().center_freq
+    }
+
+    #[napi]
+    pub fn set_center_freq(&self, freq: u32) -> AsyncTask<DeviceTask> {
+        self.change(move |sdr| sdr.set_center_freq(freq))
+    }
+
+    #[napi(getter)]
+    pub fn sample_rate(&self) -> u32 {
+        self.cached().sample_rate
+    }
+
+    #[napi]
+    pub fn set_sample_rate(&self, rate: u32) -> AsyncTask<DeviceTask> {
+        self.change(move |sdr| sdr.set_sample_rate(rate).map(|_| ()))
+    }
+
+    #[napi]
+    pub fn set_freq_correction(&self, ppm: i32) -> AsyncTask<DeviceTask> {
+        self.change(move |sdr| sdr.set_freq_correction(ppm))
+    }
+
+    /// Gain in dB, or null for automatic gain
+    #[napi]
+    pub fn set_gain(&self, gain: Option<f64>) -> AsyncTask<DeviceTask> {
+        let gain = match gain {
+            Some(db) => TunerGain::Manual((db * 10.0).round() as i32),
+            None => TunerGain::Auto,
+        };
+        self.change(move |sdr| sdr.set_tuner_gain(gain))
+    }
+
+    /// Supported gains in dB
+    #[napi]
+    pub fn tuner_gains(&self) -> Vec<f64> {
+        self.gains.clone()
+    }
+
+    #[napi]
+    pub fn set_bias_tee(&self, on: bool) -> AsyncTask<DeviceTask> {
+        self.change(move |sdr| sdr.set_bias_tee(on))
+    }
+
+    #[napi]
+    pub fn reset_buffer(&self) -> AsyncTask<DeviceTask> {
+        self.change(|sdr| sdr.reset_buffer())
+    }
+
+    /// Read `numBytes` of raw interleaved u8 I/Q, resolving to a Buffer
+    #[napi]
+    pub fn read(&self, num_bytes: u32) -> AsyncTask<ReadTask> {
+        AsyncTask::new(ReadTask {
+            sdr: self.sdr.clone(),
+            len: num_bytes as usize,
+        })
+    }
+}
+
+/// Setting change or close run off the JS thread, waiting for any pending read
+pub struct DeviceTask {
+    sdr: SharedSdr,
+    cached: Arc<Mutex<Cached>>,
+    apply: Option<SlotFn>,
+}
+
+impl Task for DeviceTask {
+    type Output = ();
+    type JsValue = ();
+
+    fn compute(&mut self) -> napi::Result<()> {
+        let mut slot = lock_slot(&self.sdr)?;
+        let result = match self.apply.take() {
+            Some(apply) => apply(&mut slot),
+            None => Ok(()),
+        };
+        // A failed change can still have moved the device
+        if let Some(sdr) = slot.as_ref() {
+            *self.cached.lock().unwrap_or_else(|e| e.into_inner()) = Cached::of(sdr);
+        }
+        result
+    }
+
+    fn resolve(&mut self, _env: Env, _output: ()) -> napi::Result<()> {
+        Ok(())
+    }
+}
+
+/// Blocking read run off the JS thread
+pub struct ReadTask {
+    sdr: SharedSdr,
+    len: usize,
+}
+
+impl Task for ReadTask {
+    type Output = Vec<u8>;
+    type JsValue = Buffer;
+
+    fn compute(&mut self) -> napi::Result<Vec<u8>> {
+        let sdr = lock(&self.sdr)?;
+        let mut buf = vec![0; self.len.next_multiple_of(READ_GRANULARITY)];
+        let mut filled = 0;
+        while filled < self.len {
+            let n = sdr.read_sync(&mut buf[filled..]).map_err(js_err)?;
+            if n == 0 {
+                return Err(Error::from_reason("Device returned no samples"));
+            }
+            filled += n;
+        }
+        buf.truncate(self.len);
+        Ok(buf)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Vec<u8>) -> napi::Result<Buffer> {
+        Ok(output.into())
+    }
+}
@@ -0,0 +1,81 @@
+//! Notifications of hardware configuration changes, e.g. for audit logs of
+//! changes that affect connected equipment such as powering an external LNA.
+use std::sync::{Arc, Mutex};
+
+use crate::{DirectSampleMode, TunerGain};
+
+/// A configuration change, with the values before and after it was applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigEvent {
+    /// Received frequency in Hz, ahead of any converter set with
+    /// `RtlSdr::set_converter_offset`. Also sent when the converter offset changes.
+    CenterFreq {
+        previous: u64,
+        new: u64,
+    },
+    SampleRate {
+        previous: u32,
+        new: u32,
+    },
+    /// Tuner IF filter bandwidth in Hz, also sent when automatic bandwidth follows a
+    /// sample rate change
+    TunerBandwidth {
+        previous: u32,
+        new: u32,
+    },
+    FreqCorrection {
+        previous: i32,
+        new: i32,
+    },
+    TunerGain {
+        previous: TunerGain,
+        new: TunerGain,
+    },
+    DirectSampling {
+        previous: DirectSampleMode,
+        new: DirectSampleMode,
+    },
+    /// Bias tee on GPIO `pin` (0 unless switched with `RtlSdr::set_bias_tee_gpio`).
+    /// `new` can stay on when the EEPROM forces the bias tee on.
+    BiasTee {
+        pin: u8,
+        previous: bool,
+        new: bool,
+    },
+    Gpio {
+        pin: u8,
+        previous: bool,
+        new: bool,
+    },
+}
+
+/// Callback invoked after each configuration change
+pub type ConfigObserver = Box<dyn Fn(&ConfigEvent) + Send>;
+
+/// Events queued for a consumer that doesn't own the device, such as a recorder fed
+/// by a `Pipeline` thread. Register `observer()` with `RtlSdr::add_config_observer`
+/// and `drain` the queue from the consumer.
+#[derive(Clone, Default)]
+pub struct EventQueue(Arc<Mutex<Vec<ConfigEvent>>>);
+
+impl EventQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Callback appending each event to the queue
+    pub fn observer(&self) -> impl Fn(&ConfigEvent) + Send + 'static {
+        let queue = self.0.clone();
+        move |event| {
+            queue
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(event.clone())
+        }
+    }
+
+    /// Events queued since the last call, oldest first
+    pub fn drain(&self) -> Vec<ConfigEvent> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
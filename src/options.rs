@@ -0,0 +1,590 @@
+//! Options for opening and initializing a device
+use std::fmt;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+use crate::device::info::USB1_USABLE_BYTES_PER_SEC;
+use crate::device::{Device, DeviceInfo, UsbConfig};
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::error::{InitError, Result, RtlsdrError};
+use crate::gain::GainStrategy;
+use crate::platform;
+use crate::rtlsdr::{InitSteps, RtlSdr as Sdr, MAX_RTL_XTAL_FREQ};
+use crate::tuners::TunerType;
+use crate::{DirectSampleMode, RtlSdr, TunerGain};
+use log::{debug, info, warn};
+use rusb::{Context, LogLevel, Speed, UsbContext};
+
+const DEFAULT_INIT_ATTEMPTS: u32 = 3;
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(100);
+const DEFAULT_RETRY_BACKOFF: u32 = 2;
+// Longest wait between attempts, however large the backoff grows
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+const MAX_SAMPLE_RATE: u32 = 3_200_000;
+
+/// Hardware a device must have to be picked by `OpenOptions::open_matching`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Requirements {
+    /// A switchable bias tee, see `RtlSdr::has_bias_tee`
+    pub needs_bias_tee: bool,
+    pub tuner: Option<TunerType>,
+    /// Sample rate the device and its USB link must sustain, in Hz
+    pub min_rate: u32,
+}
+
+impl Requirements {
+    // Why an opened device doesn't qualify, if it doesn't
+    fn mismatch(&self, sdr: &RtlSdr) -> Option<String> {
+        if let Some(tuner) = self.tuner {
+            let found = sdr
+                .sdr
+                .get_tuner_info()
+                .map(|info| TunerType::from_id(info.id));
+            if found.as_ref().ok() != Some(&tuner) {
+                return Some(format!("tuner is {:?}, not {:?}", found, tuner));
+            }
+        }
+        if self.needs_bias_tee && !sdr.sdr.has_bias_tee() {
+            return Some("no bias tee".to_string());
+        }
+        None
+    }
+}
+
+/// Configures how a device is opened, similar to `std::fs::OpenOptions`.
+/// `RtlSdr::open_by_index` and `RtlSdr::open_by_serial` use the defaults.
+///
+/// Also serves as the configuration builder returned by `RtlSdr::builder`: settings
+/// are checked against each other before the device is touched and applied in the
+/// order the hardware needs, whatever order they were given in.
+#[derive(Clone)]
+pub struct OpenOptions {
+    init_attempts: u32,
+    retry_delay: Duration,
+    retry_backoff: u32,
+    usb: UsbConfig,
+    context: Option<Context>,
+    log_level: Option<LogLevel>,
+    sample_rate: Option<u32>,
+    center_freq: Option<u32>,
+    gain: Option<GainStrategy>,
+    tuner_gain: Option<TunerGain>,
+    freq_correction: Option<i32>,
+    bandwidth: Option<u32>,
+    direct_sampling: Option<DirectSampleMode>,
+    bias_tee: Option<bool>,
+    init_steps: InitSteps,
+    warm_up: Option<Duration>,
+}
+
+impl fmt::Debug for OpenOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // rusb::LogLevel doesn't implement Debug
+        let log_level = self.log_level.map(|level| match level {
+            LogLevel::None => "None",
+            LogLevel::Error => "Error",
+            LogLevel::Warning => "Warning",
+            LogLevel::Info => "Info",
+            LogLevel::Debug => "Debug",
+        });
+        f.debug_struct("OpenOptions")
+            .field("init_attempts", &self.init_attempts)
+            .field("retry_delay", &self.retry_delay)
+            .field("retry_backoff", &self.retry_backoff)
+            .field("usb", &self.usb)
+            .field("context", &self.context)
+            .field("log_level", &log_level)
+            .field("sample_rate", &self.sample_rate)
+            .field("center_freq", &self.center_freq)
+            .field("gain", &self.gain)
+            .field("tuner_gain", &self.tuner_gain)
+            .field("freq_correction", &self.freq_correction)
+            .field("bandwidth", &self.bandwidth)
+            .field("direct_sampling", &self.direct_sampling)
+            .field("bias_tee", &self.bias_tee)
+            .field("init_steps", &self.init_steps)
+            .field("warm_up", &self.warm_up)
+            .finish()
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        OpenOptions {
+            init_attempts: DEFAULT_INIT_ATTEMPTS,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+            usb: UsbConfig::default(),
+            context: None,
+            log_level: None,
+            sample_rate: None,
+            center_freq: None,
+            gain: None,
+            tuner_gain: None,
+            freq_correction: None,
+            bandwidth: None,
+            direct_sampling: None,
+            bias_tee: None,
+            init_steps: InitSteps::default(),
+            warm_up: None,
+        }
+    }
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of times to try the open/reset/claim/init sequence before giving up
+    /// (minimum 1). Only USB errors that can clear up on their own are retried.
+    pub fn init_attempts(&mut self, attempts: u32) -> &mut Self {
+        self.init_attempts = attempts.max(1);
+        self
+    }
+
+    /// Delay before the second attempt
+    pub fn retry_delay(&mut self, delay: Duration) -> &mut Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Factor the retry delay is multiplied by after each failed attempt, up to 10 s
+    pub fn retry_backoff(&mut self, factor: u32) -> &mut Self {
+        self.retry_backoff = factor.max(1);
+        self
+    }
+
+    /// USB interface to claim, for clones that don't use interface 0
+    pub fn interface(&mut self, interface: u8) -> &mut Self {
+        self.usb.interface = interface;
+        self
+    }
+
+    /// Bulk IN endpoint samples are read from, for clones that don't use 0x81
+    pub fn bulk_endpoint(&mut self, endpoint: u8) -> &mut Self {
+        self.usb.bulk_endpoint = endpoint;
+        self
+    }
+
+    /// Timeout for register/I2C control transfers
+    pub fn control_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.usb.ctrl_timeout = timeout;
+        self
+    }
+
+    /// Timeout for sample reads; zero waits forever
+    pub fn bulk_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.usb.bulk_timeout = timeout;
+        self
+    }
+
+    /// Open devices through an existing libusb context instead of creating a new one,
+    /// so the crate can share libusb with other users in the same process
+    pub fn context(&mut self, context: Context) -> &mut Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// libusb debug log level. Only applied to contexts created by this crate,
+    /// not to one passed in with `context`.
+    pub fn usb_log_level(&mut self, level: LogLevel) -> &mut Self {
+        self.log_level = Some(level);
+        self
+    }
+
+    /// Sample rate set right after init
+    pub fn sample_rate(&mut self, rate: u32) -> &mut Self {
+        self.sample_rate = Some(rate);
+        self
+    }
+
+    /// Center frequency set right after init, after the sample rate
+    pub fn center_freq(&mut self, freq: u32) -> &mut Self {
+        self.center_freq = Some(freq);
+        self
+    }
+
+    /// Gain applied after the sample rate and frequency, e.g. `GainStrategy::AutoOnOpen`
+    pub fn gain_strategy(&mut self, strategy: GainStrategy) -> &mut Self {
+        self.gain = Some(strategy);
+        self
+    }
+
+    /// Fixed tuner gain (or tuner AGC), as `RtlSdr::set_tuner_gain`. Can't be combined
+    /// with `gain_strategy`.
+    pub fn tuner_gain(&mut self, gain: TunerGain) -> &mut Self {
+        self.tuner_gain = Some(gain);
+        self
+    }
+
+    /// Crystal correction in PPM, applied before tuning
+    pub fn freq_correction(&mut self, ppm: i32) -> &mut Self {
+        self.freq_correction = Some(ppm);
+        self
+    }
+
+    /// Tuner IF bandwidth in Hz; by default it follows the sample rate
+    pub fn bandwidth(&mut self, bw: u32) -> &mut Self {
+        self.bandwidth = Some(bw);
+        self
+    }
+
+    /// Direct sampling mode, set before the sample rate and frequency
+    pub fn direct_sampling(&mut self, mode: DirectSampleMode) -> &mut Self {
+        self.direct_sampling = Some(mode);
+        self
+    }
+
+    /// Bias tee state, switched last so an LNA is only powered once the rest is set up
+    pub fn bias_tee(&mut self, on: bool) -> &mut Self {
+        self.bias_tee = Some(on);
+        self
+    }
+
+    /// Discard samples for `duration` once the device is configured, so reading
+    /// starts after the worst of the tuner and crystal warm-up
+    pub fn warm_up(&mut self, duration: Duration) -> &mut Self {
+        self.warm_up = Some(duration);
+        self
+    }
+
+    /// Check the settings against each other. `open` and the other `open_*` methods
+    /// do this before opening the device.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(rate) = self.sample_rate {
+            crate::rtlsdr::check_sample_rate(rate)?;
+        }
+        if self.gain.is_some() && self.tuner_gain.is_some() {
+            return Err(RtlsdrErr(
+                "Set either a gain strategy or a tuner gain, not both".to_string(),
+            ));
+        }
+        let direct = matches!(
+            self.direct_sampling,
+            Some(DirectSampleMode::On | DirectSampleMode::OnSwap)
+        );
+        // The exact limit depends on the device's crystal and is checked once it is
+        // open; nothing above half the fastest supported crystal can work
+        if let (true, Some(freq)) = (direct, self.center_freq) {
+            if freq > MAX_RTL_XTAL_FREQ / 2 {
+                return Err(RtlsdrErr(format!(
+                    "{} Hz is above the direct sampling range (up to {} Hz)",
+                    freq,
+                    MAX_RTL_XTAL_FREQ / 2
+                )));
+            }
+        }
+        if let (Some(bw), Some(rate)) = (self.bandwidth, self.sample_rate) {
+            if bw > rate {
+                warn!(
+                    "Bandwidth {} Hz is wider than the sample rate {} Hz and will alias",
+                    bw, rate
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Open the first device and apply the configuration
+    pub fn open(&self) -> Result<RtlSdr> {
+        self.open_by_index(0)
+    }
+
+    /// Open the first attached device that meets `req` and isn't already in use,
+    /// for hosts where dongles with different hardware have different jobs. Each
+    /// candidate is opened with a single attempt and probed in turn; the ones that
+    /// don't qualify are closed again. Only when none qualifies are the candidates
+    /// that failed with a transient error opened again with the full retry policy.
+    pub fn open_matching(&self, req: &Requirements) -> Result<RtlSdr> {
+        if req.min_rate > MAX_SAMPLE_RATE {
+            return Err(RtlsdrErr(format!(
+                "No device supports {} S/s (maximum {})",
+                req.min_rate, MAX_SAMPLE_RATE
+            )));
+        }
+        let mut probe = self.clone();
+        probe.init_attempts(1);
+        let mut deferred = Vec::new();
+        for dev in self.enumerate()? {
+            if matches!(dev.speed, Speed::Low | Speed::Full)
+                && req.min_rate as u64 * 2 > USB1_USABLE_BYTES_PER_SEC
+            {
+                debug!(
+                    "Skipping device {}: {:?} speed link is too slow for {} S/s",
+                    dev.index, dev.speed, req.min_rate
+                );
+                continue;
+            }
+            match probe.open_by_bus_address(dev.bus, dev.address) {
+                Ok(sdr) => {
+                    if let Some(sdr) = matching(req, &dev, sdr) {
+                        return Ok(sdr);
+                    }
+                }
+                Err(e) if is_transient(&e) && !in_use(&e) => {
+                    debug!(
+                        "Device {} failed to open ({}), retrying later",
+                        dev.index, e
+                    );
+                    deferred.push(dev);
+                }
+                Err(e) => debug!("Skipping device {}: {}", dev.index, e),
+            }
+        }
+        for dev in deferred {
+            match self.open_by_bus_address(dev.bus, dev.address) {
+                Ok(sdr) => {
+                    if let Some(sdr) = matching(req, &dev, sdr) {
+                        return Ok(sdr);
+                    }
+                }
+                Err(e) => debug!("Skipping device {}: {}", dev.index, e),
+            }
+        }
+        Err(RtlsdrErr(format!("No unused device matches {:?}", req)))
+    }
+
+    /// Skip the reset check and/or EEPROM read during init. See `RtlSdr::init_timing`
+    /// for how long each stage takes on the current host.
+    pub fn init_steps(&mut self, steps: InitSteps) -> &mut Self {
+        self.init_steps = steps;
+        self
+    }
+
+    /// Whether the platform's libusb can announce hotplug events
+    pub fn has_hotplug() -> bool {
+        rusb::has_hotplug()
+    }
+
+    /// Supported devices attached to the host, as `RtlSdr::enumerate` but describing
+    /// the endpoint set with `bulk_endpoint`
+    pub fn enumerate(&self) -> Result<Vec<DeviceInfo>> {
+        Device::enumerate_with(&self.usb)
+    }
+
+    pub fn open_by_index(&self, index: usize) -> Result<RtlSdr> {
+        self.open_with(|| Device::new_by_index_with_context(self.usb_context()?, index))
+    }
+
+    pub fn open_by_serial(&self, serial: &str) -> Result<RtlSdr> {
+        self.open_with(|| Device::new_by_serial_with_context(self.usb_context()?, serial))
+    }
+
+    /// Open the device at a USB bus number and address (see `RtlSdr::enumerate`), for
+    /// hosts with several dongles sharing a serial number
+    pub fn open_by_bus_address(&self, bus: u8, address: u8) -> Result<RtlSdr> {
+        self.open_with(|| {
+            Device::new_by_bus_address_with_context(self.usb_context()?, bus, address)
+        })
+    }
+
+    /// Open the device on a physical port: `bus` and the port numbers from the root hub
+    /// down, as in `DeviceInfo::port_path`
+    pub fn open_by_port_path(&self, bus: u8, ports: &[u8]) -> Result<RtlSdr> {
+        self.open_with(|| Device::new_by_port_path_with_context(self.usb_context()?, bus, ports))
+    }
+
+    /// Open the first device with this VID/PID even if it isn't in the known device
+    /// table, for dongles not (yet) listed there
+    pub fn open_unchecked(&self, vid: u16, pid: u16) -> Result<RtlSdr> {
+        self.open_with(|| Device::new_unchecked_with_context(self.usb_context()?, vid, pid))
+    }
+
+    /// Initialize a device the application already opened itself. Since the handle
+    /// can't be reopened, init is attempted only once.
+    pub fn open_rusb_handle(&self, handle: rusb::DeviceHandle<Context>) -> Result<RtlSdr> {
+        self.validate()?;
+        self.try_open(Device::from_rusb_handle(handle)?, false)
+    }
+
+    /// Open a device from a usbfs file descriptor the platform already opened, such as
+    /// the one Android's `UsbDeviceConnection.getFileDescriptor()` returns, where apps
+    /// can't enumerate the bus themselves. Init is attempted only once. On Android, call
+    /// `rusb::disable_device_discovery()` before anything creates a libusb context.
+    ///
+    /// # Safety
+    /// `fd` must be an open USB device descriptor and stay open until the returned
+    /// `RtlSdr` is dropped. It is not closed here; that remains the caller's job.
+    #[cfg(unix)]
+    pub unsafe fn open_by_fd(&self, fd: RawFd) -> Result<RtlSdr> {
+        let handle = self.usb_context()?.open_device_with_fd(fd)?;
+        self.open_rusb_handle(handle)
+    }
+
+    fn usb_context(&self) -> Result<Context> {
+        if let Some(context) = &self.context {
+            return Ok(context.clone());
+        }
+        let mut context = Context::new()?;
+        if let Some(level) = self.log_level {
+            context.set_log_level(level);
+        }
+        Ok(context)
+    }
+
+    fn open_with<F: Fn() -> Result<Device>>(&self, open_device: F) -> Result<RtlSdr> {
+        self.validate()?;
+        let mut failures: Vec<RtlsdrError> = Vec::new();
+        let mut delay = self.retry_delay;
+        let start = platform::now();
+        for attempt in 1..=self.init_attempts {
+            let opening = platform::now();
+            let result = open_device().and_then(|dev| {
+                let usb_open = platform::now() - opening;
+                let mut sdr = self.try_open(dev, attempt < self.init_attempts)?;
+                let timing = sdr.sdr.init_timing_mut();
+                timing.usb_open = usb_open;
+                timing.attempts = attempt;
+                timing.total = platform::now() - start;
+                Ok(sdr)
+            });
+            match result {
+                Ok(sdr) => {
+                    debug!("Device init timing: {:?}", sdr.init_timing());
+                    return Ok(sdr);
+                }
+                Err(e) => {
+                    warn!(
+                        "Init attempt {}/{} failed: {}",
+                        attempt, self.init_attempts, e
+                    );
+                    let retry = is_transient(&e);
+                    failures.push(e);
+                    if !retry {
+                        break;
+                    }
+                }
+            }
+            if attempt < self.init_attempts {
+                platform::sleep(delay);
+                delay = next_retry_delay(delay, self.retry_backoff);
+            }
+        }
+        if failures.len() == 1 {
+            return Err(failures.remove(0));
+        }
+        Err(RtlsdrError::InitFailed(InitError { attempts: failures }))
+    }
+
+    fn try_open(&self, mut dev: Device, reset_on_fail: bool) -> Result<RtlSdr> {
+        dev.usb = self.usb;
+        let mut sdr = Sdr::new(dev);
+        sdr.set_init_steps(self.init_steps);
+        if let Err(e) = sdr.init() {
+            // Some dongles only come up properly after a second reset
+            if reset_on_fail {
+                if let Err(reset_err) = sdr.reset_device() {
+                    warn!("Device reset after failed init also failed: {}", reset_err);
+                }
+            }
+            return Err(e);
+        }
+        let mut sdr = RtlSdr::from_sdr(sdr);
+        let configuring = platform::now();
+        self.configure(&mut sdr)?;
+        sdr.sdr.init_timing_mut().configure = platform::now() - configuring;
+        if let Some(duration) = self.warm_up {
+            let warming = platform::now();
+            sdr.warm_up(duration)?;
+            sdr.sdr.init_timing_mut().warm_up = platform::now() - warming;
+        }
+        Ok(sdr)
+    }
+
+    // The mode picks the usable frequency range, the rate sets the tuner filters, and
+    // the correction retunes, so all come before the frequency; gain strategies measure
+    // at the final frequency and rate.
+    fn configure(&self, sdr: &mut RtlSdr) -> Result<()> {
+        if let Some(mode) = self.direct_sampling {
+            sdr.set_direct_sampling(mode)?;
+        }
+        if let Some(rate) = self.sample_rate {
+            sdr.set_sample_rate(rate)?;
+        }
+        if let Some(bw) = self.bandwidth {
+            sdr.set_tuner_bandwidth(bw)?;
+        }
+        if let Some(ppm) = self.freq_correction {
+            sdr.set_freq_correction(ppm)?;
+        }
+        if let Some(freq) = self.center_freq {
+            sdr.set_center_freq(freq)?;
+        }
+        if let Some(gain) = self.tuner_gain {
+            sdr.set_tuner_gain(gain)?;
+        }
+        if let Some(strategy) = self.gain {
+            sdr.set_gain_strategy(strategy)?;
+        }
+        if let Some(on) = self.bias_tee {
+            sdr.set_bias_tee(on)?;
+        }
+        Ok(())
+    }
+}
+
+// `sdr` if it meets `req`, closing it otherwise
+fn matching(req: &Requirements, dev: &DeviceInfo, sdr: RtlSdr) -> Option<RtlSdr> {
+    match req.mismatch(&sdr) {
+        Some(reason) => {
+            debug!("Skipping device {}: {}", dev.index, reason);
+            None
+        }
+        None => {
+            info!("Device {} ({}) matches {:?}", dev.index, dev.name, req);
+            Some(sdr)
+        }
+    }
+}
+
+// USB errors that can clear up on another attempt, e.g. a dongle still settling after
+// a reset. Configuration errors and missing devices fail the same way every time.
+fn is_transient(e: &RtlsdrError) -> bool {
+    matches!(
+        e,
+        RtlsdrError::Usb(
+            rusb::Error::Io
+                | rusb::Error::Busy
+                | rusb::Error::Timeout
+                | rusb::Error::Pipe
+                | rusb::Error::Interrupted
+                | rusb::Error::Overflow
+                | rusb::Error::NoDevice
+        )
+    )
+}
+
+// Another process has claimed the device
+fn in_use(e: &RtlsdrError) -> bool {
+    matches!(e, RtlsdrError::Usb(rusb::Error::Busy))
+}
+
+fn next_retry_delay(delay: Duration, backoff: u32) -> Duration {
+    delay.saturating_mul(backoff).min(MAX_RETRY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_usb_glitches_are_retried() {
+        assert!(is_transient(&RtlsdrError::Usb(rusb::Error::Timeout)));
+        assert!(is_transient(&RtlsdrError::Usb(rusb::Error::Pipe)));
+        assert!(!is_transient(&RtlsdrError::Usb(rusb::Error::Access)));
+        assert!(!is_transient(&RtlsdrErr("No device found".to_string())));
+    }
+
+    #[test]
+    fn retry_delay_is_capped() {
+        let delay = next_retry_delay(Duration::from_millis(100), 2);
+        assert_eq!(delay, Duration::from_millis(200));
+        assert_eq!(
+            next_retry_delay(Duration::from_secs(8), u32::MAX),
+            MAX_RETRY_DELAY
+        );
+        assert_eq!(next_retry_delay(Duration::MAX, u32::MAX), MAX_RETRY_DELAY);
+    }
+}
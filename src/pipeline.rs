@@ -0,0 +1,276 @@
+//! Feeding one device into several sinks (recorders, network streams, ...) with an
+//! orderly shutdown.
+//!
+//! `Pipeline::shutdown` stops in the order that keeps every output intact: USB
+//! transfers stop first, then each sink is flushed and finalized (closing recording
+//! chunks and writing their SigMF metadata), and only then is the device powered
+//! down. Stopping an application by just exiting can leave half-written files behind.
+use std::future::Future;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+
+use crate::convert::{self, I16Format};
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::record::{FileRecorder, WavRecorder};
+use crate::stream::channel;
+use crate::{platform, RtlSdr};
+
+/// Destination for the raw interleaved u8 I/Q of a `Pipeline`
+pub trait Sink: Send {
+    fn write(&mut self, data: &[u8]) -> Result<()>;
+    /// Flush buffered data and finalize headers or metadata. Called once, after the
+    /// last `write`.
+    fn finish(&mut self) -> Result<()>;
+}
+
+impl Sink for FileRecorder {
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        FileRecorder::write(self, data)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.rotate()
+    }
+}
+
+impl Sink for WavRecorder {
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        WavRecorder::write(self, data)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.rotate()
+    }
+}
+
+#[cfg(feature = "shm")]
+impl Sink for crate::stream::ShmSink {
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        crate::stream::ShmSink::write(self, data);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        // Readers are told the writer has gone when the sink is dropped
+        Ok(())
+    }
+}
+
+/// `Sink` for any `std::io::Write`, e.g. a `TcpStream` or a pipe
+pub struct WriteSink<W: Write + Send>(pub W);
+
+impl<W: Write + Send> Sink for WriteSink<W> {
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.0
+            .write_all(data)
+            .map_err(|e| RtlsdrErr(format!("Sink write failed: {}", e)))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.0
+            .flush()
+            .map_err(|e| RtlsdrErr(format!("Sink flush failed: {}", e)))
+    }
+}
+
+/// Sink adapter converting the raw u8 I/Q to 16-bit samples in a chosen byte order
+/// and packing before passing it on, e.g. `I16Sink::new(WriteSink(stream), format)`
+/// for clients expecting cs16 over TCP
+pub struct I16Sink<S: Sink> {
+    inner: S,
+    format: I16Format,
+    packed: Vec<u8>,
+}
+
+impl<S: Sink> I16Sink<S> {
+    pub fn new(inner: S, format: I16Format) -> Self {
+        Self {
+            inner,
+            format,
+            packed: Vec::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Sink> Sink for I16Sink<S> {
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.packed.clear();
+        convert::u8_to_packed_i16(data, self.format, &mut self.packed);
+        self.inner.write(&self.packed)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.inner.finish()
+    }
+}
+
+/// A device streaming into sinks on its own thread, created by `Pipeline::start`.
+/// Dropping it shuts down in the background without waiting.
+pub struct Pipeline {
+    stop: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+    done: Option<channel::Receiver<Result<()>>>,
+}
+
+impl Pipeline {
+    /// Reset the device buffer and start reading transfers of `transfer_len` bytes
+    /// into every sink in turn. The pipeline stops by itself on the first read or
+    /// sink error, which `shutdown` then reports.
+    pub fn start(sdr: RtlSdr, sinks: Vec<Box<dyn Sink>>, transfer_len: usize) -> Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = channel::channel(1);
+        let transfer_len = transfer_len.max(1).next_multiple_of(512);
+        let finished = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread_finished = finished.clone();
+        platform::spawn("rtlsdr-pipeline", move || {
+            let result = run(sdr, sinks, transfer_len, &thread_stop);
+            if let Err(e) = &result {
+                error!("Pipeline stopped: {}", e);
+            }
+            thread_finished.store(true, Ordering::Release);
+            tx.send_blocking(result);
+        })?;
+        Ok(Pipeline {
+            stop,
+            finished,
+            done: Some(rx),
+        })
+    }
+
+    /// Whether the pipeline has stopped by itself after an error, sinks finished and
+    /// device closed
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+
+    /// Stop transfers, finish the sinks, then close the device. Resolves once all of
+    /// it is done, with the first error any stage hit.
+    pub fn shutdown(mut self) -> Shutdown {
+        self.stop.store(true, Ordering::Release);
+        Shutdown {
+            done: self.done.take().expect("shutdown consumes the pipeline"),
+        }
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+    }
+}
+
+/// Future returned by `Pipeline::shutdown`. Works on any executor; `wait` blocks the
+/// calling thread instead.
+pub struct Shutdown {
+    done: channel::Receiver<Result<()>>,
+}
+
+impl Shutdown {
+    /// Block until the shutdown has finished, for code outside an async runtime
+    pub fn wait(self) -> Result<()> {
+        self.done
+            .recv_blocking()
+            .unwrap_or_else(|| Err(thread_exited()))
+    }
+}
+
+impl Future for Shutdown {
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        match Pin::new(&mut self.done).poll_next(cx) {
+            Poll::Ready(Some(result)) => Poll::Ready(result),
+            Poll::Ready(None) => Poll::Ready(Err(thread_exited())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+fn thread_exited() -> crate::error::RtlsdrError {
+    RtlsdrErr("Pipeline thread exited without reporting".to_string())
+}
+
+fn run(
+    sdr: RtlSdr,
+    mut sinks: Vec<Box<dyn Sink>>,
+    transfer_len: usize,
+    stop: &AtomicBool,
+) -> Result<()> {
+    let mut buf = vec![0; transfer_len];
+    let mut result = sdr.reset_buffer();
+    while result.is_ok() && !stop.load(Ordering::Acquire) {
+        result = transfer(&sdr, &mut sinks, &mut buf);
+    }
+    // Transfers have stopped; finalize every sink before the device goes down
+    for sink in sinks.iter_mut() {
+        if let Err(e) = sink.finish() {
+            error!("Failed to finish sink: {}", e);
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+    }
+    drop(sinks);
+    let closed = sdr.close();
+    result.and(closed)
+}
+
+fn transfer(sdr: &RtlSdr, sinks: &mut [Box<dyn Sink>], buf: &mut [u8]) -> Result<()> {
+    let len = sdr.read_sync(buf)?;
+    if len == 0 {
+        return Err(RtlsdrErr("Device returned no samples".to_string()));
+    }
+    for sink in sinks.iter_mut() {
+        sink.write(&buf[..len])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{Endianness, Packing};
+
+    fn sink_output(endianness: Endianness, packing: Packing) -> Vec<u8> {
+        let format = I16Format {
+            endianness,
+            packing,
+        };
+        let mut sink = I16Sink::new(WriteSink(Vec::new()), format);
+        sink.write(&[255, 0, 128, 127]).unwrap();
+        sink.finish().unwrap();
+        sink.into_inner().0
+    }
+
+    #[test]
+    fn i16_sink_packs_every_layout() {
+        // 255 -> 0x7f80, 0 -> -0x7f80, 128 -> 0x0080, 127 -> -0x0080
+        assert_eq!(
+            sink_output(Endianness::Little, Packing::Interleaved),
+            [0x80, 0x7f, 0x80, 0x80, 0x80, 0x00, 0x80, 0xff]
+        );
+        assert_eq!(
+            sink_output(Endianness::Big, Packing::Interleaved),
+            [0x7f, 0x80, 0x80, 0x80, 0x00, 0x80, 0xff, 0x80]
+        );
+        assert_eq!(
+            sink_output(Endianness::Little, Packing::Planar),
+            [0x80, 0x7f, 0x80, 0x00, 0x80, 0x80, 0x80, 0xff]
+        );
+        assert_eq!(
+            sink_output(Endianness::Big, Packing::Planar),
+            [0x7f, 0x80, 0x00, 0x80, 0x80, 0x80, 0xff, 0x80]
+        );
+    }
+}
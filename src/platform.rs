@@ -0,0 +1,94 @@
+//! Time and threading primitives used by the library.
+//!
+//! Library code sleeps, reads the clocks and spawns workers through this module
+//! instead of calling `std::thread`/`std::time` directly, so hosts without native
+//! threads or clocks (wasm with a WebUSB backend, embedded targets with a host
+//! scheduler) can plug in their own implementation with `set_platform`.
+//! Durations are `core::time::Duration`, which is available everywhere.
+use core::time::Duration;
+use std::sync::OnceLock;
+
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+
+/// Host services needed by the library
+pub trait Platform: Send + Sync {
+    /// Monotonic time since an arbitrary, fixed starting point
+    fn now(&self) -> Duration;
+    /// Wall-clock time since the Unix epoch, for timestamps. The default reads
+    /// `SystemTime`, which panics on wasm32-unknown-unknown; override it there.
+    fn wall_clock(&self) -> Duration {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+    /// Block the calling context for at least `dur`
+    fn sleep(&self, dur: Duration);
+    /// Run `f` concurrently with the caller
+    fn spawn(&self, name: &str, f: Box<dyn FnOnce() + Send + 'static>) -> Result<()>;
+}
+
+/// Default implementation backed by `std::thread` and `std::time::Instant`
+#[derive(Debug)]
+pub struct StdPlatform {
+    start: std::time::Instant,
+}
+
+impl Default for StdPlatform {
+    fn default() -> Self {
+        StdPlatform {
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+impl Platform for StdPlatform {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, dur: Duration) {
+        std::thread::sleep(dur);
+    }
+
+    fn spawn(&self, name: &str, f: Box<dyn FnOnce() + Send + 'static>) -> Result<()> {
+        std::thread::Builder::new()
+            .name(name.to_string())
+            .spawn(f)
+            .map(|_| ())
+            .map_err(|e| RtlsdrErr(format!("Unable to spawn thread {}: {}", name, e)))
+    }
+}
+
+static PLATFORM: OnceLock<Box<dyn Platform>> = OnceLock::new();
+
+/// Install a custom platform. Must be called before the library first sleeps,
+/// reads the clock or spawns, and can only be done once.
+pub fn set_platform(platform: Box<dyn Platform>) -> Result<()> {
+    PLATFORM
+        .set(platform)
+        .map_err(|_| RtlsdrErr("Platform already initialized".to_string()))
+}
+
+fn platform() -> &'static dyn Platform {
+    PLATFORM
+        .get_or_init(|| Box::new(StdPlatform::default()))
+        .as_ref()
+}
+
+pub fn now() -> Duration {
+    platform().now()
+}
+
+/// Current wall-clock time, from the installed platform
+pub fn wall_clock() -> std::time::SystemTime {
+    std::time::UNIX_EPOCH + platform().wall_clock()
+}
+
+pub fn sleep(dur: Duration) {
+    platform().sleep(dur)
+}
+
+pub fn spawn<F: FnOnce() + Send + 'static>(name: &str, f: F) -> Result<()> {
+    platform().spawn(name, Box::new(f))
+}
@@ -0,0 +1,230 @@
+//! Provenance of a capture: which library, backend, host and device produced it, so
+//! archived recordings stay interpretable long after the capture setup is gone.
+//! `build_info` describes the linked library alone, for bug reports and capability
+//! checks.
+use std::time::UNIX_EPOCH;
+
+use crate::device::EEPROM_SIZE;
+use crate::eeprom::EepromInfo;
+use crate::platform;
+use crate::record::sigmf::SIGMF_VERSION;
+use crate::stream::source::RTL_TCP_MAGIC;
+use crate::tuners::KNOWN_TUNERS;
+use crate::RtlSdr;
+
+/// Cargo features the crate was built with
+const FEATURES: &[(&str, bool)] = &[
+    ("async-tokio", cfg!(feature = "async-tokio")),
+    ("futures-io", cfg!(feature = "futures-io")),
+    ("async-std", cfg!(feature = "async-std")),
+    ("shm", cfg!(feature = "shm")),
+    ("graceful", cfg!(feature = "graceful")),
+    ("gzip", cfg!(feature = "gzip")),
+    ("zstd", cfg!(feature = "zstd")),
+    ("capi", cfg!(feature = "capi")),
+    ("python", cfg!(feature = "python")),
+    ("node", cfg!(feature = "node")),
+    ("rtl_sdr_blog", cfg!(feature = "rtl_sdr_blog")),
+];
+
+/// A wire or file format the crate speaks, with the version it implements
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolVersion {
+    pub name: &'static str,
+    pub version: String,
+}
+
+/// What the linked library provides, returned by `build_info`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub crate_version: &'static str,
+    /// Enabled Cargo features
+    pub features: Vec<&'static str>,
+    /// USB backend and its version, as in `Provenance::backend`
+    pub backend: String,
+    /// Tuner chips the driver can operate
+    pub tuners: Vec<&'static str>,
+    /// Protocols and formats, e.g. rtl_tcp ("RTL0" greeting) and SigMF
+    pub protocols: Vec<ProtocolVersion>,
+}
+
+/// Version, features and capabilities of this build of the crate
+pub fn build_info() -> BuildInfo {
+    // Only extended with the shm feature
+    #[allow(unused_mut)]
+    let mut protocols = vec![
+        ProtocolVersion {
+            name: "rtl_tcp",
+            version: String::from_utf8_lossy(RTL_TCP_MAGIC).into_owned(),
+        },
+        ProtocolVersion {
+            name: "sigmf",
+            version: SIGMF_VERSION.to_string(),
+        },
+    ];
+    #[cfg(feature = "shm")]
+    protocols.push(ProtocolVersion {
+        name: "shm",
+        version: crate::stream::shm::VERSION.to_string(),
+    });
+    BuildInfo {
+        crate_version: env!("CARGO_PKG_VERSION"),
+        features: FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect(),
+        backend: backend(),
+        tuners: KNOWN_TUNERS.iter().map(|info| info.name).collect(),
+        protocols,
+    }
+}
+
+impl BuildInfo {
+    /// JSON object, e.g. to attach to a bug report
+    pub fn to_json(&self) -> String {
+        let list = |items: &[&str]| {
+            items
+                .iter()
+                .map(|s| json_str(s))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let protocols = self
+            .protocols
+            .iter()
+            .map(|p| format!("{}:{}", json_str(p.name), json_str(&p.version)))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{{\"crate_version\":{},\"features\":[{}],\"backend\":{},\"tuners\":[{}],\"protocols\":{{{}}}}}",
+            json_str(self.crate_version),
+            list(&self.features),
+            json_str(&self.backend),
+            list(&self.tuners),
+            protocols
+        )
+    }
+}
+
+fn backend() -> String {
+    let libusb = rusb::version();
+    format!(
+        "libusb {}.{}.{}",
+        libusb.major(),
+        libusb.minor(),
+        libusb.micro()
+    )
+}
+
+/// Facts about the capture setup, collected by `Provenance::collect`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    pub crate_version: &'static str,
+    /// USB backend and its version, e.g. "libusb 1.0.27"
+    pub backend: String,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub hostname: Option<String>,
+    /// Known device table entry, e.g. "Generic RTL2832U OEM"
+    pub device: String,
+    pub vid: u16,
+    pub pid: u16,
+    pub tuner: &'static str,
+    /// EEPROM contents, if it could be read and parsed
+    pub eeprom: Option<EepromInfo>,
+    /// Seconds since the Unix epoch when this was collected
+    pub collected_at: u64,
+}
+
+impl Provenance {
+    pub fn collect(sdr: &RtlSdr) -> Provenance {
+        let signature = sdr.sdr.signature().clone();
+        let tuner = sdr.sdr.get_tuner_info().map(|info| info.name).unwrap_or("");
+        let eeprom = sdr
+            .read_eeprom(0, EEPROM_SIZE)
+            .and_then(|image| EepromInfo::parse(&image));
+        let eeprom = match eeprom {
+            Ok(info) => Some(info),
+            Err(e) => {
+                debug!("No EEPROM info for provenance: {}", e);
+                None
+            }
+        };
+        Provenance {
+            crate_version: env!("CARGO_PKG_VERSION"),
+            backend: backend(),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            hostname: hostname(),
+            device: signature.description.into_owned(),
+            vid: signature.vid,
+            pid: signature.pid,
+            tuner,
+            eeprom,
+            collected_at: platform::wall_clock()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+
+    /// JSON object, e.g. for a SigMF `global` extension field
+    pub fn to_json(&self) -> String {
+        let mut fields = vec![
+            format!("\"crate_version\":{}", json_str(self.crate_version)),
+            format!("\"backend\":{}", json_str(&self.backend)),
+            format!("\"os\":{}", json_str(self.os)),
+            format!("\"arch\":{}", json_str(self.arch)),
+            format!("\"device\":{}", json_str(&self.device)),
+            format!("\"vid\":\"{:04x}\"", self.vid),
+            format!("\"pid\":\"{:04x}\"", self.pid),
+            format!("\"tuner\":{}", json_str(self.tuner)),
+            format!("\"collected_at\":{}", self.collected_at),
+        ];
+        if let Some(hostname) = &self.hostname {
+            fields.push(format!("\"hostname\":{}", json_str(hostname)));
+        }
+        if let Some(eeprom) = &self.eeprom {
+            fields.push(format!(
+                "\"eeprom\":{{\"vid\":\"{:04x}\",\"pid\":\"{:04x}\",\"manufacturer\":{},\"product\":{},\"serial\":{},\"remote_wakeup\":{},\"enable_ir\":{}}}",
+                eeprom.vid,
+                eeprom.pid,
+                json_str(&eeprom.manufacturer),
+                json_str(&eeprom.product),
+                json_str(&eeprom.serial),
+                eeprom.remote_wakeup,
+                eeprom.enable_ir
+            ));
+        }
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+fn hostname() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Quoted and escaped JSON string
+pub(crate) fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
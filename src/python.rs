@@ -0,0 +1,198 @@
+//! Python bindings, enabled with the `python` feature. Build and install the module
+//! with `maturin develop --release` (see `pyproject.toml`).
+//!
+//! ```python
+//! import rtlsdr_rs
+//! sdr = rtlsdr_rs.RtlSdr.open(0)
+//! sdr.sample_rate = 2_048_000
+//! sdr.center_freq = 100_000_000
+//! sdr.gain = 29.7          # dB, None for automatic gain
+//! sdr.reset_buffer()
+//! iq = sdr.read_samples(256 * 1024)  # numpy complex64 array
+//! ```
+use numpy::{Complex32, PyArray1};
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+
+use crate::convert::Converter;
+use crate::error::RtlsdrError;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::{RtlSdr, TunerGain};
+
+// Bulk reads must be a multiple of the USB packet size
+const READ_GRANULARITY: usize = 512;
+
+fn py_err(e: RtlsdrError) -> PyErr {
+    PyIOError::new_err(e.to_string())
+}
+
+/// An open RTL-SDR device
+#[pyclass(name = "RtlSdr", unsendable)]
+pub struct PyRtlSdr {
+    // None once closed
+    sdr: Option<RtlSdr>,
+}
+
+impl PyRtlSdr {
+    fn sdr(&self) -> PyResult<&RtlSdr> {
+        self.sdr
+            .as_ref()
+            .ok_or_else(|| PyIOError::new_err("Device is closed"))
+    }
+
+    fn sdr_mut(&mut self) -> PyResult<&mut RtlSdr> {
+        self.sdr
+            .as_mut()
+            .ok_or_else(|| PyIOError::new_err("Device is closed"))
+    }
+
+    // Read exactly `len` bytes with the GIL released, so other Python threads run
+    // during the transfers. `RtlSdr` isn't `Sync`, so the device moves into the
+    // reading closure and back.
+    fn read_raw(&mut self, py: Python<'_>, len: usize) -> PyResult<Vec<u8>> {
+        let sdr = self
+            .sdr
+            .take()
+            .ok_or_else(|| PyIOError::new_err("Device is closed"))?;
+        let (sdr, result) = py.detach(move || {
+            let result = read_exact(&sdr, len);
+            (sdr, result)
+        });
+        self.sdr = Some(sdr);
+        result.map_err(py_err)
+    }
+}
+
+// Read exactly `len` bytes, in whole USB packets
+fn read_exact(sdr: &RtlSdr, len: usize) -> crate::error::Result<Vec<u8>> {
+    let mut buf = vec![0; len.next_multiple_of(READ_GRANULARITY)];
+    let mut filled = 0;
+    while filled < len {
+        let n = sdr.read_sync(&mut buf[filled..])?;
+        if n == 0 {
+            return Err(RtlsdrErr("Device returned no samples".to_string()));
+        }
+        filled += n;
+    }
+    buf.truncate(len);
+    Ok(buf)
+}
+
+#[pymethods]
+impl PyRtlSdr {
+    #[staticmethod]
+    fn open(index: usize) -> PyResult<Self> {
+        let sdr = RtlSdr::open_by_index(index).map_err(py_err)?;
+        Ok(PyRtlSdr { sdr: Some(sdr) })
+    }
+
+    #[staticmethod]
+    fn open_by_serial(serial: &str) -> PyResult<Self> {
+        let sdr = RtlSdr::open_by_serial(serial).map_err(py_err)?;
+        Ok(PyRtlSdr { sdr: Some(sdr) })
+    }
+
+    fn close(&mut self) -> PyResult<()> {
+        match self.sdr.take() {
+            Some(sdr) => sdr.close().map_err(py_err),
+            None => Ok(()),
+        }
+    }
+
+    #[getter]
+    fn get_center_freq(&self) -> PyResult<u32> {
+        Ok(self.sdr()?.get_center_freq())
+    }
+
+    #[setter]
+    fn set_center_freq(&mut self, freq: u32) -> PyResult<()> {
+        self.sdr_mut()?.set_center_freq(freq).map_err(py_err)
+    }
+
+    #[getter]
+    fn get_sample_rate(&self) -> PyResult<u32> {
+        Ok(self.sdr()?.get_sample_rate())
+    }
+
+    #[setter]
+    fn set_sample_rate(&mut self, rate: u32) -> PyResult<()> {
+        self.sdr_mut()?
+            .set_sample_rate(rate)
+            .map(|_| ())
+            .map_err(py_err)
+    }
+
+    #[getter]
+    fn get_freq_correction(&self) -> PyResult<i32> {
+        Ok(self.sdr()?.get_freq_correction())
+    }
+
+    #[setter]
+    fn set_freq_correction(&mut self, ppm: i32) -> PyResult<()> {
+        self.sdr_mut()?.set_freq_correction(ppm).map_err(py_err)
+    }
+
+    /// Gain in dB, or None for automatic gain
+    #[getter]
+    fn get_gain(&self) -> PyResult<Option<f32>> {
+        Ok(match self.sdr()?.sdr.get_tuner_gain() {
+            TunerGain::Auto => None,
+            TunerGain::Manual(gain) => Some(gain as f32 / 10.0),
+        })
+    }
+
+    #[setter]
+    fn set_gain(&mut self, gain: Option<f32>) -> PyResult<()> {
+        let gain = match gain {
+            Some(db) => TunerGain::Manual((db * 10.0).round() as i32),
+            None => TunerGain::Auto,
+        };
+        self.sdr_mut()?.set_tuner_gain(gain).map_err(py_err)
+    }
+
+    /// Supported gains in dB
+    fn tuner_gains(&self) -> PyResult<Vec<f32>> {
+        let gains = self.sdr()?.get_tuner_gains().map_err(py_err)?;
+        Ok(gains.iter().map(|g| *g as f32 / 10.0).collect())
+    }
+
+    fn set_bias_tee(&self, on: bool) -> PyResult<()> {
+        self.sdr()?.set_bias_tee(on).map_err(py_err)
+    }
+
+    fn reset_buffer(&self) -> PyResult<()> {
+        self.sdr()?.reset_buffer().map_err(py_err)
+    }
+
+    /// Raw interleaved u8 I/Q as a uint8 array of `num_bytes`
+    fn read_bytes<'py>(
+        &mut self,
+        py: Python<'py>,
+        num_bytes: usize,
+    ) -> PyResult<Bound<'py, PyArray1<u8>>> {
+        let raw = self.read_raw(py, num_bytes)?;
+        Ok(PyArray1::from_vec(py, raw))
+    }
+
+    /// `num_samples` samples as a complex64 array scaled to roughly [-1, 1]
+    fn read_samples<'py>(
+        &mut self,
+        py: Python<'py>,
+        num_samples: usize,
+    ) -> PyResult<Bound<'py, PyArray1<Complex32>>> {
+        let raw = self.read_raw(py, num_samples * 2)?;
+        let mut iq = Vec::with_capacity(raw.len());
+        Converter::default().to_f32(&raw, &mut iq);
+        let samples: Vec<Complex32> = iq
+            .chunks_exact(2)
+            .map(|s| Complex32::new(s[0], s[1]))
+            .collect();
+        Ok(PyArray1::from_vec(py, samples))
+    }
+}
+
+#[pymodule]
+fn rtlsdr_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyRtlSdr>()?;
+    Ok(())
+}
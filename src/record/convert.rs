@@ -0,0 +1,435 @@
+//! Conversion of IQ recordings between the formats other tools expect: raw u8 as
+//! captured (`cu8`), 32-bit float (`cf32`), 16-bit integer (`cs16`) and stereo WAV
+//! with I on the left channel and Q on the right.
+//!
+//! Samples go through floats with full scale at ±1.0: u8 is centered on
+//! `DEFAULT_DC_OFFSET`, i16 is scaled by `I16_SCALE` as GNU Radio does. `cs16` is
+//! little-endian unless `ConvertOptions` or a `ci16_be` sidecar says otherwise.
+//! Sample rate, center frequency and start time carry over from a `.sigmf-meta`
+//! sidecar or the WAV header to the output's sidecar or header.
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::convert::{self, Endianness, I16Format, Packing, DEFAULT_DC_OFFSET, I16_SCALE};
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::error::{io_err, Result};
+use crate::record::sigmf::SigmfMeta;
+
+const WAV_HEADER_LEN: u64 = 44;
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+// Complex samples converted per read
+const CHUNK_SAMPLES: usize = 65536;
+
+/// File format of an IQ recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IqFormat {
+    /// Interleaved unsigned 8-bit, as read from the device
+    Cu8,
+    /// Interleaved little-endian 32-bit float
+    Cf32,
+    /// Interleaved signed 16-bit, little-endian unless `ConvertOptions` says otherwise
+    Cs16,
+    /// Two-channel WAV; written as 16-bit PCM, read as 8/16-bit PCM or 32-bit float
+    Wav,
+}
+
+impl IqFormat {
+    /// Format for a file extension such as "cu8", "cfile" or "wav"
+    pub fn from_path(path: &Path) -> Option<IqFormat> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "cu8" | "bin" | "raw" => Some(IqFormat::Cu8),
+            "cf32" | "fc32" | "cfile" => Some(IqFormat::Cf32),
+            "cs16" | "sc16" => Some(IqFormat::Cs16),
+            "wav" => Some(IqFormat::Wav),
+            _ => None,
+        }
+    }
+
+    /// Format for a SigMF datatype; WAV has none
+    pub fn from_sigmf_datatype(datatype: &str) -> Option<IqFormat> {
+        match datatype {
+            "cu8" => Some(IqFormat::Cu8),
+            "cf32_le" => Some(IqFormat::Cf32),
+            "ci16_le" | "ci16_be" => Some(IqFormat::Cs16),
+            _ => None,
+        }
+    }
+
+    pub fn sigmf_datatype(self) -> Option<&'static str> {
+        match self {
+            IqFormat::Cu8 => Some("cu8"),
+            IqFormat::Cf32 => Some("cf32_le"),
+            IqFormat::Cs16 => Some("ci16_le"),
+            IqFormat::Wav => None,
+        }
+    }
+}
+
+/// Settings for `convert_file`. Formats default to the file extensions (or the input
+/// sidecar's datatype); sample rate and frequency override what the input carries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConvertOptions {
+    pub input_format: Option<IqFormat>,
+    pub output_format: Option<IqFormat>,
+    pub sample_rate: Option<u32>,
+    pub center_freq: Option<u64>,
+    /// Byte order of `cs16` input; defaults to the sidecar's datatype, then little-endian
+    pub input_endianness: Option<Endianness>,
+    /// Byte order of `cs16` output; little-endian by default
+    pub output_endianness: Option<Endianness>,
+}
+
+/// What `convert_file` wrote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertSummary {
+    /// Complex samples converted
+    pub samples: u64,
+    pub sample_rate: Option<u32>,
+    pub center_freq: Option<u64>,
+}
+
+// How one I or Q value is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    U8,
+    I16(Endianness),
+    F32,
+}
+
+impl Encoding {
+    fn size(self) -> usize {
+        match self {
+            Encoding::U8 => 1,
+            Encoding::I16(_) => 2,
+            Encoding::F32 => 4,
+        }
+    }
+
+    // `ints` is scratch space for 16-bit samples
+    fn decode(self, bytes: &[u8], ints: &mut Vec<i16>, out: &mut Vec<f32>) {
+        match self {
+            Encoding::U8 => out.extend(
+                bytes
+                    .iter()
+                    .map(|b| (*b as f32 - DEFAULT_DC_OFFSET) / DEFAULT_DC_OFFSET),
+            ),
+            Encoding::I16(endianness) => {
+                ints.clear();
+                convert::unpack_i16(bytes, endianness, ints);
+                out.extend(ints.iter().map(|v| *v as f32 / I16_SCALE));
+            }
+            Encoding::F32 => out.extend(
+                bytes
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            ),
+        }
+    }
+
+    fn encode(self, samples: &[f32], ints: &mut Vec<i16>, out: &mut Vec<u8>) {
+        match self {
+            Encoding::U8 => out.extend(samples.iter().map(|s| {
+                (s * DEFAULT_DC_OFFSET + DEFAULT_DC_OFFSET)
+                    .round()
+                    .clamp(0.0, 255.0) as u8
+            })),
+            Encoding::I16(endianness) => {
+                ints.clear();
+                convert::f32_to_i16(samples, ints);
+                let format = I16Format {
+                    endianness,
+                    packing: Packing::Interleaved,
+                };
+                convert::pack_i16(ints, format, out);
+            }
+            Encoding::F32 => samples
+                .iter()
+                .for_each(|s| out.extend_from_slice(&s.to_le_bytes())),
+        }
+    }
+}
+
+/// Convert the recording at `input` into `output`, writing a `.sigmf-meta` sidecar
+/// next to a non-WAV output whenever the sample rate is known
+pub fn convert_file(
+    input: &Path,
+    output: &Path,
+    options: &ConvertOptions,
+) -> Result<ConvertSummary> {
+    let meta = read_sidecar(input);
+    let input_format = options
+        .input_format
+        .or_else(|| IqFormat::from_path(input))
+        .or_else(|| {
+            meta.as_ref()
+                .and_then(|m| m.datatype.as_deref())
+                .and_then(IqFormat::from_sigmf_datatype)
+        })
+        .ok_or_else(|| RtlsdrErr(format!("{}: unknown IQ format", input.display())))?;
+    let output_format = options
+        .output_format
+        .or_else(|| IqFormat::from_path(output))
+        .ok_or_else(|| RtlsdrErr(format!("{}: unknown IQ format", output.display())))?;
+
+    let file = File::open(input).map_err(|e| io_err(input, e))?;
+    let mut reader = BufReader::new(file);
+    let (encoding, wav_rate, mut remaining) = match input_format {
+        IqFormat::Wav => {
+            let (encoding, rate, len) = read_wav_header(&mut reader, input)?;
+            (encoding, Some(rate), Some(len))
+        }
+        IqFormat::Cu8 => (Encoding::U8, None, None),
+        IqFormat::Cf32 => (Encoding::F32, None, None),
+        IqFormat::Cs16 => {
+            let endianness = options.input_endianness.unwrap_or_else(|| {
+                match meta.as_ref().and_then(|m| m.datatype.as_deref()) {
+                    Some("ci16_be") => Endianness::Big,
+                    _ => Endianness::Little,
+                }
+            });
+            (Encoding::I16(endianness), None, None)
+        }
+    };
+    let sample_rate = options
+        .sample_rate
+        .or(wav_rate)
+        .or(meta.as_ref().map(|m| m.sample_rate));
+    let center_freq = options
+        .center_freq
+        .or(meta.as_ref().map(|m| m.center_freq).filter(|f| *f != 0));
+    let out_encoding = match output_format {
+        IqFormat::Cu8 => Encoding::U8,
+        IqFormat::Cf32 => Encoding::F32,
+        IqFormat::Cs16 => Encoding::I16(options.output_endianness.unwrap_or_default()),
+        IqFormat::Wav => Encoding::I16(Endianness::Little),
+    };
+    if output_format == IqFormat::Wav && sample_rate.is_none() {
+        return Err(RtlsdrErr(format!(
+            "{}: sample rate unknown, needed for the WAV header",
+            input.display()
+        )));
+    }
+
+    let file = File::create(output).map_err(|e| io_err(output, e))?;
+    let mut writer = BufWriter::new(file);
+    if output_format == IqFormat::Wav {
+        // Sizes are filled in once the data is written
+        write_wav_header(&mut writer, sample_rate.unwrap_or(0), 0)
+            .map_err(|e| io_err(output, e))?;
+    }
+    let frame = encoding.size() * 2;
+    let mut inbuf = vec![0; CHUNK_SAMPLES * frame];
+    let mut samples = Vec::with_capacity(CHUNK_SAMPLES * 2);
+    let mut ints = Vec::with_capacity(CHUNK_SAMPLES * 2);
+    let mut outbuf = Vec::with_capacity(CHUNK_SAMPLES * 2 * out_encoding.size());
+    let mut total: u64 = 0;
+    loop {
+        let want = match remaining {
+            Some(left) => (left as usize).min(inbuf.len()),
+            None => inbuf.len(),
+        };
+        let len = read_full(&mut reader, &mut inbuf[..want]).map_err(|e| io_err(input, e))?;
+        // Whole complex samples only; a truncated last one is dropped
+        let len = len - len % frame;
+        if len == 0 {
+            break;
+        }
+        if let Some(left) = remaining.as_mut() {
+            *left -= len as u64;
+        }
+        samples.clear();
+        encoding.decode(&inbuf[..len], &mut ints, &mut samples);
+        outbuf.clear();
+        out_encoding.encode(&samples, &mut ints, &mut outbuf);
+        writer.write_all(&outbuf).map_err(|e| io_err(output, e))?;
+        total += (len / frame) as u64;
+    }
+    let mut file = writer
+        .into_inner()
+        .map_err(|e| io_err(output, e.into_error()))?;
+    if output_format == IqFormat::Wav {
+        let data_len = total * 4;
+        if data_len > (u32::MAX as u64 - WAV_HEADER_LEN) {
+            warn!(
+                "{}: more than 4 GiB of samples, WAV sizes are truncated",
+                output.display()
+            );
+        }
+        file.seek(SeekFrom::Start(0))
+            .and_then(|_| write_wav_header(&mut file, sample_rate.unwrap_or(0), data_len))
+            .map_err(|e| io_err(output, e))?;
+    } else if let Some(sample_rate) = sample_rate {
+        let meta_path = output.with_extension("sigmf-meta");
+        let out_meta = SigmfMeta {
+            datatype: match out_encoding {
+                Encoding::I16(Endianness::Big) => Some("ci16_be".to_string()),
+                _ => output_format.sigmf_datatype().map(|d| d.to_string()),
+            },
+            sample_rate,
+            center_freq: center_freq.unwrap_or(0),
+            datetime: meta.and_then(|m| m.datetime),
+            retunes: Vec::new(),
+            provenance: None,
+            annotations: Vec::new(),
+        };
+        fs::write(&meta_path, out_meta.to_json()).map_err(|e| io_err(&meta_path, e))?;
+    }
+    info!(
+        "Converted {} samples from {} ({:?}) to {} ({:?})",
+        total,
+        input.display(),
+        input_format,
+        output.display(),
+        output_format
+    );
+    Ok(ConvertSummary {
+        samples: total,
+        sample_rate,
+        center_freq,
+    })
+}
+
+// Sidecar of `path`, if there is one that parses
+fn read_sidecar(path: &Path) -> Option<SigmfMeta> {
+    let meta_path = path.with_extension("sigmf-meta");
+    let text = fs::read_to_string(&meta_path).ok()?;
+    let meta = SigmfMeta::parse(&text);
+    if meta.is_none() {
+        warn!("{}: no sample rate, ignored", meta_path.display());
+    }
+    meta
+}
+
+// Sample encoding, sample rate and data length of a two-channel WAV file, leaving
+// `reader` at the start of the samples
+fn read_wav_header<R: Read>(reader: &mut R, path: &Path) -> Result<(Encoding, u32, u64)> {
+    let bad = |what: &str| RtlsdrErr(format!("{}: {}", path.display(), what));
+    let mut riff = [0; 12];
+    reader.read_exact(&mut riff).map_err(|e| io_err(path, e))?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Err(bad("not a WAV file"));
+    }
+    let mut format = None;
+    loop {
+        let mut header = [0; 8];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| bad("no data chunk"))?;
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+        match &header[0..4] {
+            b"fmt " => {
+                let mut fmt = vec![0; len as usize];
+                reader.read_exact(&mut fmt).map_err(|e| io_err(path, e))?;
+                if fmt.len() < 16 {
+                    return Err(bad("short fmt chunk"));
+                }
+                let mut tag = u16::from_le_bytes([fmt[0], fmt[1]]);
+                if tag == WAVE_FORMAT_EXTENSIBLE && fmt.len() >= 26 {
+                    tag = u16::from_le_bytes([fmt[24], fmt[25]]);
+                }
+                let channels = u16::from_le_bytes([fmt[2], fmt[3]]);
+                let rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+                let bits = u16::from_le_bytes([fmt[14], fmt[15]]);
+                if channels != 2 {
+                    return Err(bad(&format!("{} channels, expected 2 (I and Q)", channels)));
+                }
+                let encoding = match (tag, bits) {
+                    (WAVE_FORMAT_PCM, 8) => Encoding::U8,
+                    (WAVE_FORMAT_PCM, 16) => Encoding::I16(Endianness::Little),
+                    (WAVE_FORMAT_IEEE_FLOAT, 32) => Encoding::F32,
+                    _ => {
+                        return Err(bad(&format!(
+                            "unsupported sample format {} with {} bits",
+                            tag, bits
+                        )))
+                    }
+                };
+                format = Some((encoding, rate));
+            }
+            b"data" => {
+                let (encoding, rate) = format.ok_or_else(|| bad("data before fmt chunk"))?;
+                return Ok((encoding, rate, len));
+            }
+            _ => {
+                // Chunks are padded to an even length
+                let skip = len + len % 2;
+                std::io::copy(&mut reader.by_ref().take(skip), &mut std::io::sink())
+                    .map_err(|e| io_err(path, e))?;
+            }
+        }
+    }
+}
+
+fn write_wav_header<W: Write>(out: &mut W, rate: u32, data_len: u64) -> std::io::Result<()> {
+    let data_len = data_len.min(u32::MAX as u64 - WAV_HEADER_LEN) as u32;
+    let mut header = Vec::with_capacity(WAV_HEADER_LEN as usize);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(data_len + WAV_HEADER_LEN as u32 - 8).to_le_bytes());
+    header.extend_from_slice(b"WAVE");
+    header.extend_from_slice(b"fmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+    // Two channels of 16 bits
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&rate.to_le_bytes());
+    header.extend_from_slice(&(rate * 4).to_le_bytes());
+    header.extend_from_slice(&4u16.to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_len.to_le_bytes());
+    out.write_all(&header)
+}
+
+// Fill `buf` unless the input ends first, returning the bytes read
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(len) => filled += len,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn big_endian_cs16_round_trips() {
+        let dir = std::env::temp_dir().join(format!("rtlsdr-convert-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let raw = dir.join("in.cu8");
+        let cs16 = dir.join("mid.cs16");
+        let back = dir.join("out.cu8");
+        fs::write(&raw, [255, 0, 128, 127]).unwrap();
+
+        let options = ConvertOptions {
+            sample_rate: Some(1_024_000),
+            output_endianness: Some(Endianness::Big),
+            ..Default::default()
+        };
+        let summary = convert_file(&raw, &cs16, &options).unwrap();
+        assert_eq!(summary.samples, 2);
+        // 1.0 saturates, -1.0 is full scale, 0.5 / 127.5 * 32768 rounds to 129
+        assert_eq!(
+            fs::read(&cs16).unwrap(),
+            [0x7f, 0xff, 0x80, 0x00, 0x00, 0x81, 0xff, 0x7f]
+        );
+        let meta = fs::read_to_string(cs16.with_extension("sigmf-meta")).unwrap();
+        assert!(meta.contains("\"ci16_be\""));
+
+        // The sidecar tells the reader the byte order
+        convert_file(&cs16, &back, &ConvertOptions::default()).unwrap();
+        assert_eq!(fs::read(&back).unwrap(), [255, 0, 128, 127]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,457 @@
+//! Recording raw u8 I/Q to a series of files, rotated by size or duration.
+//!
+//! Each chunk is written as `<prefix>-<UTC start>-<index>.cu8` (plus `.gz`/`.zst` when
+//! compressed) with a `.sigmf-meta` sidecar, or as `.cs16` once `set_i16_format`
+//! selects 16-bit output. While open, a chunk carries an extra
+//! `.part` suffix; it is renamed once complete, so tools watching the directory only
+//! ever see finished chunks.
+//!
+//! Retunes and gain changes reported through `config_observer` are written into the
+//! metadata as extra SigMF captures and annotations, so a chunk spanning a retune
+//! still says which samples were taken where.
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::convert::{self, I16Format};
+use crate::error::{io_err, Result};
+use crate::observer::{ConfigEvent, EventQueue};
+use crate::platform;
+use crate::record::sigmf::{file_timestamp, iso8601, SigmfMeta};
+use crate::TunerGain;
+
+const PART_SUFFIX: &str = ".part";
+
+/// Compression applied to each chunk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    /// Requires the `gzip` feature
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// Requires the `zstd` feature
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    fn extension(&self) -> &'static str {
+        match self {
+            Compression::None => "",
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => ".gz",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+/// When to close the current file and start the next. With no limits set, everything
+/// goes into a single file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RotationPolicy {
+    /// Uncompressed bytes per file, as written (two bytes per I/Q pair for cu8, four
+    /// for cs16)
+    pub max_bytes: Option<u64>,
+    /// Capture time per file, counted in samples when the sample rate is known and
+    /// by wall clock otherwise
+    pub max_duration: Option<Duration>,
+    pub compression: Compression,
+}
+
+enum ChunkWriter {
+    Plain(BufWriter<File>),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<BufWriter<File>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl ChunkWriter {
+    fn create(path: &Path, compression: Compression) -> std::io::Result<ChunkWriter> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(match compression {
+            Compression::None => ChunkWriter::Plain(file),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => ChunkWriter::Gzip(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            )),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => ChunkWriter::Zstd(zstd::Encoder::new(file, 0)?),
+        })
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        match self {
+            ChunkWriter::Plain(w) => w.write_all(buf),
+            #[cfg(feature = "gzip")]
+            ChunkWriter::Gzip(w) => w.write_all(buf),
+            #[cfg(feature = "zstd")]
+            ChunkWriter::Zstd(w) => w.write_all(buf),
+        }
+    }
+
+    // Write any compression trailer and flush to disk
+    #[allow(clippy::infallible_destructuring_match)] // without compression features
+    fn finish(self) -> std::io::Result<()> {
+        let file = match self {
+            ChunkWriter::Plain(w) => w,
+            #[cfg(feature = "gzip")]
+            ChunkWriter::Gzip(w) => w.finish()?,
+            #[cfg(feature = "zstd")]
+            ChunkWriter::Zstd(w) => w.finish()?,
+        };
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()
+    }
+}
+
+impl RotationPolicy {
+    // Raw u8 bytes `chunk` can still take, whole I/Q pairs only
+    fn room(&self, chunk: &Chunk) -> u64 {
+        let pair_bytes = if chunk.format.is_some() { 4 } else { 2 };
+        let mut pairs = self.max_bytes.map_or(u64::MAX, |max| max / pair_bytes);
+        if let Some(max) = self.max_duration {
+            if chunk.sample_rate > 0 {
+                let samples = max.as_secs_f64() * chunk.sample_rate as f64;
+                pairs = pairs.min(samples as u64);
+            } else if platform::now() - chunk.opened_at >= max {
+                return 0;
+            }
+        }
+        pairs.saturating_sub(chunk.bytes / 2).saturating_mul(2)
+    }
+}
+
+struct Chunk {
+    path: PathBuf,
+    writer: ChunkWriter,
+    // 16-bit layout written, `None` for raw u8
+    format: Option<I16Format>,
+    // Raw u8 bytes taken, two per sample whatever the format
+    bytes: u64,
+    opened_at: Duration,
+    started: u64,
+    sample_rate: u32,
+    center_freq: u64,
+    // (first sample, center frequency) of each retune within the chunk
+    retunes: Vec<(u64, u64)>,
+    // (first sample, gain) of each gain setting within the chunk
+    gains: Vec<(u64, TunerGain)>,
+}
+
+// Replace the entry at `sample` if there is one, so only the last of several changes
+// between two writes is kept
+pub(super) fn set_at<T>(entries: &mut Vec<(u64, T)>, sample: u64, value: T) {
+    match entries.last_mut() {
+        Some(last) if last.0 == sample => last.1 = value,
+        _ => entries.push((sample, value)),
+    }
+}
+
+pub(super) fn gain_label(gain: &TunerGain) -> String {
+    match gain {
+        TunerGain::Auto => "gain auto".to_string(),
+        TunerGain::Manual(_) => format!("gain {} dB", gain),
+    }
+}
+
+/// Writes a capture to rotating files under one directory
+pub struct FileRecorder {
+    dir: PathBuf,
+    prefix: String,
+    policy: RotationPolicy,
+    sample_rate: u32,
+    center_freq: u64,
+    gain: Option<TunerGain>,
+    format: Option<I16Format>,
+    events: EventQueue,
+    provenance: Option<String>,
+    current: Option<Chunk>,
+    // Conversion buffer for 16-bit output
+    packed: Vec<u8>,
+    index: u32,
+    closed: Vec<PathBuf>,
+}
+
+impl FileRecorder {
+    pub fn new<P: AsRef<Path>>(dir: P, prefix: &str, policy: RotationPolicy) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(|e| io_err(&dir, e))?;
+        Ok(FileRecorder {
+            dir,
+            prefix: prefix.to_string(),
+            policy,
+            sample_rate: 0,
+            center_freq: 0,
+            gain: None,
+            format: None,
+            events: EventQueue::new(),
+            provenance: None,
+            current: None,
+            packed: Vec::new(),
+            index: 0,
+            closed: Vec::new(),
+        })
+    }
+
+    /// Capture settings recorded in each chunk's metadata. Takes effect from the next
+    /// chunk; call `rotate` to apply it immediately after retuning.
+    pub fn set_metadata(&mut self, sample_rate: u32, center_freq: u64) {
+        self.sample_rate = sample_rate;
+        self.center_freq = center_freq;
+    }
+
+    /// Write chunks as 16-bit I/Q in `format` (`.cs16`) instead of raw u8, or raw u8
+    /// again with `None`. Takes effect from the next chunk. Planar packing applies to
+    /// each buffer passed to `write`; SigMF can't describe it, so such chunks have no
+    /// datatype in their metadata.
+    pub fn set_i16_format(&mut self, format: Option<I16Format>) {
+        self.format = format;
+    }
+
+    /// Observer to register with `RtlSdr::add_config_observer`. Changes it reports are
+    /// applied at the start of the next `write`.
+    pub fn config_observer(&self) -> impl Fn(&ConfigEvent) + Send + 'static {
+        self.events.observer()
+    }
+
+    /// Record a configuration change at the current position. A retune starts a new
+    /// SigMF capture and a gain change an annotation; a sample rate change closes the
+    /// file, since SigMF has one rate per recording.
+    pub fn config_event(&mut self, event: &ConfigEvent) -> Result<()> {
+        match event {
+            ConfigEvent::CenterFreq { new, .. } => {
+                self.center_freq = *new;
+                if let Some(chunk) = self.current.as_mut() {
+                    if chunk.bytes == 0 {
+                        chunk.center_freq = *new;
+                    } else {
+                        set_at(&mut chunk.retunes, chunk.bytes / 2, *new);
+                    }
+                }
+            }
+            ConfigEvent::SampleRate { new, .. } => {
+                self.sample_rate = *new;
+                match self.current.as_mut() {
+                    Some(chunk) if chunk.bytes == 0 => chunk.sample_rate = *new,
+                    Some(_) => self.rotate()?,
+                    None => {}
+                }
+            }
+            ConfigEvent::TunerGain { new, .. } => {
+                self.gain = Some(*new);
+                if let Some(chunk) = self.current.as_mut() {
+                    set_at(&mut chunk.gains, chunk.bytes / 2, *new);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Attach `Provenance::to_json` output to each chunk's metadata
+    pub fn set_provenance(&mut self, provenance_json: String) {
+        self.provenance = Some(provenance_json);
+    }
+
+    /// Append raw interleaved I/Q, starting new files as the policy requires. A buffer
+    /// is only ever split between I/Q pairs.
+    pub fn write(&mut self, mut buf: &[u8]) -> Result<()> {
+        for event in self.events.drain() {
+            self.config_event(&event)?;
+        }
+        while !buf.is_empty() {
+            if self.current.is_none() {
+                self.open_chunk()?;
+            }
+            let chunk = self.current.as_mut().unwrap();
+            let room = self.policy.room(chunk);
+            if room == 0 && chunk.bytes > 0 {
+                self.rotate()?;
+                continue;
+            }
+            // Even a tiny limit takes at least one pair per file
+            let room = if chunk.bytes == 0 { room.max(2) } else { room };
+            let n = (buf.len() as u64).min(room) as usize;
+            let out = match chunk.format {
+                Some(format) => {
+                    self.packed.clear();
+                    convert::u8_to_packed_i16(&buf[..n], format, &mut self.packed);
+                    &self.packed[..]
+                }
+                None => &buf[..n],
+            };
+            chunk
+                .writer
+                .write_all(out)
+                .map_err(|e| io_err(&chunk.path, e))?;
+            chunk.bytes += n as u64;
+            buf = &buf[n..];
+        }
+        Ok(())
+    }
+
+    /// Close the current file; the next write starts a new one
+    pub fn rotate(&mut self) -> Result<()> {
+        let Some(chunk) = self.current.take() else {
+            return Ok(());
+        };
+        let part = chunk.path.clone();
+        chunk.writer.finish().map_err(|e| io_err(&part, e))?;
+        let name = part.to_string_lossy();
+        let path = PathBuf::from(name.strip_suffix(PART_SUFFIX).unwrap_or(&name));
+        fs::rename(&part, &path).map_err(|e| io_err(&path, e))?;
+
+        // Each gain annotation lasts until the next change or the end of the chunk
+        let samples = chunk.bytes / 2;
+        let annotations = chunk
+            .gains
+            .iter()
+            .enumerate()
+            .map(|(i, (start, gain))| {
+                let end = chunk.gains.get(i + 1).map_or(samples, |next| next.0);
+                (*start, end - start, gain_label(gain))
+            })
+            .collect();
+        let meta = SigmfMeta {
+            datatype: chunk
+                .format
+                .and_then(|format| format.sigmf_datatype())
+                .map(str::to_string),
+            sample_rate: chunk.sample_rate,
+            center_freq: chunk.center_freq,
+            datetime: Some(iso8601(chunk.started)),
+            retunes: chunk.retunes,
+            provenance: self.provenance.clone(),
+            annotations,
+        };
+        let meta_path = self
+            .dir
+            .join(format!("{}.sigmf-meta", self.chunk_stem(chunk.started)));
+        fs::write(&meta_path, meta.to_json()).map_err(|e| io_err(&meta_path, e))?;
+        debug!("Closed recording chunk {}", path.display());
+        self.closed.push(path);
+        self.index += 1;
+        Ok(())
+    }
+
+    /// Close the current file
+    pub fn finish(mut self) -> Result<Vec<PathBuf>> {
+        self.rotate()?;
+        Ok(std::mem::take(&mut self.closed))
+    }
+
+    /// Files closed since the last call, oldest first
+    pub fn take_closed(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.closed)
+    }
+
+    fn chunk_stem(&self, started: u64) -> String {
+        format!(
+            "{}-{}-{:04}",
+            self.prefix,
+            file_timestamp(started),
+            self.index
+        )
+    }
+
+    fn open_chunk(&mut self) -> Result<()> {
+        let started = platform::wall_clock()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let path = self.dir.join(format!(
+            "{}.{}{}{}",
+            self.chunk_stem(started),
+            if self.format.is_some() { "cs16" } else { "cu8" },
+            self.policy.compression.extension(),
+            PART_SUFFIX
+        ));
+        let writer =
+            ChunkWriter::create(&path, self.policy.compression).map_err(|e| io_err(&path, e))?;
+        self.current = Some(Chunk {
+            path,
+            writer,
+            format: self.format,
+            bytes: 0,
+            opened_at: platform::now(),
+            started,
+            sample_rate: self.sample_rate,
+            center_freq: self.center_freq,
+            retunes: Vec::new(),
+            gains: self.gain.iter().map(|gain| (0, *gain)).collect(),
+        });
+        Ok(())
+    }
+}
+
+impl Drop for FileRecorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.rotate() {
+            error!("Failed to close recording: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{Endianness, Packing};
+
+    #[test]
+    fn i16_chunks_rotate_on_written_bytes() {
+        let dir = std::env::temp_dir().join(format!("rtlsdr-file-test-{}", std::process::id()));
+        let policy = RotationPolicy {
+            max_bytes: Some(8),
+            ..Default::default()
+        };
+        let mut recorder = FileRecorder::new(&dir, "cs16", policy).unwrap();
+        recorder.set_metadata(2_048_000, 100_000_000);
+        recorder.set_i16_format(Some(I16Format {
+            endianness: Endianness::Big,
+            packing: Packing::Interleaved,
+        }));
+        // Three I/Q pairs: two fill the first 8 byte file, the third starts another
+        recorder.write(&[255, 0, 255, 0, 255, 0]).unwrap();
+        let files = recorder.finish().unwrap();
+
+        assert_eq!(files.len(), 2);
+        assert!(files.iter().all(|f| f.extension().unwrap() == "cs16"));
+        assert_eq!(
+            fs::read(&files[0]).unwrap(),
+            [0x7f, 0x80, 0x80, 0x80, 0x7f, 0x80, 0x80, 0x80]
+        );
+        assert_eq!(fs::read(&files[1]).unwrap(), [0x7f, 0x80, 0x80, 0x80]);
+        let meta = fs::read_to_string(files[0].with_extension("sigmf-meta")).unwrap();
+        assert!(meta.contains("\"ci16_be\""));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn retunes_record_the_received_frequency() {
+        let dir = std::env::temp_dir().join(format!("rtlsdr-retune-test-{}", std::process::id()));
+        let mut recorder = FileRecorder::new(&dir, "x", RotationPolicy::default()).unwrap();
+        // 10 GHz band through a 9.75 GHz LNB: the tuner sits at 618 MHz
+        let lnb = 9_750_000_000;
+        recorder.set_metadata(2_048_000, crate::rf_freq(250_000_000, lnb));
+        recorder.write(&[127; 4]).unwrap();
+        recorder
+            .config_event(&ConfigEvent::CenterFreq {
+                previous: crate::rf_freq(250_000_000, lnb),
+                new: crate::rf_freq(618_000_000, lnb),
+            })
+            .unwrap();
+        recorder.write(&[127; 4]).unwrap();
+        let files = recorder.finish().unwrap();
+
+        let meta = fs::read_to_string(files[0].with_extension("sigmf-meta")).unwrap();
+        assert!(meta.contains("\"core:frequency\":10000000000"));
+        assert!(meta.contains("{\"core:sample_start\":2,\"core:frequency\":10368000000}"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
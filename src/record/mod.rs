@@ -0,0 +1,10 @@
+//! Recording raw samples to disk, and converting recordings between file formats.
+pub mod convert;
+pub mod file;
+pub mod pretrigger;
+pub mod sigmf;
+pub mod wav;
+pub use convert::{convert_file, ConvertOptions, ConvertSummary, IqFormat};
+pub use file::{Compression, FileRecorder, RotationPolicy};
+pub use pretrigger::PreTriggerBuffer;
+pub use wav::WavRecorder;
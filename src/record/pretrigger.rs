@@ -0,0 +1,117 @@
+//! Keeping the most recent samples in memory so a capture can start before the event
+//! that triggered it.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::error::{io_err, Result};
+use crate::platform;
+use crate::record::sigmf::{iso8601, SigmfMeta};
+
+/// Ring buffer of the last N seconds of raw u8 I/Q. Feed it every block read from the
+/// device and call `dump` when a detector fires.
+pub struct PreTriggerBuffer {
+    ring: Vec<u8>,
+    // Next byte to overwrite; also the oldest byte once the ring has filled
+    head: usize,
+    filled: bool,
+    sample_rate: u32,
+    // Total bytes pushed since creation
+    pushed: u64,
+}
+
+impl PreTriggerBuffer {
+    /// Buffer holding `duration` of samples at `sample_rate`
+    pub fn new(duration: Duration, sample_rate: u32) -> Self {
+        let samples = (duration.as_secs_f64() * sample_rate as f64).ceil() as usize;
+        Self::with_samples(samples, sample_rate)
+    }
+
+    pub fn with_samples(samples: usize, sample_rate: u32) -> Self {
+        PreTriggerBuffer {
+            ring: vec![0; samples.max(1) * 2],
+            head: 0,
+            filled: false,
+            sample_rate,
+            pushed: 0,
+        }
+    }
+
+    /// Append raw interleaved I/Q, overwriting the oldest samples when full
+    pub fn push(&mut self, mut buf: &[u8]) {
+        self.pushed += buf.len() as u64;
+        // Only the tail of an oversized block survives
+        if buf.len() >= self.ring.len() {
+            buf = &buf[buf.len() - self.ring.len()..];
+            self.ring.copy_from_slice(buf);
+            self.head = 0;
+            self.filled = true;
+            return;
+        }
+        let first = buf.len().min(self.ring.len() - self.head);
+        self.ring[self.head..self.head + first].copy_from_slice(&buf[..first]);
+        self.ring[..buf.len() - first].copy_from_slice(&buf[first..]);
+        let head = self.head + buf.len();
+        self.filled |= head >= self.ring.len();
+        self.head = head % self.ring.len();
+    }
+
+    /// Buffered samples, oldest first
+    pub fn snapshot(&self) -> Vec<u8> {
+        if self.filled {
+            [&self.ring[self.head..], &self.ring[..self.head]].concat()
+        } else {
+            self.ring[..self.head].to_vec()
+        }
+    }
+
+    /// Number of samples buffered
+    pub fn len(&self) -> usize {
+        if self.filled {
+            self.ring.len() / 2
+        } else {
+            self.head / 2
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Index, counted from the first push, of the oldest buffered sample
+    pub fn first_sample(&self) -> u64 {
+        self.pushed / 2 - self.len() as u64
+    }
+
+    /// Forget all buffered samples, e.g. after retuning
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.filled = false;
+    }
+
+    /// Write the buffered samples to `path` (raw cu8) with a `.sigmf-meta` sidecar
+    /// next to it, returning the sidecar path. The buffer is left intact.
+    pub fn dump<P: AsRef<Path>>(&self, path: P, center_freq: u64) -> Result<PathBuf> {
+        let path = path.as_ref();
+        fs::write(path, self.snapshot()).map_err(|e| io_err(path, e))?;
+
+        // The newest sample is now; work back to when the oldest was taken
+        let now = platform::wall_clock()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let span = self.len() as f64 / self.sample_rate.max(1) as f64;
+        let meta = SigmfMeta {
+            datatype: None,
+            sample_rate: self.sample_rate,
+            center_freq,
+            datetime: Some(iso8601((now - span).max(0.0) as u64)),
+            retunes: Vec::new(),
+            provenance: None,
+            annotations: Vec::new(),
+        };
+        let meta_path = path.with_extension("sigmf-meta");
+        fs::write(&meta_path, meta.to_json()).map_err(|e| io_err(&meta_path, e))?;
+        Ok(meta_path)
+    }
+}
@@ -0,0 +1,137 @@
+//! Minimal SigMF metadata (`.sigmf-meta`) for recordings: written for raw u8 captures,
+//! and read back for the fields format conversion carries over.
+use crate::provenance::json_str;
+
+/// SigMF specification version the metadata follows
+pub const SIGMF_VERSION: &str = "1.0.0";
+
+/// Global and capture fields for one recording
+#[derive(Debug, Clone, Default)]
+pub struct SigmfMeta {
+    /// SigMF datatype such as "cf32_le"; `None` for raw u8 ("cu8")
+    pub datatype: Option<String>,
+    pub sample_rate: u32,
+    /// 0 if unknown, which leaves it out of the metadata
+    pub center_freq: u64,
+    /// ISO 8601 UTC time of the first sample
+    pub datetime: Option<String>,
+    /// Captures after the first, as (first sample, center frequency), one per retune
+    pub retunes: Vec<(u64, u64)>,
+    /// `Provenance::to_json` output
+    pub provenance: Option<String>,
+    /// Annotations as (first sample, sample count, label)
+    pub annotations: Vec<(u64, u64, String)>,
+}
+
+impl SigmfMeta {
+    /// Datatype, sample rate, frequency and start time of a `.sigmf-meta` file, taking
+    /// the first capture. Anything else, including annotations, is ignored.
+    pub fn parse(json: &str) -> Option<SigmfMeta> {
+        let number = |key| json_value(json, key).and_then(|v| v.parse::<f64>().ok());
+        Some(SigmfMeta {
+            datatype: json_value(json, "core:datatype").map(|v| v.to_string()),
+            sample_rate: number("core:sample_rate")?.round() as u32,
+            center_freq: number("core:frequency").map_or(0, |f| f.round() as u64),
+            datetime: json_value(json, "core:datetime").map(|v| v.to_string()),
+            retunes: Vec::new(),
+            provenance: None,
+            annotations: Vec::new(),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut global = vec![
+            format!(
+                "\"core:datatype\":{}",
+                json_str(self.datatype.as_deref().unwrap_or("cu8"))
+            ),
+            format!("\"core:version\":{}", json_str(SIGMF_VERSION)),
+            format!("\"core:sample_rate\":{}", self.sample_rate),
+            format!(
+                "\"core:recorder\":{}",
+                json_str(concat!("rtlsdr-rs ", env!("CARGO_PKG_VERSION")))
+            ),
+        ];
+        if let Some(provenance) = &self.provenance {
+            global.push(format!("\"rtlsdr_rs:provenance\":{}", provenance));
+        }
+        let mut capture = vec!["\"core:sample_start\":0".to_string()];
+        if self.center_freq != 0 {
+            capture.push(format!("\"core:frequency\":{}", self.center_freq));
+        }
+        if let Some(datetime) = &self.datetime {
+            capture.push(format!("\"core:datetime\":{}", json_str(datetime)));
+        }
+        let mut captures = vec![format!("{{{}}}", capture.join(","))];
+        captures.extend(self.retunes.iter().map(|(start, freq)| {
+            format!(
+                "{{\"core:sample_start\":{},\"core:frequency\":{}}}",
+                start, freq
+            )
+        }));
+        let annotations: Vec<String> = self
+            .annotations
+            .iter()
+            .map(|(start, count, label)| {
+                format!(
+                    "{{\"core:sample_start\":{},\"core:sample_count\":{},\"core:label\":{}}}",
+                    start,
+                    count,
+                    json_str(label)
+                )
+            })
+            .collect();
+        format!(
+            "{{\"global\":{{{}}},\"captures\":[{}],\"annotations\":[{}]}}\n",
+            global.join(","),
+            captures.join(","),
+            annotations.join(",")
+        )
+    }
+}
+
+// Value of the first `"key":` in `json`, unquoted. Enough for the flat strings and
+// numbers of the core fields; escapes are not decoded.
+fn json_value<'a>(json: &'a str, key: &str) -> Option<&'a str> {
+    let start = json.find(&format!("\"{}\"", key))? + key.len() + 2;
+    let rest = json[start..].trim_start().strip_prefix(':')?.trim_start();
+    match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next(),
+        None => rest.split([',', '}', ']']).next().map(str::trim),
+    }
+}
+
+/// ISO 8601 UTC timestamp for `secs` since the Unix epoch, e.g. "2024-05-01T12:00:00Z"
+pub fn iso8601(secs: u64) -> String {
+    let (y, mo, d, h, mi, s) = civil(secs);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, mo, d, h, mi, s)
+}
+
+/// Compact UTC timestamp for file names, e.g. "20240501T120000Z"
+pub fn file_timestamp(secs: u64) -> String {
+    let (y, mo, d, h, mi, s) = civil(secs);
+    format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", y, mo, d, h, mi, s)
+}
+
+// Gregorian date and time from Unix seconds (days-to-civil algorithm)
+pub(crate) fn civil(secs: u64) -> (i64, u32, u32, u32, u32, u32) {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (
+        y,
+        m,
+        d,
+        (rem / 3600) as u32,
+        (rem % 3600 / 60) as u32,
+        (rem % 60) as u32,
+    )
+}
@@ -0,0 +1,304 @@
+//! Recording raw u8 I/Q as 8-bit stereo WAV (I left, Q right) with an `auxi` chunk in
+//! the SpectraVue/SDR# layout, for tools that play back WAV IQ recordings.
+//!
+//! `auxi` holds a single center frequency and sample rate, so a retune or rate change
+//! reported through `config_observer` closes the current file and continues in a new
+//! one whose `auxi` carries the new setting. Gain changes are listed in a trailing
+//! `LIST`/`INFO` comment, as `auxi` has no field for them.
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{io_err, Result};
+use crate::observer::{ConfigEvent, EventQueue};
+use crate::platform;
+use crate::record::file::{gain_label, set_at};
+use crate::record::sigmf::{civil, file_timestamp};
+use crate::TunerGain;
+
+const PART_SUFFIX: &str = ".part";
+const WAVE_FORMAT_PCM: u16 = 1;
+const AUXI_LEN: u32 = 68;
+// RIFF/WAVE, fmt, auxi and the data chunk header
+const HEADER_LEN: u64 = 12 + 8 + 16 + 8 + AUXI_LEN as u64 + 8;
+// Offset of the auxi stop time, patched when the file is closed
+const AUXI_STOP_OFFSET: u64 = 12 + 8 + 16 + 8 + 16;
+// RIFF sizes are 32-bit; leave room for the header and trailing comment
+const MAX_DATA_BYTES: u64 = u32::MAX as u64 - 64 * 1024;
+
+struct WavFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    bytes: u64,
+    started: SystemTime,
+    // (first sample, gain) of each gain setting within the file
+    gains: Vec<(u64, TunerGain)>,
+}
+
+/// Writes a capture to WAV files under one directory, one file per tuning
+pub struct WavRecorder {
+    dir: PathBuf,
+    prefix: String,
+    sample_rate: u32,
+    center_freq: u64,
+    gain: Option<TunerGain>,
+    events: EventQueue,
+    current: Option<WavFile>,
+    index: u32,
+    closed: Vec<PathBuf>,
+}
+
+impl WavRecorder {
+    pub fn new<P: AsRef<Path>>(dir: P, prefix: &str) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(|e| io_err(&dir, e))?;
+        Ok(WavRecorder {
+            dir,
+            prefix: prefix.to_string(),
+            sample_rate: 0,
+            center_freq: 0,
+            gain: None,
+            events: EventQueue::new(),
+            current: None,
+            index: 0,
+            closed: Vec::new(),
+        })
+    }
+
+    /// Capture settings written to each file's header. Takes effect from the next
+    /// file; `config_event` applies changes immediately.
+    pub fn set_metadata(&mut self, sample_rate: u32, center_freq: u64) {
+        self.sample_rate = sample_rate;
+        self.center_freq = center_freq;
+    }
+
+    /// Observer to register with `RtlSdr::add_config_observer`. Changes it reports are
+    /// applied at the start of the next `write`.
+    pub fn config_observer(&self) -> impl Fn(&ConfigEvent) + Send + 'static {
+        self.events.observer()
+    }
+
+    /// Record a configuration change at the current position: a retune or sample rate
+    /// change starts a new file, a gain change is added to the file's comment
+    pub fn config_event(&mut self, event: &ConfigEvent) -> Result<()> {
+        match event {
+            ConfigEvent::CenterFreq { new, .. } => {
+                self.center_freq = *new;
+                self.restart()?;
+            }
+            ConfigEvent::SampleRate { new, .. } => {
+                self.sample_rate = *new;
+                self.restart()?;
+            }
+            ConfigEvent::TunerGain { new, .. } => {
+                self.gain = Some(*new);
+                if let Some(file) = self.current.as_mut() {
+                    set_at(&mut file.gains, file.bytes / 2, *new);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Append raw interleaved I/Q, starting a new file before one outgrows the 4 GiB
+    /// WAV limit
+    pub fn write(&mut self, mut buf: &[u8]) -> Result<()> {
+        for event in self.events.drain() {
+            self.config_event(&event)?;
+        }
+        while !buf.is_empty() {
+            if self.current.is_none() {
+                self.open_file()?;
+            }
+            let file = self.current.as_mut().unwrap();
+            let room = MAX_DATA_BYTES - file.bytes;
+            if room < 2 {
+                self.rotate()?;
+                continue;
+            }
+            let n = (buf.len() as u64).min(room & !1) as usize;
+            file.writer
+                .write_all(&buf[..n])
+                .map_err(|e| io_err(&file.path, e))?;
+            file.bytes += n as u64;
+            buf = &buf[n..];
+        }
+        Ok(())
+    }
+
+    /// Close the current file, filling in its sizes and stop time; the next write
+    /// starts a new one
+    pub fn rotate(&mut self) -> Result<()> {
+        let Some(file) = self.current.take() else {
+            return Ok(());
+        };
+        let part = file.path.clone();
+        finish_file(file).map_err(|e| io_err(&part, e))?;
+        let name = part.to_string_lossy();
+        let path = PathBuf::from(name.strip_suffix(PART_SUFFIX).unwrap_or(&name));
+        fs::rename(&part, &path).map_err(|e| io_err(&path, e))?;
+        debug!("Closed WAV recording {}", path.display());
+        self.closed.push(path);
+        self.index += 1;
+        Ok(())
+    }
+
+    /// Close the current file
+    pub fn finish(mut self) -> Result<Vec<PathBuf>> {
+        self.rotate()?;
+        Ok(std::mem::take(&mut self.closed))
+    }
+
+    /// Files closed since the last call, oldest first
+    pub fn take_closed(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.closed)
+    }
+
+    // Move to a new file for changed settings, unless nothing is written yet
+    fn restart(&mut self) -> Result<()> {
+        match self.current.as_ref() {
+            Some(file) if file.bytes > 0 => self.rotate(),
+            // The header is rewritten with the new settings
+            Some(_) => {
+                let file = self.current.as_mut().unwrap();
+                file.writer
+                    .seek(SeekFrom::Start(0))
+                    .and_then(|_| {
+                        write_header(
+                            &mut file.writer,
+                            self.sample_rate,
+                            self.center_freq,
+                            file.started,
+                        )
+                    })
+                    .map_err(|e| io_err(&file.path, e))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn open_file(&mut self) -> Result<()> {
+        let started = platform::wall_clock();
+        let secs = started
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let path = self.dir.join(format!(
+            "{}-{}-{:04}.wav{}",
+            self.prefix,
+            file_timestamp(secs),
+            self.index,
+            PART_SUFFIX
+        ));
+        let mut writer = BufWriter::new(File::create(&path).map_err(|e| io_err(&path, e))?);
+        write_header(&mut writer, self.sample_rate, self.center_freq, started)
+            .map_err(|e| io_err(&path, e))?;
+        self.current = Some(WavFile {
+            path,
+            writer,
+            bytes: 0,
+            started,
+            gains: self.gain.iter().map(|gain| (0, *gain)).collect(),
+        });
+        Ok(())
+    }
+}
+
+impl Drop for WavRecorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.rotate() {
+            error!("Failed to close WAV recording: {}", e);
+        }
+    }
+}
+
+// Header with the sizes left at 0 and the stop time equal to the start
+fn write_header<W: Write>(
+    out: &mut W,
+    sample_rate: u32,
+    center_freq: u64,
+    started: SystemTime,
+) -> std::io::Result<()> {
+    let mut header = Vec::with_capacity(HEADER_LEN as usize);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(b"WAVE");
+    header.extend_from_slice(b"fmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+    // Two channels of 8 bits, unsigned like the samples from the device
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&8u16.to_le_bytes());
+    header.extend_from_slice(b"auxi");
+    header.extend_from_slice(&AUXI_LEN.to_le_bytes());
+    header.extend_from_slice(&system_time(started));
+    header.extend_from_slice(&system_time(started));
+    header.extend_from_slice(&(center_freq.min(u32::MAX as u64) as u32).to_le_bytes());
+    // A/D rate; IF frequency, bandwidth, IQ offset and four unused fields are 0
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&[0; 7 * 4]);
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&0u32.to_le_bytes());
+    out.write_all(&header)
+}
+
+// Append the gain comment and fill in the sizes and stop time
+fn finish_file(file: WavFile) -> std::io::Result<()> {
+    let mut writer = file.writer;
+    if !file.gains.is_empty() {
+        let mut comment = file
+            .gains
+            .iter()
+            .map(|(start, gain)| format!("{} from sample {}", gain_label(gain), start))
+            .collect::<Vec<_>>()
+            .join("; ")
+            .into_bytes();
+        comment.push(0);
+        if comment.len() % 2 == 1 {
+            comment.push(0);
+        }
+        writer.write_all(b"LIST")?;
+        writer.write_all(&(4 + 8 + comment.len() as u32).to_le_bytes())?;
+        writer.write_all(b"INFO")?;
+        writer.write_all(b"ICMT")?;
+        writer.write_all(&(comment.len() as u32).to_le_bytes())?;
+        writer.write_all(&comment)?;
+    }
+    let len = writer.stream_position()?;
+    writer.seek(SeekFrom::Start(4))?;
+    writer.write_all(&((len - 8) as u32).to_le_bytes())?;
+    writer.seek(SeekFrom::Start(AUXI_STOP_OFFSET))?;
+    writer.write_all(&system_time(platform::wall_clock()))?;
+    writer.seek(SeekFrom::Start(HEADER_LEN - 4))?;
+    writer.write_all(&(file.bytes as u32).to_le_bytes())?;
+    writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+}
+
+// Windows SYSTEMTIME (UTC) as used by `auxi`
+fn system_time(time: SystemTime) -> [u8; 16] {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (y, mo, d, h, mi, s) = civil(secs);
+    // 1970-01-01 was a Thursday
+    let weekday = (secs / 86_400 + 4) % 7;
+    let fields = [
+        y as u16,
+        mo as u16,
+        weekday as u16,
+        d as u16,
+        h as u16,
+        mi as u16,
+        s as u16,
+        since_epoch.subsec_millis() as u16,
+    ];
+    let mut out = [0; 16];
+    for (chunk, field) in out.chunks_exact_mut(2).zip(fields) {
+        chunk.copy_from_slice(&field.to_le_bytes());
+    }
+    out
+}
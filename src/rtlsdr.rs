@@ -1,19 +1,128 @@
-use super::{DirectSampleMode, TunerGain};
+use core::time::Duration;
+
+use super::{AdcInput, DirectSampleMode, IfMode, TunerGain};
+use crate::device::device_handle::UsbStrings;
+use crate::device::sequence::{self, Sequence};
 use crate::device::{
-    Device, BLOCK_SYS, BLOCK_USB, DEMOD_CTL, DEMOD_CTL_1, EEPROM_SIZE, GPD, GPO, GPOE, USB_EPA_CTL,
-    USB_EPA_MAXPKT, USB_SYSCTL,
+    AsyncCanceller, Device, DeviceQuirks, UsbDeviceSignature, BLOCK_SYS, BLOCK_USB, DEMOD_CTL,
+    EEPROM_SIZE, GPD, GPI, GPO, GPOE, USB_EPA_CTL, USB_STAT,
 };
-use crate::error::Result;
 use crate::error::RtlsdrError::RtlsdrErr;
+use crate::error::{Result, SampleRateError};
+use crate::platform;
 use crate::tuners::r820t::{R820T, R82XX_IF_FREQ, TUNER_ID};
-use crate::tuners::{NoTuner, Tuner, KNOWN_TUNERS};
-use log::{error, info};
-
-const INTERFACE_ID: u8 = 0;
+use crate::tuners::{
+    GainStage, GainTable, NoTuner, Tuner, TunerCapabilities, TunerInfo, TunerType, KNOWN_TUNERS,
+};
+use log::{debug, error, info, warn};
 
 const DEF_RTL_XTAL_FREQ: u32 = 28_800_000;
 const MIN_RTL_XTAL_FREQ: u32 = DEF_RTL_XTAL_FREQ - 1000;
-const MAX_RTL_XTAL_FREQ: u32 = DEF_RTL_XTAL_FREQ + 1000;
+pub(crate) const MAX_RTL_XTAL_FREQ: u32 = DEF_RTL_XTAL_FREQ + 1000;
+
+// EPA_CTL bits written by reset_buffer: 0x1000 stalls the endpoint, 0x0002 resets the FIFO
+const EPA_CTL_FIFO_RESET: u16 = 0x1002;
+
+/// Sample FIFO state read back from the USB block.
+///
+/// The RTL2832U has no documented FIFO overflow flag, so the raw `USB_STAT` and
+/// `USB_EPA_CTL` registers are included for diagnosis alongside the decoded bits that
+/// are known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FifoStatus {
+    pub usb_stat: u16,
+    pub epa_ctl: u16,
+    /// The endpoint is stalled or its FIFO held in reset, so no samples are delivered
+    pub held_in_reset: bool,
+}
+
+/// Optional parts of the init sequence, which can be skipped to speed up `open` on
+/// slow hubs and embedded hosts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InitSteps {
+    /// Skip the dummy register write that detects a wedged device and resets it
+    pub skip_reset_check: bool,
+    /// Skip reading the EEPROM, losing the bias tee and direct sampling overrides
+    /// stored there
+    pub skip_eeprom: bool,
+}
+
+/// How long each stage of opening the device took
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InitTiming {
+    /// Open attempts made, including the successful one
+    pub attempts: u32,
+    /// Opening the USB device
+    pub usb_open: Duration,
+    /// Claiming the interface and the reset check
+    pub reset: Duration,
+    pub baseband: Duration,
+    pub tuner_detect: Duration,
+    pub eeprom: Duration,
+    pub tuner_init: Duration,
+    /// Applying the sample rate, frequency and gain from `OpenOptions`
+    pub configure: Duration,
+    /// Discarding samples during `OpenOptions::warm_up`
+    pub warm_up: Duration,
+    /// Everything, including failed attempts and retry delays
+    pub total: Duration,
+}
+
+/// Sample rate programmed by `RtlSdr::set_sample_rate`. The resampler divides the
+/// crystal clock by a fixed-point ratio, so most requests land slightly off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AchievedRate {
+    pub requested: u32,
+    /// Exact rate in Hz, derived from the resampling ratio and crystal frequency
+    pub actual: f64,
+}
+
+impl AchievedRate {
+    /// Achieved minus requested rate, in Hz
+    pub fn error_hz(&self) -> f64 {
+        self.actual - self.requested as f64
+    }
+
+    /// Rate error in parts per million, the factor a downstream resampler has to
+    /// correct for
+    pub fn error_ppm(&self) -> f64 {
+        self.error_hz() / self.requested as f64 * 1e6
+    }
+}
+
+/// Loop parameters of the RTL2832's digital AGC, see `RtlSdr::set_digital_agc`.
+///
+/// The defaults are the demod's power-on values. The loop reacts to every burst,
+/// which makes the noise floor pump on bursty signals; a lower `loop_gain` slows it
+/// down, a lower `target` leaves more headroom above the average level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DigitalAgc {
+    /// Output level the loop aims for. Values from 0x20 to 0x60 are safe; much
+    /// higher clips strong signals, much lower wastes ADC resolution.
+    pub target: u8,
+    /// Loop speed, 0-31. Values from 0x08 up are safe; lower ones follow level
+    /// changes so slowly that strong bursts clip before the loop catches up.
+    pub loop_gain: u8,
+}
+
+impl Default for DigitalAgc {
+    fn default() -> Self {
+        DigitalAgc {
+            target: 0x39,
+            loop_gain: 0x16,
+        }
+    }
+}
+
+// Digital AGC registers, demod page 1
+const DAGC_TARGET_REG: u16 = 0x12;
+const DAGC_LOOP_GAIN_REG: u16 = 0xc8;
+const DAGC_LOOP_GAIN_MASK: u16 = 0x1f;
+
+pub(crate) const BIAS_TEE_GPIO: u8 = 0;
+
+/// Sample rates the resampler supports, in Hz, as inclusive ranges
+pub const SAMPLE_RATE_RANGES: [(u32, u32); 2] = [(225_001, 300_000), (900_001, 3_200_000)];
 
 pub(crate) const FIR_LEN: usize = 16;
 const DEFAULT_FIR: &'static [i32; FIR_LEN] = &[
@@ -27,15 +136,27 @@ pub struct RtlSdr {
     tuner: Box<dyn Tuner>,
     freq: u32, // Hz
     rate: u32, // Hz
+    // Rate passed to `set_sample_rate`, re-applied when the crystal changes
+    requested_rate: u32,
+    exact_rate: f64,
     bw: u32,
+    gain: TunerGain,
+    tuner_agc: bool,
+    rtl_agc: bool,
+    spectrum_inversion: bool,
+    iq_swap: bool,
     direct_sampling: DirectSampleMode,
+    if_mode: IfMode,
     xtal: u32,
     tuner_xtal: u32,
-    ppm_correction: u32,
     offset_freq: u32,
     corr: i32, // PPM
     force_bt: bool,
     force_ds: bool,
+    init_steps: InitSteps,
+    init_timing: InitTiming,
+    // Interface claimed and baseband possibly running; cleared by `close`
+    active: bool,
     //fir: [i32; FIR_LEN],
 }
 
@@ -46,74 +167,115 @@ impl RtlSdr {
             tuner: Box::new(NoTuner {}),
             freq: 0,
             rate: 0,
+            requested_rate: 0,
+            exact_rate: 0.0,
             bw: 0,
-            ppm_correction: 0,
+            gain: TunerGain::Auto,
+            tuner_agc: true,
+            rtl_agc: false,
+            spectrum_inversion: false,
+            iq_swap: false,
             xtal: DEF_RTL_XTAL_FREQ,
             tuner_xtal: DEF_RTL_XTAL_FREQ,
             direct_sampling: DirectSampleMode::Off,
+            if_mode: IfMode::LowIf,
             offset_freq: 0,
             corr: 0,
             force_bt: false,
             force_ds: false,
+            init_steps: InitSteps::default(),
+            init_timing: InitTiming::default(),
+            active: false,
             //fir: *DEFAULT_FIR,
         }
     }
 
     pub fn init(&mut self) -> Result<()> {
-        self.handle.claim_interface(INTERFACE_ID)?;
-        self.handle.test_write()?;
+        let mut stage = platform::now();
+        // Time since the previous stage ended
+        let mut lap = || {
+            let now = platform::now();
+            let elapsed = now - stage;
+            stage = now;
+            elapsed
+        };
+        self.handle.claim_interface(self.handle.usb.interface)?;
+        self.active = true;
+        if !self.init_steps.skip_reset_check {
+            self.handle.test_write()?;
+        }
+        self.init_timing.reset = lap();
         self.init_baseband()?;
+        for reg in self.handle.quirks.demod_regs.iter() {
+            self.handle
+                .demod_write_reg(reg.page, reg.addr, reg.val, reg.len)?;
+        }
+        self.init_timing.baseband = lap();
         self.set_i2c_repeater(true)?;
 
+        let signature = self.handle.signature().clone();
         self.tuner = {
             let tuner_id = match self.search_tuner() {
                 Some(tid) => {
                     info!("Got tuner ID {}", tid);
+                    if let Some(expected) = signature.tuner {
+                        if expected != tid {
+                            warn!(
+                                "{} usually ships with a {} tuner, but found {}",
+                                signature.description, expected, tid
+                            );
+                        }
+                    }
                     tid
                 }
                 None => {
-                    panic!("Failed to find tuner, aborting");
+                    return Err(RtlsdrErr(match signature.tuner {
+                        Some(expected) => format!(
+                            "Failed to find tuner ({} usually ships with an unsupported {} tuner)",
+                            signature.description, expected
+                        ),
+                        None => "Failed to find tuner".to_string(),
+                    }));
                 }
             };
             match tuner_id {
                 TUNER_ID => Box::new(R820T::new(&mut self.handle)),
-                _ => panic!("Unable to find recognized tuner"),
+                _ => return Err(RtlsdrErr("Unable to find recognized tuner".to_string())),
             }
         };
+        self.init_timing.tuner_detect = lap();
         // Use the RTL clock value by default
         self.tuner_xtal = self.xtal;
         self.tuner.set_xtal_freq(self.get_tuner_xtal_freq())?;
 
-        // disable Zero-IF mode
-        self.handle.demod_write_reg(1, 0xb1, 0x1a, 1)?;
-
-        // only enable In-phase ADC input
-        self.handle.demod_write_reg(0, 0x08, 0x4d, 1)?;
+        // disable Zero-IF mode, only enable In-phase ADC input
+        self.handle
+            .write_sequence(&sequence::sequence(Sequence::LowIf))?;
 
         // the R82XX use 3.57 MHz IF for the DVB-T 6 MHz mode, and
         // 4.57 MHz for the 8 MHz mode
         self.set_if_freq(R82XX_IF_FREQ)?;
 
         // enable spectrum inversion
-        self.handle.demod_write_reg(1, 0x15, 0x01, 1)?;
+        self.write_spectrum_inversion()?;
 
-        // Hack to force the Bias T to always be on if we set the IR-Endpoint bit in the EEPROM to 0. Default on EEPROM is 1.
-        let buf: [u8; EEPROM_SIZE] = [0; EEPROM_SIZE];
-        self.handle.read_eeprom(&buf, 0, EEPROM_SIZE)?;
-        if buf[7] & 0x02 != 0 {
+        if self.init_steps.skip_eeprom {
+            info!("Skipping EEPROM read for {}", signature.description);
             self.force_bt = false;
-        } else {
-            self.force_bt = true;
-        }
-        // Hack to force direct sampling mode to always be on if we set the remote-enabled bit in the EEPROM to 1. Default on EEPROM is 0.
-        if buf[7] & 0x01 != 0 {
-            self.force_ds = true;
-        } else {
             self.force_ds = false;
+        } else {
+            // Hack to force the Bias T to always be on if we set the IR-Endpoint bit in the EEPROM to 0. Default on EEPROM is 1.
+            let mut buf: [u8; EEPROM_SIZE] = [0; EEPROM_SIZE];
+            self.handle.read_eeprom(&mut buf, 0, EEPROM_SIZE)?;
+            self.force_bt = buf[7] & 0x02 == 0;
+            // Hack to force direct sampling mode to always be on if we set the remote-enabled bit in the EEPROM to 1. Default on EEPROM is 0.
+            self.force_ds = buf[7] & 0x01 != 0;
         }
+        self.init_timing.eeprom = lap();
         // TODO: if(force_ds){tuner_type = TUNER_UNKNOWN}
         info!("Init tuner");
         self.tuner.init(&self.handle)?;
+        self.init_timing.tuner_init = lap();
 
         // Finished Init
         self.set_i2c_repeater(false)?;
@@ -121,6 +283,62 @@ impl RtlSdr {
         Ok(())
     }
 
+    /// Must be called before `init`
+    pub fn set_init_steps(&mut self, steps: InitSteps) {
+        self.init_steps = steps;
+    }
+
+    pub fn init_timing(&self) -> &InitTiming {
+        &self.init_timing
+    }
+
+    pub(crate) fn init_timing_mut(&mut self) -> &mut InitTiming {
+        &mut self.init_timing
+    }
+
+    /// Known device table entry for the open device
+    pub fn signature(&self) -> &UsbDeviceSignature {
+        self.handle.signature()
+    }
+
+    #[cfg(feature = "testing")]
+    pub fn inject_faults(&mut self, script: Option<std::sync::Arc<crate::testing::FaultScript>>) {
+        self.handle.handle.set_fault_script(script);
+    }
+
+    pub fn get_usb_strings(&self) -> Result<UsbStrings> {
+        self.handle.handle.usb_strings()
+    }
+
+    pub fn get_tuner_info(&self) -> Result<TunerInfo> {
+        self.tuner.get_info()
+    }
+
+    /// Tuner found during init, `Unknown` before init
+    pub fn get_tuner_type(&self) -> TunerType {
+        self.tuner
+            .get_info()
+            .map(|info| TunerType::from_id(info.id))
+            .unwrap_or(TunerType::Unknown)
+    }
+
+    /// Tuner type and its tuning ranges, falling back to the driver's min/max for
+    /// tuners without a published range table
+    pub fn tuner_capabilities(&self) -> Result<TunerCapabilities> {
+        let tuner = self.get_tuner_type();
+        let mut freq_ranges = tuner.freq_ranges();
+        if freq_ranges.is_empty() {
+            let info = self.tuner.get_info()?;
+            freq_ranges.push((info.min_freq, info.max_freq));
+        }
+        Ok(TunerCapabilities { tuner, freq_ranges })
+    }
+
+    /// USB reset of the underlying device, used to recover from a failed init
+    pub fn reset_device(&mut self) -> Result<()> {
+        self.handle.reset()
+    }
+
     pub fn get_tuner_gains(&self) -> Result<Vec<i32>> {
         self.tuner.get_gains()
     }
@@ -130,10 +348,150 @@ impl RtlSdr {
         self.set_i2c_repeater(true)?;
         self.tuner.set_gain(&self.handle, gain)?;
         self.set_i2c_repeater(false)?;
+        self.gain = gain;
         Ok(())
     }
 
-    // TODO: set_bias_tee
+    pub fn get_tuner_gain(&self) -> TunerGain {
+        self.gain
+    }
+
+    /// Set one gain stage to a register index, see `Tuner::set_stage_gain`
+    pub fn set_stage_gain(&mut self, stage: GainStage, index: u8) -> Result<()> {
+        self.set_i2c_repeater(true)?;
+        let result = self.tuner.set_stage_gain(&self.handle, stage, index);
+        self.set_i2c_repeater(false)?;
+        result
+    }
+
+    /// Set one IF gain stage, numbered from 1, in tenths of a dB
+    pub fn set_tuner_if_gain(&mut self, stage: u8, gain: i32) -> Result<()> {
+        self.set_i2c_repeater(true)?;
+        let result = self.tuner.set_if_gain(&self.handle, stage, gain);
+        self.set_i2c_repeater(false)?;
+        result
+    }
+
+    /// Use a custom LNA/mixer/VGA gain partition table (`None` for the built-in one),
+    /// reapplying the current manual gain with it
+    pub fn set_gain_table(&mut self, table: Option<GainTable>) -> Result<()> {
+        self.tuner.set_gain_table(table)?;
+        if let TunerGain::Manual(_) = self.gain {
+            self.set_tuner_gain(self.gain)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_tuner_agc(&mut self, on: bool) -> Result<()> {
+        self.set_i2c_repeater(true)?;
+        self.tuner.set_agc(&self.handle, on)?;
+        self.set_i2c_repeater(false)?;
+        self.tuner_agc = on;
+        Ok(())
+    }
+
+    pub fn get_tuner_agc(&self) -> bool {
+        self.tuner_agc
+    }
+
+    /// Turn the RTL2832's digital AGC on or off (off after init)
+    pub fn set_rtl_agc(&mut self, on: bool) -> Result<()> {
+        self.handle
+            .demod_write_reg(0, 0x19, if on { 0x25 } else { 0x05 }, 1)?;
+        self.rtl_agc = on;
+        Ok(())
+    }
+
+    pub fn get_rtl_agc(&self) -> bool {
+        self.rtl_agc
+    }
+
+    /// Mirror the spectrum in the demodulator, relative to the orientation of the
+    /// current input. Survives switching direct sampling on and off.
+    pub fn set_spectrum_inversion(&mut self, on: bool) -> Result<()> {
+        self.spectrum_inversion = on;
+        self.write_spectrum_inversion()
+    }
+
+    pub fn get_spectrum_inversion(&self) -> bool {
+        self.spectrum_inversion
+    }
+
+    fn write_spectrum_inversion(&self) -> Result<()> {
+        // The R82XX low IF arrives inverted and is normally flipped back; direct
+        // sampling isn't inverted
+        let native = matches!(self.direct_sampling, DirectSampleMode::Off);
+        self.handle
+            .demod_write_reg(1, 0x15, (native != self.spectrum_inversion) as u16, 1)?;
+        Ok(())
+    }
+
+    /// Swap I and Q in every block returned by `read_sync`. No demodulator register
+    /// swaps its output, so this is done on the samples after each transfer.
+    pub fn set_iq_swap(&mut self, on: bool) {
+        self.iq_swap = on;
+    }
+
+    pub fn get_iq_swap(&self) -> bool {
+        self.iq_swap
+    }
+
+    /// Set the digital AGC's target level and loop gain. Takes effect while the
+    /// digital AGC is on.
+    pub fn set_digital_agc(&self, params: DigitalAgc) -> Result<()> {
+        if params.loop_gain as u16 > DAGC_LOOP_GAIN_MASK {
+            return Err(RtlsdrErr(format!(
+                "Digital AGC loop gain {} is out of range 0-{}",
+                params.loop_gain, DAGC_LOOP_GAIN_MASK
+            )));
+        }
+        if !(0x20..=0x60).contains(&params.target) {
+            warn!(
+                "Digital AGC target {:#04x} is outside the safe range 0x20-0x60",
+                params.target
+            );
+        }
+        self.handle
+            .demod_write_reg(1, DAGC_TARGET_REG, params.target as u16, 1)?;
+        let r = self.handle.demod_read_reg(1, DAGC_LOOP_GAIN_REG)?;
+        let r = (r & !DAGC_LOOP_GAIN_MASK) | params.loop_gain as u16;
+        self.handle.demod_write_reg(1, DAGC_LOOP_GAIN_REG, r, 1)?;
+        Ok(())
+    }
+
+    /// Digital AGC parameters, read back from the demodulator
+    pub fn get_digital_agc(&self) -> Result<DigitalAgc> {
+        Ok(DigitalAgc {
+            target: self.handle.demod_read_reg(1, DAGC_TARGET_REG)? as u8,
+            loop_gain: (self.handle.demod_read_reg(1, DAGC_LOOP_GAIN_REG)? & DAGC_LOOP_GAIN_MASK)
+                as u8,
+        })
+    }
+
+    /// Power down the tuner while keeping the demod and USB configuration
+    pub fn tuner_standby(&mut self) -> Result<()> {
+        if !matches!(self.direct_sampling, DirectSampleMode::Off) {
+            // Tuner is already shut down for direct sampling
+            return Ok(());
+        }
+        self.set_i2c_repeater(true)?;
+        self.tuner.exit(&self.handle)?;
+        self.set_i2c_repeater(false)?;
+        Ok(())
+    }
+
+    /// Re-initialize the tuner after `tuner_standby` and restore frequency, bandwidth and gain
+    pub fn tuner_wakeup(&mut self) -> Result<()> {
+        if !matches!(self.direct_sampling, DirectSampleMode::Off) {
+            return Ok(());
+        }
+        self.set_i2c_repeater(true)?;
+        self.tuner.init(&self.handle)?;
+        self.set_i2c_repeater(false)?;
+        // Also retunes
+        self.set_tuner_bandwidth(self.bw)?;
+        self.set_tuner_gain(self.gain)
+    }
 
     pub fn reset_buffer(&self) -> Result<()> {
         self.handle.write_reg(BLOCK_USB, USB_EPA_CTL, 0x1002, 2)?;
@@ -141,6 +499,17 @@ impl RtlSdr {
         Ok(())
     }
 
+    /// Raw USB block status registers, see `FifoStatus`
+    pub fn get_fifo_status(&self) -> Result<FifoStatus> {
+        let usb_stat = self.handle.read_reg(BLOCK_USB, USB_STAT, 1)?;
+        let epa_ctl = self.handle.read_reg(BLOCK_USB, USB_EPA_CTL, 2)?;
+        Ok(FifoStatus {
+            usb_stat,
+            epa_ctl,
+            held_in_reset: epa_ctl & EPA_CTL_FIFO_RESET != 0,
+        })
+    }
+
     pub fn get_center_freq(&self) -> u32 {
         self.freq
     }
@@ -159,9 +528,8 @@ impl RtlSdr {
     }
 
     pub fn set_if_freq(&self, freq: u32) -> Result<()> {
-        // Get corrected clock value - start with default
-        let rtl_xtal: u32 = DEF_RTL_XTAL_FREQ;
-        // Apply PPM correction
+        // Corrected clock value
+        let rtl_xtal = self.get_xtal_freq();
         let base = 1u32 << 22;
         let if_freq: i32 = (freq as f64 * base as f64 / rtl_xtal as f64 * -1f64) as i32;
 
@@ -193,15 +561,18 @@ impl RtlSdr {
         Ok(())
     }
 
+    /// Achieved sample rate, rounded to the nearest Hz
     pub fn get_sample_rate(&self) -> u32 {
         self.rate
     }
 
-    pub fn set_sample_rate(&mut self, rate: u32) -> Result<()> {
-        // Check if rate is supported by the resampler
-        if rate <= 225_000 || rate > 3_200_000 || (rate > 300000 && rate <= 900000) {
-            return Err(RtlsdrErr(format!("Invalid sample rate: {} Hz", rate)));
-        }
+    /// Achieved sample rate in Hz, unrounded
+    pub fn get_exact_sample_rate(&self) -> f64 {
+        self.exact_rate
+    }
+
+    pub fn set_sample_rate(&mut self, rate: u32) -> Result<AchievedRate> {
+        check_sample_rate(rate)?;
 
         // Compute exact sample rate
         let rsamp_ratio =
@@ -213,15 +584,30 @@ impl RtlSdr {
         let real_resamp_ratio = rsamp_ratio | ((rsamp_ratio & 0x08000000) << 1);
         info!("real_resamp_ratio: {}", real_resamp_ratio);
         let real_rate = (self.xtal as u128 * 2_u128.pow(22)) as f64 / real_resamp_ratio as f64;
-        if rate as f64 != real_rate {
-            info!("Exact sample rate is {} Hz", real_rate);
+        let achieved = AchievedRate {
+            requested: rate,
+            actual: real_rate,
+        };
+        if achieved.error_hz() != 0.0 {
+            warn!(
+                "Exact sample rate is {} Hz ({:+.3} Hz, {:+.3} ppm from requested)",
+                real_rate,
+                achieved.error_hz(),
+                achieved.error_ppm()
+            );
         }
         // Save exact rate
-        self.rate = real_rate as u32;
+        self.rate = real_rate.round() as u32;
+        self.requested_rate = rate;
+        self.exact_rate = real_rate;
 
         // Configure tuner
         self.set_i2c_repeater(true)?;
-        let val = if self.bw > 0 { self.bw } else { self.rate };
+        let val = if self.bw > 0 {
+            self.bw
+        } else {
+            self.tuner.auto_bandwidth(self.rate)
+        };
         self.tuner.set_bandwidth(&self.handle, val, self.rate)?;
         self.set_i2c_repeater(false)?;
         if self.tuner.get_info()?.id == TUNER_ID {
@@ -244,13 +630,19 @@ impl RtlSdr {
         if self.offset_freq != 0 {
             self.set_offset_tuning(true)?;
         }
-        Ok(())
+        Ok(achieved)
     }
 
-    pub fn set_tuner_bandwidth(&mut self, mut bw: u32) -> Result<()> {
-        bw = if bw > 0 { bw } else { self.rate };
+    /// Set the tuner's IF filter bandwidth in Hz. 0 selects automatic mode, in which
+    /// the filter follows the sample rate whenever that changes.
+    pub fn set_tuner_bandwidth(&mut self, bw: u32) -> Result<()> {
+        let val = if bw > 0 {
+            bw
+        } else {
+            self.tuner.auto_bandwidth(self.rate)
+        };
         self.set_i2c_repeater(true)?;
-        self.tuner.set_bandwidth(&self.handle, bw, self.rate)?;
+        self.tuner.set_bandwidth(&self.handle, val, self.rate)?;
         self.set_i2c_repeater(false)?;
         if self.tuner.get_info()?.id == TUNER_ID {
             self.set_if_freq(self.tuner.get_if_freq()?)?;
@@ -260,6 +652,22 @@ impl RtlSdr {
         Ok(())
     }
 
+    /// Whether the IF filter bandwidth follows the sample rate
+    pub fn is_tuner_bandwidth_auto(&self) -> bool {
+        self.bw == 0
+    }
+
+    /// Bandwidth of the tuner's IF filter as programmed, which is the requested
+    /// bandwidth rounded to one the filter supports
+    pub fn get_tuner_bandwidth(&self) -> Result<u32> {
+        self.tuner.get_bandwidth()
+    }
+
+    /// Bandwidths the tuner's IF filter supports, in Hz, ascending
+    pub fn get_tuner_bandwidths(&self) -> Result<Vec<u32>> {
+        self.tuner.get_bandwidths()
+    }
+
     pub fn set_testmode(&mut self, on: bool) -> Result<()> {
         match on {
             true => {
@@ -272,6 +680,58 @@ impl RtlSdr {
         Ok(())
     }
 
+    /// Disjoint ranges of center frequencies usable with direct sampling in state `mode`
+    pub fn get_freq_ranges(&self, mode: DirectSampleMode) -> Result<Vec<(u32, u32)>> {
+        match mode {
+            DirectSampleMode::Off => Ok(self.tuner_capabilities()?.freq_ranges),
+            DirectSampleMode::On | DirectSampleMode::OnSwap => {
+                Ok(vec![(0, self.direct_sampling_max_freq())])
+            }
+        }
+    }
+
+    /// Range of center frequencies usable with direct sampling in state `mode`
+    pub fn get_freq_range(&self, mode: DirectSampleMode) -> Result<(u32, u32)> {
+        match mode {
+            DirectSampleMode::Off => {
+                let info = self.tuner.get_info()?;
+                Ok((info.min_freq, info.max_freq))
+            }
+            DirectSampleMode::On | DirectSampleMode::OnSwap => {
+                Ok((0, self.direct_sampling_max_freq()))
+            }
+        }
+    }
+
+    /// Highest frequency reachable in direct sampling mode: the Nyquist limit of the
+    /// ADC, clocked by the corrected RTL2832 crystal
+    pub fn direct_sampling_max_freq(&self) -> u32 {
+        self.get_xtal_freq() / 2
+    }
+
+    pub fn get_direct_sampling(&self) -> DirectSampleMode {
+        self.direct_sampling
+    }
+
+    /// Read the ADC input selection back from the demodulator registers
+    pub fn get_adc_input(&self) -> Result<AdcInput> {
+        // Zero-IF tuners use both ADCs; 0x4d enables only the in-phase ADC
+        if self.handle.demod_read_reg(0, 0x08)? & 0xff != 0x4d {
+            return Ok(AdcInput::Tuner);
+        }
+        // Low-IF tuners (R82XX) also use only the I ADC, but with spectrum inversion
+        // (unless it was flipped with `set_spectrum_inversion`)
+        if (self.handle.demod_read_reg(1, 0x15)? & 0x01 != 0) != self.spectrum_inversion {
+            return Ok(AdcInput::Tuner);
+        }
+        // opt_adc_iq: 0x90 swaps the I and Q ADCs
+        if self.handle.demod_read_reg(0, 0x06)? & 0xff == 0x90 {
+            Ok(AdcInput::DirectQ)
+        } else {
+            Ok(AdcInput::DirectI)
+        }
+    }
+
     pub fn set_direct_sampling(&mut self, mut mode: DirectSampleMode) -> Result<()> {
         if self.force_ds {
             mode = DirectSampleMode::OnSwap;
@@ -285,9 +745,6 @@ impl RtlSdr {
                 // Disable Zero-IF mode
                 self.handle.demod_write_reg(1, 0xb1, 0x1a, 1)?;
 
-                // Disable spectrum inversion
-                self.handle.demod_write_reg(1, 0x15, 0x00, 1)?;
-
                 // Only enable in-phase ADC input
                 self.handle.demod_write_reg(0, 0x08, 0x4d, 1)?;
 
@@ -309,8 +766,7 @@ impl RtlSdr {
                 if self.tuner.get_info()?.id == TUNER_ID {
                     // tuner init already does all this
                     // self.set_if_freq(R82XX_IF_FREQ);
-                    // Enable spectrum inversion
-                    // handle.demod_write_reg(1, 0x15, 0x01, 1);
+                    // Spectrum inversion is restored below
                 } else {
                     self.set_if_freq(0)?;
 
@@ -326,33 +782,128 @@ impl RtlSdr {
                 self.direct_sampling = DirectSampleMode::Off;
             }
         }
+        // The native orientation differs between the two paths
+        self.write_spectrum_inversion()?;
         self.set_center_freq(self.freq)?;
         Ok(())
     }
 
+    pub fn set_if_mode(&mut self, mode: IfMode) -> Result<()> {
+        if !matches!(self.direct_sampling, DirectSampleMode::Off) {
+            return Err(RtlsdrErr(
+                "IF mode can't be changed while direct sampling".to_string(),
+            ));
+        }
+        match mode {
+            IfMode::ZeroIf => {
+                if !self.tuner.supports_zero_if() {
+                    return Err(RtlsdrErr(format!(
+                        "{} tuner doesn't support zero-IF",
+                        self.tuner.get_info()?.name
+                    )));
+                }
+                self.set_if_freq(0)?;
+                // Enable in-phase + quadrature ADC input
+                self.handle.demod_write_reg(0, 0x08, 0xcd, 1)?;
+                // Enable Zero-IF mode
+                self.handle.demod_write_reg(1, 0xb1, 0x1b, 1)?;
+            }
+            IfMode::LowIf => {
+                self.set_if_freq(self.tuner.get_if_freq()?)?;
+                self.handle
+                    .write_sequence(&sequence::sequence(Sequence::LowIf))?;
+            }
+        }
+        self.if_mode = mode;
+        Ok(())
+    }
+
+    pub fn get_if_mode(&self) -> IfMode {
+        self.if_mode
+    }
+
     pub fn set_offset_tuning(&self, _enable: bool) -> Result<()> {
         // RTL-SDR-BLOG Hack, enables us to turn on the bias tee by clicking on "offset tuning"
         // in software that doesn't have specified bias tee support.
         // Offset tuning is not used for R820T devices so it is no problem.
         #[cfg(feature = "rtl_sdr_blog")]
-        self.set_gpio(0, _enable)?;
+        self.set_gpio(BIAS_TEE_GPIO, _enable)?;
 
         // TODO: implement the rest when we support tuners beyond R82xx
         Ok(())
     }
 
     pub fn set_bias_tee(&self, on: bool) -> Result<()> {
-        Ok(self.set_gpio(0, on)?)
+        self.set_bias_tee_gpio(BIAS_TEE_GPIO, on)
+    }
+
+    /// Switch a bias tee wired to `gpio_pin` instead of the usual GPIO 0. The EEPROM
+    /// override keeps it on like `set_bias_tee`.
+    pub fn set_bias_tee_gpio(&self, gpio_pin: u8, on: bool) -> Result<()> {
+        self.set_gpio(gpio_pin, on)
+    }
+
+    /// Whether the bias tee on GPIO 0 is on, read back from the GPIO output register
+    pub fn get_bias_tee(&self) -> Result<bool> {
+        self.get_gpio(BIAS_TEE_GPIO)
+    }
+
+    /// Whether the board is known to have a switchable bias tee: RTL-SDR Blog dongles
+    /// that name themselves in their USB strings, boards with the `BIAS_TEE` quirk and
+    /// boards whose EEPROM forces the bias tee on
+    pub fn has_bias_tee(&self) -> bool {
+        if self.force_bt || self.handle.quirks.quirks.contains(DeviceQuirks::BIAS_TEE) {
+            return true;
+        }
+        match self.get_usb_strings() {
+            Ok(strings) => {
+                strings.manufacturer.contains("Blog") || strings.product.contains("Blog")
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Current output level of a GPIO pin
+    pub fn get_gpio(&self, gpio_pin: u8) -> Result<bool> {
+        let r = self.handle.read_reg(BLOCK_SYS, GPO, 1)?;
+        Ok(r & (1 << gpio_pin) != 0)
+    }
+
+    /// Drive a GPIO pin, without the EEPROM bias tee override applied by `set_bias_tee`
+    pub fn set_gpio_pin(&self, gpio_pin: u8, on: bool) -> Result<()> {
+        self.set_gpio_output(gpio_pin)?;
+        self.set_gpio_bit(gpio_pin, on)
+    }
+
+    /// Stop driving a GPIO pin so an external signal can be read with `get_gpio_input`
+    pub fn set_gpio_input(&self, gpio_pin: u8) -> Result<()> {
+        let gpio = 1 << gpio_pin;
+        let r = self.handle.read_reg(BLOCK_SYS, GPOE, 1)?;
+        self.handle.write_reg(BLOCK_SYS, GPOE, r & !gpio, 1)?;
+        let r = self.handle.read_reg(BLOCK_SYS, GPD, 1)?;
+        self.handle.write_reg(BLOCK_SYS, GPD, r | gpio, 1)?;
+        Ok(())
+    }
+
+    /// Level on a GPIO pin configured with `set_gpio_input`
+    pub fn get_gpio_input(&self, gpio_pin: u8) -> Result<bool> {
+        let r = self.handle.read_reg(BLOCK_SYS, GPI, 1)?;
+        Ok(r & (1 << gpio_pin) != 0)
     }
 
+    /// RTL2832 crystal frequency in Hz, with the frequency correction applied
     pub fn get_xtal_freq(&self) -> u32 {
-        (self.xtal as f32 * (1.0 + self.ppm_correction as f32 / 1e6)) as u32
+        apply_ppm(self.xtal, self.corr)
     }
 
+    /// Tuner crystal frequency in Hz, with the frequency correction applied
     pub fn get_tuner_xtal_freq(&self) -> u32 {
-        (self.tuner_xtal as f32 * (1.0 + self.ppm_correction as f32 / 1e6)) as u32
+        apply_ppm(self.tuner_xtal, self.corr)
     }
 
+    /// Set the actual crystal frequencies, e.g. after a TCXO mod. A `rtl_freq` of 0
+    /// keeps the current one; a `tuner_freq` of 0 means the tuner shares the RTL2832's
+    /// clock. The sample rate and center frequency are re-programmed to match.
     pub fn set_xtal_freq(&mut self, rtl_freq: u32, tuner_freq: u32) -> Result<()> {
         if rtl_freq > 0 && (rtl_freq < MIN_RTL_XTAL_FREQ || rtl_freq > MAX_RTL_XTAL_FREQ) {
             return Err(RtlsdrErr(format!(
@@ -364,17 +915,18 @@ impl RtlSdr {
             self.xtal = rtl_freq;
 
             // Update xtal-dependent settings
-            if self.rate != 0 {
-                self.set_sample_rate(self.rate)?;
+            if self.requested_rate != 0 {
+                self.set_sample_rate(self.requested_rate)?;
             }
         }
 
-        if self.tuner.get_xtal_freq()? != tuner_freq {
-            if tuner_freq == 0 {
-                self.tuner_xtal = self.xtal;
-            } else {
-                self.tuner_xtal = tuner_freq;
-            }
+        let tuner_freq = if tuner_freq == 0 {
+            self.xtal
+        } else {
+            tuner_freq
+        };
+        if self.tuner_xtal != tuner_freq {
+            self.tuner_xtal = tuner_freq;
 
             // Read corrected clock value into tuner
             self.tuner.set_xtal_freq(self.get_tuner_xtal_freq())?;
@@ -387,61 +939,64 @@ impl RtlSdr {
         Ok(())
     }
 
-    pub fn read_sync(&self, buf: &mut [u8]) -> Result<usize> {
-        self.handle.bulk_transfer(buf)
+    pub fn read_eeprom(&self, offset: u8, len: usize) -> Result<Vec<u8>> {
+        if len + offset as usize > EEPROM_SIZE {
+            return Err(RtlsdrErr(format!(
+                "EEPROM read of {} bytes at {:#04x} exceeds EEPROM size",
+                len, offset
+            )));
+        }
+        let mut buf = vec![0; len];
+        self.handle.read_eeprom(&mut buf, offset, len)?;
+        Ok(buf)
     }
 
-    fn init_baseband(&self) -> Result<()> {
-        // Init baseband
-        // info!("Initialize USB");
-        self.handle.write_reg(BLOCK_USB, USB_SYSCTL, 0x09, 1)?;
-        self.handle
-            .write_reg(BLOCK_USB, USB_EPA_MAXPKT, 0x0002, 2)?;
-        self.handle.write_reg(BLOCK_USB, USB_EPA_CTL, 0x1002, 2)?;
-
-        // info!("Power-on demod");
-        self.handle.write_reg(BLOCK_SYS, DEMOD_CTL_1, 0x22, 1)?;
-        self.handle.write_reg(BLOCK_SYS, DEMOD_CTL, 0xe8, 1)?;
-
-        // info!("Reset demod (bit 3, soft_rst)");
-        self.handle.reset_demod()?;
-
-        // info!("Disable spectrum inversion and adjust channel rejection");
-        self.handle.demod_write_reg(1, 0x15, 0x00, 1)?;
-        self.handle.demod_write_reg(1, 0x16, 0x00, 2)?;
+    pub fn write_eeprom(&self, data: &[u8], offset: u8) -> Result<usize> {
+        self.handle.write_eeprom(data, offset)
+    }
 
-        // info!("Clear DDC shift and IF registers");
-        for i in 0..5 {
-            self.handle.demod_write_reg(1, 0x16 + i, 0x00, 1)?;
+    pub fn read_sync(&self, buf: &mut [u8]) -> Result<usize> {
+        let len = self.handle.bulk_transfer(buf)?;
+        if self.iq_swap {
+            crate::convert::swap_iq(&mut buf[..len]);
         }
-        self.set_fir(DEFAULT_FIR)?;
-
-        // info!("Enable SDR mode, disable DAGC (bit 5)");
-        self.handle.demod_write_reg(0, 0x19, 0x05, 1)?;
-
-        // info!("Init FSM state-holding register");
-        self.handle.demod_write_reg(1, 0x93, 0xf0, 1)?;
-        self.handle.demod_write_reg(1, 0x94, 0x0f, 1)?;
-
-        // Disable AGC (en_dagc, bit 0) (seems to have no effect)
-        self.handle.demod_write_reg(1, 0x11, 0x00, 1)?;
-
-        // Disable RF and IF AGC loop
-        self.handle.demod_write_reg(1, 0x04, 0x00, 1)?;
-
-        // Disable PID filter
-        self.handle.demod_write_reg(0, 0x61, 0x60, 1)?;
+        Ok(len)
+    }
 
-        // opt_adc_iq = 0, default ADC_I/ADC_Q datapath
-        self.handle.demod_write_reg(0, 0x06, 0x80, 1)?;
+    pub fn read_async<F: FnMut(&[u8]) -> bool>(
+        &self,
+        mut callback: F,
+        buf_num: usize,
+        buf_len: usize,
+    ) -> Result<()> {
+        self.handle.read_async(
+            |buf| {
+                if self.iq_swap {
+                    crate::convert::swap_iq(buf);
+                }
+                callback(buf)
+            },
+            buf_num,
+            buf_len,
+        )
+    }
 
-        // Enable Zero-IF mode, DC cancellation, and IQ estimation/compensation
-        self.handle.demod_write_reg(1, 0xb1, 0x1b, 1)?;
+    pub fn async_canceller(&self) -> AsyncCanceller {
+        self.handle.async_canceller()
+    }
 
-        // Disable 4.096 MHz clock output on pin TP_CK0
-        self.handle.demod_write_reg(0, 0x0d, 0x83, 1)?;
+    pub(crate) fn device(&self) -> &Device {
+        &self.handle
+    }
 
-        Ok(())
+    fn init_baseband(&self) -> Result<()> {
+        // USB setup and demod power-on, then SDR mode once the FIR filter is loaded;
+        // see `device::sequence`
+        self.handle
+            .write_sequence(&sequence::sequence(Sequence::Baseband))?;
+        self.set_fir(DEFAULT_FIR)?;
+        self.handle
+            .write_sequence(&sequence::sequence(Sequence::SdrMode))
     }
 
     pub fn deinit_baseband(&mut self) -> Result<()> {
@@ -455,6 +1010,18 @@ impl RtlSdr {
         Ok(())
     }
 
+    /// Power down the tuner and demod and release the USB interface. Also done on drop;
+    /// calling it again does nothing.
+    pub fn close(&mut self) -> Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        self.active = false;
+        let deinit = self.deinit_baseband();
+        self.handle.release_interface(self.handle.usb.interface)?;
+        deinit
+    }
+
     fn set_sample_freq_correction(&self, ppm: i32) -> Result<()> {
         let offs = (ppm * (-1) * 2_i32.pow(24) / 1_000_000) as i16;
         self.handle
@@ -563,3 +1130,29 @@ impl RtlSdr {
         None
     }
 }
+
+impl Drop for RtlSdr {
+    fn drop(&mut self) {
+        // Expected to fail if the device was unplugged
+        if let Err(e) = self.close() {
+            debug!("Closing device on drop failed: {}", e);
+        }
+    }
+}
+
+/// Check that the resampler supports `rate`, naming the nearest rate that works if not
+pub(crate) fn check_sample_rate(rate: u32) -> Result<()> {
+    let nearest = SAMPLE_RATE_RANGES
+        .iter()
+        .map(|(min, max)| rate.clamp(*min, *max))
+        .min_by_key(|r| r.abs_diff(rate))
+        .unwrap_or(rate);
+    if nearest == rate {
+        return Ok(());
+    }
+    Err(SampleRateError { rate, nearest }.into())
+}
+
+fn apply_ppm(freq: u32, ppm: i32) -> u32 {
+    (freq as f64 * (1.0 + ppm as f64 / 1e6)) as u32
+}
@@ -0,0 +1,278 @@
+//! Separate handles for controlling and reading one device from different threads,
+//! created by `RtlSdr::split`.
+//!
+//! Both halves share the device behind a mutex. A control call made while a read is
+//! in flight takes effect as soon as that USB transfer completes, so keep transfers
+//! short (e.g. 16-32 KiB) when retuning latency matters.
+//!
+//! Several parties can control one device through controllers with different
+//! priorities (`Controller::with_priority`), e.g. a decoder that must stay on its
+//! frequency and a GUI that may still adjust the gain. A controller can lock a
+//! setting with `lock_param`; the other controllers' setters for it then fail until
+//! the lock is released, and a higher priority controller can take the lock over.
+//! Use `add_config_observer` (through `with`) to be told about changes.
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::device::AsyncCanceller;
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::rtlsdr::AchievedRate;
+use crate::{DirectSampleMode, RtlSdr, TunerGain};
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // A panic in one half shouldn't take the device away from the other
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Settings a `Controller` can lock against changes by the other controllers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Param {
+    CenterFreq,
+    SampleRate,
+    TunerGain,
+    FreqCorrection,
+    DirectSampling,
+    BiasTee,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Holder {
+    id: u64,
+    priority: u8,
+}
+
+type Locks = Mutex<HashMap<Param, Holder>>;
+
+static NEXT_CONTROLLER_ID: AtomicU64 = AtomicU64::new(0);
+
+// One arbitration identity, shared by plain clones of a controller. Its locks are
+// released once the last clone is gone.
+struct Identity {
+    id: u64,
+    priority: u8,
+    locks: Arc<Locks>,
+}
+
+impl Identity {
+    fn new(priority: u8, locks: Arc<Locks>) -> Arc<Identity> {
+        Arc::new(Identity {
+            id: NEXT_CONTROLLER_ID.fetch_add(1, Ordering::Relaxed),
+            priority,
+            locks,
+        })
+    }
+}
+
+impl Drop for Identity {
+    fn drop(&mut self) {
+        lock(&self.locks).retain(|_, holder| holder.id != self.id);
+    }
+}
+
+/// Tuning and gain half of a split device. Cheap to clone; clones share their
+/// priority and locks.
+#[derive(Clone)]
+pub struct Controller {
+    sdr: Arc<Mutex<RtlSdr>>,
+    identity: Arc<Identity>,
+    // Kept outside the mutex, which a running `read_async` holds
+    canceller: AsyncCanceller,
+}
+
+impl Controller {
+    /// Run `f` with exclusive access to the device, for settings without a
+    /// shortcut here. Parameter locks are not checked.
+    pub fn with<T, F: FnOnce(&mut RtlSdr) -> T>(&self, f: F) -> T {
+        f(&mut lock(&self.sdr))
+    }
+
+    /// Another controller of the same device with its own priority and locks. The
+    /// controller returned by `RtlSdr::split` has priority 0.
+    pub fn with_priority(&self, priority: u8) -> Controller {
+        Controller {
+            sdr: self.sdr.clone(),
+            identity: Identity::new(priority, self.identity.locks.clone()),
+            canceller: self.canceller.clone(),
+        }
+    }
+
+    pub fn priority(&self) -> u8 {
+        self.identity.priority
+    }
+
+    /// Keep the other controllers from changing `param`. Takes the lock over from a
+    /// lower priority controller; fails while one of equal or higher priority holds it.
+    pub fn lock_param(&self, param: Param) -> Result<()> {
+        let mut locks = lock(&self.identity.locks);
+        if let Some(holder) = locks.get(&param) {
+            if holder.id != self.identity.id && holder.priority >= self.identity.priority {
+                return Err(locked_err(param, holder));
+            }
+        }
+        locks.insert(
+            param,
+            Holder {
+                id: self.identity.id,
+                priority: self.identity.priority,
+            },
+        );
+        Ok(())
+    }
+
+    /// Release a lock taken with `lock_param`. Does nothing if another controller
+    /// holds it.
+    pub fn unlock_param(&self, param: Param) {
+        let mut locks = lock(&self.identity.locks);
+        if locks.get(&param).is_some_and(|h| h.id == self.identity.id) {
+            locks.remove(&param);
+        }
+    }
+
+    /// Priority of the controller holding a lock on `param`, if any
+    pub fn param_holder(&self, param: Param) -> Option<u8> {
+        lock(&self.identity.locks).get(&param).map(|h| h.priority)
+    }
+
+    // Run a setter for `param` unless another controller holds its lock. The lock
+    // table stays locked meanwhile so nobody can take the lock halfway.
+    fn set<T, F: FnOnce(&mut RtlSdr) -> Result<T>>(&self, param: Param, f: F) -> Result<T> {
+        let locks = lock(&self.identity.locks);
+        if let Some(holder) = locks.get(&param) {
+            if holder.id != self.identity.id {
+                return Err(locked_err(param, holder));
+            }
+        }
+        f(&mut lock(&self.sdr))
+    }
+
+    pub fn get_center_freq(&self) -> u32 {
+        lock(&self.sdr).get_center_freq()
+    }
+
+    pub fn set_center_freq(&self, freq: u32) -> Result<()> {
+        self.set(Param::CenterFreq, |sdr| sdr.set_center_freq(freq))
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        lock(&self.sdr).get_sample_rate()
+    }
+
+    pub fn set_sample_rate(&self, rate: u32) -> Result<AchievedRate> {
+        self.set(Param::SampleRate, |sdr| sdr.set_sample_rate(rate))
+    }
+
+    pub fn set_tuner_gain(&self, gain: TunerGain) -> Result<()> {
+        self.set(Param::TunerGain, |sdr| sdr.set_tuner_gain(gain))
+    }
+
+    pub fn set_tuner_agc(&self, on: bool) -> Result<()> {
+        self.set(Param::TunerGain, |sdr| sdr.set_tuner_agc(on))
+    }
+
+    pub fn set_freq_correction(&self, ppm: i32) -> Result<()> {
+        self.set(Param::FreqCorrection, |sdr| sdr.set_freq_correction(ppm))
+    }
+
+    pub fn get_direct_sampling(&self) -> DirectSampleMode {
+        lock(&self.sdr).get_direct_sampling()
+    }
+
+    pub fn set_direct_sampling(&self, mode: DirectSampleMode) -> Result<()> {
+        self.set(Param::DirectSampling, |sdr| sdr.set_direct_sampling(mode))
+    }
+
+    pub fn get_bias_tee(&self) -> Result<bool> {
+        lock(&self.sdr).get_bias_tee()
+    }
+
+    pub fn set_bias_tee(&self, on: bool) -> Result<()> {
+        self.set(Param::BiasTee, |sdr| sdr.set_bias_tee(on))
+    }
+
+    pub fn set_bias_tee_gpio(&self, pin: u8, on: bool) -> Result<()> {
+        self.set(Param::BiasTee, |sdr| sdr.set_bias_tee_gpio(pin, on))
+    }
+
+    /// Stop a running `Reader::read_async`. Doesn't wait for the device lock.
+    pub fn cancel_async(&self) {
+        self.canceller.cancel()
+    }
+}
+
+/// Sample reading half of a split device
+pub struct Reader {
+    sdr: Arc<Mutex<RtlSdr>>,
+}
+
+impl Reader {
+    pub fn read_sync(&self, buf: &mut [u8]) -> Result<usize> {
+        lock(&self.sdr).read_sync(buf)
+    }
+
+    pub fn reset_buffer(&self) -> Result<()> {
+        lock(&self.sdr).reset_buffer()
+    }
+
+    /// `RtlSdr::read_async`. The device stays locked until the read ends, so control
+    /// calls wait for it; stop it with `Controller::cancel_async`.
+    pub fn read_async<F: FnMut(&[u8]) -> bool>(
+        &self,
+        callback: F,
+        buf_num: usize,
+        buf_len: usize,
+    ) -> Result<()> {
+        lock(&self.sdr).read_async(callback, buf_num, buf_len)
+    }
+
+    /// Settings the samples about to be read were captured with, read together
+    /// with the samples so a concurrent retune can't slip in between
+    pub fn read_tagged(&self, buf: &mut [u8]) -> Result<(usize, u32, u32)> {
+        let sdr = lock(&self.sdr);
+        let len = sdr.read_sync(buf)?;
+        Ok((len, sdr.get_center_freq(), sdr.get_sample_rate()))
+    }
+}
+
+impl Read for Reader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        lock(&self.sdr).read(buf)
+    }
+}
+
+fn locked_err(param: Param, holder: &Holder) -> crate::error::RtlsdrError {
+    RtlsdrErr(format!(
+        "{:?} is locked by a controller with priority {}",
+        param, holder.priority
+    ))
+}
+
+pub(crate) fn split(sdr: RtlSdr) -> (Controller, Reader) {
+    let canceller = sdr.async_canceller();
+    let sdr = Arc::new(Mutex::new(sdr));
+    let controller = Controller {
+        sdr: sdr.clone(),
+        identity: Identity::new(0, Arc::new(Mutex::new(HashMap::new()))),
+        canceller,
+    };
+    (controller, Reader { sdr })
+}
+
+/// Put a split device back together. Fails, returning the halves, while clones of
+/// the controller are still alive or if the halves come from different devices.
+pub fn unsplit(
+    controller: Controller,
+    reader: Reader,
+) -> std::result::Result<RtlSdr, (Controller, Reader)> {
+    // Only these two handles may be left; clones can only be made from them
+    if !Arc::ptr_eq(&controller.sdr, &reader.sdr) || Arc::strong_count(&reader.sdr) != 2 {
+        return Err((controller, reader));
+    }
+    drop(controller);
+    match Arc::try_unwrap(reader.sdr) {
+        Ok(sdr) => Ok(sdr.into_inner().unwrap_or_else(|e| e.into_inner())),
+        Err(_) => unreachable!("no other handles to the device are left"),
+    }
+}
@@ -0,0 +1,147 @@
+//! Reading on a dedicated thread, created by `RtlSdr::start_stream`. The thread keeps
+//! USB transfers queued (`read_async`) and hands filled buffers over through a bounded
+//! channel, so a consumer that stalls for a moment costs queue space instead of
+//! overflowing the device FIFO. When the queue is full the newest buffer is dropped
+//! and counted; `SampleChunk::first_sample` shows where the gap is.
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::open::SampleChunk;
+use crate::device::AsyncCanceller;
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::{platform, RtlSdr};
+
+/// Buffers queued between the reader thread and the consumer by default
+pub const DEFAULT_QUEUE_LEN: usize = 16;
+
+/// Receiving end of a background reader thread. Dropping it stops the thread and
+/// closes the device; `stop` hands the device back instead.
+pub struct BackgroundStream {
+    chunks: Receiver<Result<SampleChunk>>,
+    device: Receiver<RtlSdr>,
+    canceller: AsyncCanceller,
+    // Also checked for every buffer, in case `cancel` came before the read started
+    stop: Arc<AtomicBool>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl BackgroundStream {
+    pub(crate) fn start(sdr: RtlSdr, queue_len: usize, buf_len: usize) -> Result<Self> {
+        let queue_len = if queue_len == 0 {
+            DEFAULT_QUEUE_LEN
+        } else {
+            queue_len
+        };
+        let (chunk_tx, chunks) = mpsc::sync_channel(queue_len);
+        let (device_tx, device) = mpsc::sync_channel(1);
+        let canceller = sdr.async_canceller();
+        let dropped = Arc::new(AtomicU64::new(0));
+        let thread_dropped = dropped.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        platform::spawn("rtlsdr-reader", move || {
+            read_loop(&sdr, buf_len, &chunk_tx, &thread_stop, &thread_dropped);
+            // Fails if the stream was dropped; the device is closed here then
+            let _ = device_tx.send(sdr);
+        })?;
+        Ok(BackgroundStream {
+            chunks,
+            device,
+            canceller,
+            stop,
+            dropped,
+        })
+    }
+
+    /// Next buffer, waiting for it. `None` once the reader thread has stopped.
+    pub fn recv(&self) -> Option<Result<SampleChunk>> {
+        self.chunks.recv().ok()
+    }
+
+    /// Next buffer if one is queued
+    pub fn try_recv(&self) -> Option<Result<SampleChunk>> {
+        self.chunks.try_recv().ok()
+    }
+
+    /// Next buffer, waiting at most `timeout`
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Result<SampleChunk>> {
+        self.chunks.recv_timeout(timeout).ok()
+    }
+
+    /// Buffers dropped so far because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Stop reading and hand the device back, once the reader thread has released it
+    pub fn stop(self) -> Result<RtlSdr> {
+        self.stop.store(true, Ordering::SeqCst);
+        self.canceller.cancel();
+        // Unblock a reader thread waiting to deliver an error
+        while self.chunks.try_recv().is_ok() {}
+        self.device
+            .recv()
+            .map_err(|_| RtlsdrErr("Reader thread exited without the device".to_string()))
+    }
+}
+
+impl Iterator for BackgroundStream {
+    type Item = Result<SampleChunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+impl Drop for BackgroundStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.canceller.cancel();
+    }
+}
+
+fn read_loop(
+    sdr: &RtlSdr,
+    buf_len: usize,
+    chunks: &SyncSender<Result<SampleChunk>>,
+    stop: &AtomicBool,
+    dropped: &AtomicU64,
+) {
+    let mut first_sample = 0;
+    let result = sdr.reset_buffer().and_then(|_| {
+        sdr.read_async(
+            |data| {
+                if stop.load(Ordering::SeqCst) {
+                    return false;
+                }
+                let chunk = SampleChunk {
+                    data: data.to_vec(),
+                    first_sample,
+                    sample_rate: sdr.get_sample_rate(),
+                    center_freq: sdr.get_center_freq(),
+                };
+                first_sample += data.len() as u64 / 2;
+                match chunks.try_send(Ok(chunk)) {
+                    Ok(()) => true,
+                    Err(TrySendError::Full(_)) => {
+                        if dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                            warn!("Consumer too slow, dropping buffers");
+                        }
+                        true
+                    }
+                    // The stream has been dropped
+                    Err(TrySendError::Disconnected(_)) => false,
+                }
+            },
+            0,
+            buf_len,
+        )
+    });
+    if let Err(e) = result {
+        error!("Background read stopped: {}", e);
+        let _ = chunks.send(Err(e));
+    }
+}
@@ -0,0 +1,174 @@
+//! Raw sample buffers as a `futures_core::Stream`, created by `RtlSdr::byte_stream`, so
+//! async code can `while let Some(buf) = stream.next().await` instead of polling
+//! `AsyncRead` by hand. The stream also implements tokio's `AsyncRead` (and
+//! `futures_io::AsyncRead` with `futures-io`) for code that wants a byte reader.
+//!
+//! A dedicated thread keeps USB transfers queued (`read_async`) and hands buffers over
+//! through a bounded channel. Unlike `BackgroundStream`, nothing is dropped: when the
+//! consumer falls behind, the reader thread waits, and the device FIFO takes up the
+//! slack until it overflows.
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+pub use bytes::Bytes;
+use futures_core::Stream;
+
+use super::channel;
+use crate::device::AsyncCanceller;
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::{platform, RtlSdr};
+
+/// Buffers read ahead of the consumer by default
+pub const DEFAULT_STREAM_QUEUE_LEN: usize = 8;
+
+/// Stream of raw interleaved u8 I/Q buffers. It ends after the first error. Dropping
+/// it stops reading and closes the device; `stop` hands the device back instead.
+pub struct ByteStream {
+    buffers: channel::Receiver<Result<Bytes>>,
+    device: mpsc::Receiver<RtlSdr>,
+    canceller: AsyncCanceller,
+    // Also checked for every buffer, in case `cancel` came before the read started
+    stop: Arc<AtomicBool>,
+    // Rest of a buffer only partly consumed by `AsyncRead`
+    pending: Bytes,
+}
+
+impl ByteStream {
+    pub(crate) fn start(sdr: RtlSdr, queue_len: usize, buf_len: usize) -> Result<Self> {
+        let queue_len = if queue_len == 0 {
+            DEFAULT_STREAM_QUEUE_LEN
+        } else {
+            queue_len
+        };
+        let (tx, buffers) = channel::channel(queue_len);
+        let (device_tx, device) = mpsc::sync_channel(1);
+        let canceller = sdr.async_canceller();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        platform::spawn("rtlsdr-stream", move || {
+            read_loop(&sdr, buf_len, &tx, &thread_stop);
+            // End the stream before handing the device back
+            drop(tx);
+            // Fails if the stream was dropped; the device is closed here then
+            let _ = device_tx.send(sdr);
+        })?;
+        Ok(ByteStream {
+            buffers,
+            device,
+            canceller,
+            stop,
+            pending: Bytes::new(),
+        })
+    }
+
+    /// Stop reading and hand the device back, once the reader thread has released it.
+    /// Blocks for as long as cancelling the queued transfers takes.
+    pub fn stop(self) -> Result<RtlSdr> {
+        self.stop.store(true, Ordering::SeqCst);
+        self.canceller.cancel();
+        // Unblock a reader thread waiting for queue space
+        self.buffers.close();
+        self.device
+            .recv()
+            .map_err(|_| RtlsdrErr("Reader thread exited without the device".to_string()))
+    }
+
+    // Copy as much of the next buffer into `dst` as fits, waiting for the reader
+    // thread without blocking. 0 bytes means the stream has ended.
+    fn poll_read_into(&mut self, cx: &mut Context<'_>, dst: &mut [u8]) -> Poll<io::Result<usize>> {
+        if dst.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        while self.pending.is_empty() {
+            match Pin::new(&mut self.buffers).poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => return Poll::Ready(Ok(0)),
+                Poll::Ready(Some(Err(e))) => {
+                    return Poll::Ready(Err(io::Error::other(format!(
+                        "Error reading from device: {:?}",
+                        e
+                    ))))
+                }
+                Poll::Ready(Some(Ok(buf))) => self.pending = buf,
+            }
+        }
+        let len = dst.len().min(self.pending.len());
+        dst[..len].copy_from_slice(&self.pending[..len]);
+        self.pending = self.pending.slice(len..);
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl Stream for ByteStream {
+    type Item = Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Whatever `AsyncRead` left over comes first
+        if !self.pending.is_empty() {
+            return Poll::Ready(Some(Ok(std::mem::take(&mut self.pending))));
+        }
+        Pin::new(&mut self.buffers).poll_next(cx)
+    }
+}
+
+#[cfg(feature = "async-tokio")]
+impl tokio::io::AsyncRead for ByteStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let len = match self.poll_read_into(cx, buf.initialize_unfilled()) {
+            Poll::Ready(Ok(len)) => len,
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        };
+        buf.advance(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl futures_io::AsyncRead for ByteStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_read_into(cx, buf)
+    }
+}
+
+impl Drop for ByteStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.canceller.cancel();
+    }
+}
+
+fn read_loop(
+    sdr: &RtlSdr,
+    buf_len: usize,
+    buffers: &channel::Sender<Result<Bytes>>,
+    stop: &AtomicBool,
+) {
+    let result = sdr.reset_buffer().and_then(|_| {
+        sdr.read_async(
+            |data| {
+                !stop.load(Ordering::SeqCst)
+                    && buffers.send_blocking(Ok(Bytes::copy_from_slice(data)))
+            },
+            0,
+            buf_len,
+        )
+    });
+    if let Err(e) = result {
+        error!("Stream read stopped: {}", e);
+        buffers.send_blocking(Err(e));
+    }
+}
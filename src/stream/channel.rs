@@ -0,0 +1,167 @@
+//! Bounded channel from a blocking reader thread to an async consumer.
+//!
+//! Only needs `std`: the consumer side is woken through the `Waker` of whatever
+//! executor polls it, so `open_stream` works the same under tokio, async-std, smol or
+//! a hand-rolled `block_on`.
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream;
+
+struct State<T> {
+    queue: VecDeque<T>,
+    waker: Option<Waker>,
+    sender_gone: bool,
+    receiver_gone: bool,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    space: Condvar,
+    // Signalled with the waker, for `recv_blocking`
+    items: Condvar,
+    capacity: usize,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub(crate) struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub(crate) struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub(crate) fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            waker: None,
+            sender_gone: false,
+            receiver_gone: false,
+        }),
+        space: Condvar::new(),
+        items: Condvar::new(),
+        capacity: capacity.max(1),
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+impl<T> Sender<T> {
+    /// Queue `item`, blocking while the queue is full. Returns false once the
+    /// receiver has been dropped.
+    pub(crate) fn send_blocking(&self, item: T) -> bool {
+        let mut state = self.shared.lock();
+        while state.queue.len() >= self.shared.capacity && !state.receiver_gone {
+            state = self
+                .shared
+                .space
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        if state.receiver_gone {
+            return false;
+        }
+        state.queue.push_back(item);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.shared.items.notify_one();
+        true
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.sender_gone = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.shared.items.notify_one();
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut state = self.shared.lock();
+        if let Some(item) = state.queue.pop_front() {
+            self.shared.space.notify_one();
+            return Poll::Ready(Some(item));
+        }
+        if state.sender_gone {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Take the next item, blocking until one arrives. `None` once the sender is gone
+    /// and the queue is empty.
+    pub(crate) fn recv_blocking(&self) -> Option<T> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(item) = state.queue.pop_front() {
+                self.shared.space.notify_one();
+                return Some(item);
+            }
+            if state.sender_gone {
+                return None;
+            }
+            state = self
+                .shared
+                .items
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Make the sender give up as if the receiver had been dropped, waking it if it
+    /// is waiting for space
+    pub(crate) fn close(&self) {
+        self.shared.lock().receiver_gone = true;
+        self.shared.space.notify_one();
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recv_blocking_waits_for_the_sender() {
+        let (tx, rx) = channel(1);
+        let sender = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            tx.send_blocking(1);
+            tx.send_blocking(2);
+        });
+        assert_eq!(rx.recv_blocking(), Some(1));
+        assert_eq!(rx.recv_blocking(), Some(2));
+        // The sender is dropped when its thread ends
+        assert_eq!(rx.recv_blocking(), None);
+        sender.join().unwrap();
+    }
+}
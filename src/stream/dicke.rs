@@ -0,0 +1,77 @@
+use crate::error::Result;
+use crate::{RtlSdr, DEFAULT_BUF_LENGTH};
+
+/// Noise source state while a block was captured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseState {
+    On,
+    Off,
+    /// First blocks after a switch, which may hold samples from before it (USB FIFO
+    /// latency) and should be left out of the on/off averages
+    Settling {
+        on: bool,
+    },
+}
+
+/// Dicke switching: toggles a GPIO-driven noise source every `blocks_per_state` USB
+/// blocks and tags each block with the state it was captured in, created by
+/// `RtlSdr::dicke_switch`. Comparing on and off power cancels slow gain drift.
+pub struct DickeSwitch<'a> {
+    sdr: &'a RtlSdr,
+    pin: u8,
+    blocks_per_state: usize,
+    settle_blocks: usize,
+    on: bool,
+    block: usize,
+    buf: Vec<u8>,
+}
+
+impl<'a> DickeSwitch<'a> {
+    pub(crate) fn new(sdr: &'a RtlSdr, pin: u8, blocks_per_state: usize) -> Result<Self> {
+        sdr.sdr.set_gpio_pin(pin, false)?;
+        Ok(DickeSwitch {
+            sdr,
+            pin,
+            blocks_per_state: blocks_per_state.max(1),
+            settle_blocks: 1,
+            on: false,
+            block: 0,
+            buf: vec![0; DEFAULT_BUF_LENGTH],
+        })
+    }
+
+    /// Blocks after each switch tagged `Settling` (default 1)
+    pub fn set_settle_blocks(&mut self, blocks: usize) {
+        self.settle_blocks = blocks;
+    }
+
+    /// Bytes per block, rounded up to a multiple of 512
+    pub fn set_block_len(&mut self, len: usize) {
+        self.buf = vec![0; len.max(1).next_multiple_of(512)];
+    }
+
+    /// Read the next block, switching the noise source first if its state is over.
+    /// The returned slice is valid until the next call.
+    pub fn read(&mut self) -> Result<(&[u8], NoiseState)> {
+        if self.block == self.blocks_per_state {
+            self.on = !self.on;
+            self.sdr.sdr.set_gpio_pin(self.pin, self.on)?;
+            self.block = 0;
+        }
+        let state = if self.block < self.settle_blocks {
+            NoiseState::Settling { on: self.on }
+        } else if self.on {
+            NoiseState::On
+        } else {
+            NoiseState::Off
+        };
+        self.block += 1;
+        let len = self.sdr.read_sync(&mut self.buf)?;
+        Ok((&self.buf[..len], state))
+    }
+
+    /// Turn the noise source off
+    pub fn stop(self) -> Result<()> {
+        self.sdr.sdr.set_gpio_pin(self.pin, false)
+    }
+}
@@ -0,0 +1,216 @@
+//! LO frequency dithering for long spectral integrations. Spurs made inside the dongle
+//! (clock harmonics, the DC spike) stay put relative to the LO, so in a plain average
+//! they build up just like a real weak line. Retuning by a random offset between
+//! integration blocks and shifting each block's spectrum back by the same offset keeps
+//! sky signals on their bins while the spurs land somewhere else every time and
+//! average out into the noise floor.
+//!
+//! Offsets are whole FFT bins, so the digital compensation is an exact bin shift.
+//! Bins near the band edges are covered by fewer blocks than those in the middle.
+use std::time::UNIX_EPOCH;
+
+use crate::convert::DEFAULT_DC_OFFSET;
+use crate::dsp::fft::{fft, hann};
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::platform;
+use crate::{RtlSdr, DEFAULT_BUF_LENGTH};
+
+/// Settings for `RtlSdr::dithered_spectrum`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreqDitherConfig {
+    /// FFT length (a power of two)
+    pub fft_len: usize,
+    /// Largest LO offset from the center frequency in either direction, in Hz
+    pub max_offset: u32,
+    /// Blocks integrated at one offset before retuning
+    pub blocks_per_step: usize,
+    /// Blocks discarded after each retune
+    pub settle_blocks: usize,
+}
+
+impl Default for FreqDitherConfig {
+    fn default() -> Self {
+        FreqDitherConfig {
+            fft_len: 1024,
+            max_offset: 100_000,
+            blocks_per_step: 4,
+            settle_blocks: 1,
+        }
+    }
+}
+
+/// Power spectrum averaged over LO-dithered blocks, created by
+/// `RtlSdr::dithered_spectrum`
+pub struct DitheredSpectrum<'a> {
+    sdr: &'a mut RtlSdr,
+    center: u32,
+    config: FreqDitherConfig,
+    bin_hz: f64,
+    max_bins: i64,
+    rng: u64,
+    window: Vec<f32>,
+    // Sum of |X|^2 over the window of one full-scale sample: N * sum(w^2)
+    norm: f64,
+    re: Vec<f32>,
+    im: Vec<f32>,
+    // Accumulated power and frame count per bin, lowest frequency first
+    sum: Vec<f64>,
+    frames: Vec<u64>,
+    steps: u64,
+    buf: Vec<u8>,
+}
+
+impl<'a> DitheredSpectrum<'a> {
+    pub(crate) fn new(sdr: &'a mut RtlSdr, center: u32, config: FreqDitherConfig) -> Result<Self> {
+        let rate = sdr.get_sample_rate();
+        if rate == 0 {
+            return Err(RtlsdrErr(
+                "Set a sample rate before integrating a spectrum".to_string(),
+            ));
+        }
+        if !config.fft_len.is_power_of_two() || config.fft_len * 2 > DEFAULT_BUF_LENGTH {
+            return Err(RtlsdrErr(format!(
+                "FFT length {} is not a power of two up to {}",
+                config.fft_len,
+                DEFAULT_BUF_LENGTH / 2
+            )));
+        }
+        if config.max_offset >= rate / 4 {
+            return Err(RtlsdrErr(format!(
+                "Dither offset {} Hz must stay below a quarter of the sample rate",
+                config.max_offset
+            )));
+        }
+        if config.blocks_per_step == 0 {
+            return Err(RtlsdrErr(
+                "Integrate at least one block per step".to_string(),
+            ));
+        }
+        let n = config.fft_len;
+        let bin_hz = rate as f64 / n as f64;
+        let window = hann(n);
+        let norm = n as f64 * window.iter().map(|w| (w * w) as f64).sum::<f64>();
+        let seed = platform::wall_clock()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Ok(DitheredSpectrum {
+            sdr,
+            center,
+            config,
+            bin_hz,
+            max_bins: (config.max_offset as f64 / bin_hz) as i64,
+            // xorshift must not start at zero
+            rng: seed | 1,
+            window,
+            norm,
+            re: vec![0.0; n],
+            im: vec![0.0; n],
+            sum: vec![0.0; n],
+            frames: vec![0; n],
+            steps: 0,
+            buf: vec![0; DEFAULT_BUF_LENGTH],
+        })
+    }
+
+    /// Tune to a new random offset, integrate `blocks_per_step` blocks there and
+    /// return the offset used in Hz
+    pub fn step(&mut self) -> Result<i64> {
+        let offset_bins = self.next_offset();
+        let offset = (offset_bins as f64 * self.bin_hz).round() as i64;
+        let freq = u32::try_from(self.center as i64 + offset).map_err(|_| {
+            RtlsdrErr(format!(
+                "Dithered frequency {} Hz is out of range",
+                self.center as i64 + offset
+            ))
+        })?;
+        self.sdr.set_center_freq(freq)?;
+        for _ in 0..self.config.settle_blocks {
+            self.sdr.read_sync(&mut self.buf)?;
+        }
+        for _ in 0..self.config.blocks_per_step {
+            self.integrate_block(offset_bins)?;
+        }
+        self.steps += 1;
+        trace!("Dither step {}: offset {} Hz", self.steps, offset);
+        Ok(offset)
+    }
+
+    /// Run `steps` dither steps
+    pub fn integrate(&mut self, steps: usize) -> Result<()> {
+        for _ in 0..steps {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Average power per bin relative to full scale, lowest frequency first, aligned
+    /// to the center frequency. Bins no block has covered yet are 0.
+    pub fn spectrum(&self) -> Vec<f64> {
+        self.sum
+            .iter()
+            .zip(self.frames.iter())
+            .map(|(sum, frames)| match frames {
+                0 => 0.0,
+                frames => sum / (*frames as f64 * self.norm),
+            })
+            .collect()
+    }
+
+    /// Frequency of bin `index` of `spectrum` in Hz
+    pub fn bin_freq(&self, index: usize) -> f64 {
+        self.center as f64 + (index as f64 - (self.config.fft_len / 2) as f64) * self.bin_hz
+    }
+
+    /// Dither steps integrated so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Discard the integration so far
+    pub fn reset(&mut self) {
+        self.sum.iter_mut().for_each(|p| *p = 0.0);
+        self.frames.iter_mut().for_each(|f| *f = 0);
+        self.steps = 0;
+    }
+
+    // Uniform in [-max_bins, max_bins]
+    fn next_offset(&mut self) -> i64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng % (2 * self.max_bins as u64 + 1)) as i64 - self.max_bins
+    }
+
+    fn integrate_block(&mut self, offset_bins: i64) -> Result<()> {
+        let len = self.sdr.read_sync(&mut self.buf)?;
+        let n = self.config.fft_len;
+        let half = (n / 2) as i64;
+        let mut frames = 0;
+        for frame in self.buf[..len].chunks_exact(n * 2) {
+            for (i, iq) in frame.chunks_exact(2).enumerate() {
+                self.re[i] =
+                    (iq[0] as f32 - DEFAULT_DC_OFFSET) / DEFAULT_DC_OFFSET * self.window[i];
+                self.im[i] =
+                    (iq[1] as f32 - DEFAULT_DC_OFFSET) / DEFAULT_DC_OFFSET * self.window[i];
+            }
+            fft(&mut self.re, &mut self.im);
+            for (k, (r, i)) in self.re.iter().zip(self.im.iter()).enumerate() {
+                // FFT bin to position relative to the tuned LO, then back onto the
+                // center frequency; bins shifted past the band edge are dropped
+                let tuned = (k as i64 + half).rem_euclid(n as i64);
+                let target = tuned + offset_bins;
+                if (0..n as i64).contains(&target) {
+                    self.sum[target as usize] += (r * r + i * i) as f64;
+                    self.frames[target as usize] += 1;
+                }
+            }
+            frames += 1;
+        }
+        if frames == 0 {
+            return Err(RtlsdrErr(format!("Short read of {} bytes", len)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use crate::convert::MagnitudeDecimator;
+use crate::error::Result;
+use crate::{RtlSdr, DEFAULT_BUF_LENGTH};
+
+/// Blocking stream of decimated magnitude samples, created by `RtlSdr::magnitude_stream`
+pub struct MagnitudeStream<'a> {
+    sdr: &'a RtlSdr,
+    decimator: MagnitudeDecimator,
+    buf: Vec<u8>,
+}
+
+impl<'a> MagnitudeStream<'a> {
+    pub(crate) fn new(sdr: &'a RtlSdr, decimation: usize) -> Self {
+        MagnitudeStream {
+            sdr,
+            decimator: MagnitudeDecimator::new(decimation),
+            buf: vec![0; DEFAULT_BUF_LENGTH],
+        }
+    }
+
+    /// Center of the raw samples, `DEFAULT_DC_OFFSET` unless changed
+    pub fn set_dc_offset(&mut self, dc_offset: f32) {
+        self.decimator.set_dc_offset(dc_offset);
+    }
+
+    pub fn decimation(&self) -> usize {
+        self.decimator.decimation()
+    }
+
+    /// Read one USB block and append its magnitudes to `out`.
+    /// Returns the number of samples appended.
+    pub fn read_f32(&mut self, out: &mut Vec<f32>) -> Result<usize> {
+        let start = out.len();
+        let len = self.sdr.read_sync(&mut self.buf)?;
+        self.decimator.to_f32(&self.buf[..len], out);
+        Ok(out.len() - start)
+    }
+
+    /// Like `read_f32`, scaled to the full u16 range
+    pub fn read_u16(&mut self, out: &mut Vec<u16>) -> Result<usize> {
+        let start = out.len();
+        let len = self.sdr.read_sync(&mut self.buf)?;
+        self.decimator.to_u16(&self.buf[..len], out);
+        Ok(out.len() - start)
+    }
+}
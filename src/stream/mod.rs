@@ -0,0 +1,45 @@
+//! Sample streams layered on top of `RtlSdr::read_sync`.
+pub mod background;
+#[cfg(any(feature = "async-tokio", feature = "futures-io"))]
+pub mod byte_stream;
+pub(crate) mod channel;
+pub mod dicke;
+pub mod dither;
+pub mod magnitude;
+pub mod multiplex;
+pub mod multirate;
+pub mod open;
+pub mod power;
+pub mod pps;
+pub mod radiometer;
+pub mod reader;
+pub mod resume;
+pub mod runtime;
+pub mod settle;
+#[cfg(feature = "shm")]
+pub mod shm;
+pub mod source;
+pub mod stats;
+pub use background::BackgroundStream;
+#[cfg(any(feature = "async-tokio", feature = "futures-io"))]
+pub use byte_stream::{ByteStream, Bytes};
+pub use dicke::{DickeSwitch, NoiseState};
+pub use dither::{DitheredSpectrum, FreqDitherConfig};
+pub use magnitude::MagnitudeStream;
+pub use multiplex::{Multiplexer, Slot, VirtualReceiver};
+pub use multirate::MultiRateReader;
+pub use open::{open_stream, SampleChunk, SampleStream, SourceSelector, StreamConfig};
+pub use power::{ChannelPower, ChannelPowerConfig, ChannelPowerLogger, PowerFormat};
+pub use pps::{PpsEdge, PpsTagger};
+pub use radiometer::{Radiometer, RadiometerConfig, RadiometerPoint};
+pub use reader::{AdaptiveTransfer, Reader};
+pub use resume::{ResumeEvent, ResumingReader};
+#[cfg(feature = "async-std")]
+pub use runtime::open_stream_async_channel;
+#[cfg(feature = "async-tokio")]
+pub use runtime::open_stream_tokio;
+pub use settle::{SettleConfig, SettleStatus, SettleTracker};
+#[cfg(feature = "shm")]
+pub use shm::{ShmSink, ShmSource};
+pub use source::{SdrReader, StaticConfig, StdinSource, TcpSource};
+pub use stats::StreamStats;
@@ -0,0 +1,121 @@
+//! Time-slicing of one device across several "virtual receivers".
+//!
+//! Each receiver has its own frequency, gain and sample rate. The multiplexer visits the
+//! receivers in round-robin order, retunes, discards the samples captured while the
+//! tuner settles and then records a fixed dwell, so every receiver gets a gapped stream
+//! whose slots are labeled with the receiver and settings they were captured with.
+use core::time::Duration;
+
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::{RtlSdr, TunerGain};
+
+/// Samples discarded after each retune by default
+pub const DEFAULT_SETTLE_SAMPLES: usize = 16384;
+
+/// Settings for one virtual receiver
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VirtualReceiver {
+    pub label: String,
+    pub freq: u32,
+    pub gain: TunerGain,
+    pub rate: u32,
+    /// How long each slot records for
+    pub dwell: Duration,
+}
+
+/// Samples recorded for one receiver during one slot
+#[derive(Debug, Clone)]
+pub struct Slot {
+    /// Index of the receiver, as returned by `Multiplexer::add_receiver`
+    pub receiver: usize,
+    pub label: String,
+    pub freq: u32,
+    pub rate: u32,
+    /// Counts slots across all receivers, so gaps between a receiver's slots can be sized
+    pub sequence: u64,
+    /// Raw interleaved u8 I/Q
+    pub data: Vec<u8>,
+}
+
+/// Round-robin scheduler sharing one `RtlSdr` between virtual receivers
+pub struct Multiplexer<'a> {
+    sdr: &'a mut RtlSdr,
+    receivers: Vec<VirtualReceiver>,
+    next: usize,
+    sequence: u64,
+    settle_samples: usize,
+}
+
+impl<'a> Multiplexer<'a> {
+    pub fn new(sdr: &'a mut RtlSdr) -> Self {
+        Multiplexer {
+            sdr,
+            receivers: Vec::new(),
+            next: 0,
+            sequence: 0,
+            settle_samples: DEFAULT_SETTLE_SAMPLES,
+        }
+    }
+
+    /// Number of samples discarded after each retune while the tuner PLL settles
+    pub fn set_settle_samples(&mut self, samples: usize) {
+        self.settle_samples = samples;
+    }
+
+    /// Register a receiver, returning its index
+    pub fn add_receiver(&mut self, receiver: VirtualReceiver) -> usize {
+        self.receivers.push(receiver);
+        self.receivers.len() - 1
+    }
+
+    pub fn receivers(&self) -> &[VirtualReceiver] {
+        &self.receivers
+    }
+
+    /// Retune to the next receiver and record one slot for it
+    pub fn next_slot(&mut self) -> Result<Slot> {
+        if self.receivers.is_empty() {
+            return Err(RtlsdrErr("No virtual receivers registered".to_string()));
+        }
+        let receiver = self.next;
+        self.next = (self.next + 1) % self.receivers.len();
+        let rx = self.receivers[receiver].clone();
+
+        if self.sdr.get_sample_rate() != rx.rate {
+            self.sdr.set_sample_rate(rx.rate)?;
+        }
+        self.sdr.set_center_freq(rx.freq)?;
+        self.sdr.set_tuner_gain(rx.gain)?;
+        self.sdr.reset_buffer()?;
+
+        // Reads must be a multiple of 512 bytes
+        let settle = (self.settle_samples * 2).next_multiple_of(512);
+        if settle > 0 {
+            let mut discard = vec![0; settle];
+            self.sdr.read_sync(&mut discard)?;
+        }
+        let samples = (rx.rate as f64 * rx.dwell.as_secs_f64()) as usize;
+        let mut data = vec![0; (samples * 2).next_multiple_of(512)];
+        let mut filled = 0;
+        while filled < data.len() {
+            let len = self.sdr.read_sync(&mut data[filled..])?;
+            if len == 0 {
+                break;
+            }
+            filled += len;
+        }
+        data.truncate(filled.min(samples * 2));
+
+        let sequence = self.sequence;
+        self.sequence += 1;
+        Ok(Slot {
+            receiver,
+            label: rx.label,
+            freq: rx.freq,
+            rate: self.sdr.get_sample_rate(),
+            sequence,
+            data,
+        })
+    }
+}
@@ -0,0 +1,119 @@
+//! Several decimated views ("taps") of one stream at once, e.g. full rate for a Mode S
+//! decoder next to a 48 kHz tap for audio monitoring.
+//!
+//! Raw samples are converted to f32 once per block for all taps. A tap whose
+//! decimation is a multiple of an existing tap's is fed from that tap's output rather
+//! than from the full-rate samples, so adding taps at 10 and then 50 filters the 50
+//! only by a further 5.
+use crate::convert::u8_to_f32;
+use crate::dsp::decimate::FirDecimator;
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::{RtlSdr, DEFAULT_BUF_LENGTH};
+
+struct Tap {
+    decimation: usize,
+    // Tap whose output feeds this one, or `None` for the full-rate samples
+    source: Option<usize>,
+    decimator: FirDecimator,
+    out: Vec<f32>,
+}
+
+/// Blocking reader delivering each block at several sample rates, created by
+/// `RtlSdr::multi_rate_reader`
+pub struct MultiRateReader<'a> {
+    sdr: &'a RtlSdr,
+    taps: Vec<Tap>,
+    // Tap indices by ascending decimation, so sources are filled first
+    order: Vec<usize>,
+    buf: Vec<u8>,
+    full: Vec<f32>,
+}
+
+impl<'a> MultiRateReader<'a> {
+    pub(crate) fn new(sdr: &'a RtlSdr) -> Self {
+        MultiRateReader {
+            sdr,
+            taps: Vec::new(),
+            order: Vec::new(),
+            buf: vec![0; DEFAULT_BUF_LENGTH],
+            full: Vec::new(),
+        }
+    }
+
+    /// Register a tap at the sample rate divided by `decimation` (1 for full rate),
+    /// returning its index
+    pub fn add_tap(&mut self, decimation: usize) -> Result<usize> {
+        if decimation == 0 {
+            return Err(RtlsdrErr("Tap decimation must be at least 1".to_string()));
+        }
+        // Coarsest existing tap this one can be derived from
+        let source = self
+            .taps
+            .iter()
+            .enumerate()
+            .filter(|(_, tap)| {
+                tap.decimation < decimation && decimation.is_multiple_of(tap.decimation)
+            })
+            .max_by_key(|(_, tap)| tap.decimation)
+            .map(|(index, _)| index);
+        let factor = match source {
+            Some(source) => decimation / self.taps[source].decimation,
+            None => decimation,
+        };
+        self.taps.push(Tap {
+            decimation,
+            source,
+            decimator: FirDecimator::new(factor),
+            out: Vec::new(),
+        });
+        let index = self.taps.len() - 1;
+        self.order.push(index);
+        self.order.sort_by_key(|i| self.taps[*i].decimation);
+        debug!(
+            "Tap {}: decimation {}, fed from {:?} by a factor of {}",
+            index, decimation, source, factor
+        );
+        Ok(index)
+    }
+
+    /// Sample rate of tap `index` in Hz
+    pub fn tap_rate(&self, index: usize) -> f64 {
+        self.sdr.get_sample_rate() as f64 / self.taps[index].decimation as f64
+    }
+
+    /// Read one USB block and run it through all taps. Returns the number of bytes
+    /// read.
+    pub fn read(&mut self) -> Result<usize> {
+        if self.taps.is_empty() {
+            return Err(RtlsdrErr("No taps registered".to_string()));
+        }
+        let len = self.sdr.read_sync(&mut self.buf)?;
+        self.full.clear();
+        u8_to_f32(&self.buf[..len], &mut self.full);
+        for index in self.order.iter().copied() {
+            let mut out = std::mem::take(&mut self.taps[index].out);
+            out.clear();
+            match self.taps[index].source {
+                Some(source) => {
+                    // Sources are registered before the taps they feed
+                    let (head, tail) = self.taps.split_at_mut(index);
+                    tail[0].decimator.process(&head[source].out, &mut out);
+                }
+                None => self.taps[index].decimator.process(&self.full, &mut out),
+            }
+            self.taps[index].out = out;
+        }
+        Ok(len)
+    }
+
+    /// Interleaved I/Q of tap `index` from the last `read`, full scale ~1.0
+    pub fn tap(&self, index: usize) -> &[f32] {
+        &self.taps[index].out
+    }
+
+    /// Drop samples buffered in the filters, e.g. after a retune
+    pub fn reset(&mut self) {
+        self.taps.iter_mut().for_each(|tap| tap.decimator.reset());
+    }
+}
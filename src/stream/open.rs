@@ -0,0 +1,135 @@
+//! One entry point for streaming from any kind of source, for applications that pick
+//! the source at runtime.
+use std::path::PathBuf;
+use std::pin::Pin;
+
+use futures_core::Stream;
+
+use super::channel;
+use super::runtime::ChunkSink;
+use super::source::{SdrReader, StaticConfig, StdinSource, TcpSource};
+use crate::error::{io_err, Result};
+use crate::{platform, RtlSdr, DEFAULT_BUF_LENGTH};
+
+// Chunks read ahead of the consumer before the reader thread blocks
+const CHUNK_QUEUE_LEN: usize = 4;
+
+/// Where samples come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceSelector {
+    /// USB device by index
+    Index(usize),
+    /// USB device by serial number
+    Serial(String),
+    /// `rtl_tcp` server, e.g. "192.168.1.10:1234"
+    Tcp(String),
+    /// Raw u8 I/Q recording
+    File(PathBuf),
+    /// Raw u8 I/Q piped in on stdin
+    Stdin,
+}
+
+/// Settings applied to the source (or assumed, for files and stdin)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamConfig {
+    pub sample_rate: u32,
+    pub center_freq: u32,
+    /// Bytes per chunk
+    pub chunk_len: usize,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            sample_rate: 2_048_000,
+            center_freq: 100_000_000,
+            chunk_len: DEFAULT_BUF_LENGTH,
+        }
+    }
+}
+
+/// A block of raw interleaved u8 I/Q
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampleChunk {
+    pub data: Vec<u8>,
+    /// Index of the chunk's first sample since the stream started
+    pub first_sample: u64,
+    pub sample_rate: u32,
+    pub center_freq: u32,
+}
+
+pub type SampleStream = Pin<Box<dyn Stream<Item = Result<SampleChunk>> + Send>>;
+
+/// Open `selector`, apply `config`, and stream its samples. Reads run on a dedicated
+/// thread; dropping the stream stops them and releases the source. The stream ends
+/// when a file or pipe does, and after the first error.
+///
+/// The stream only relies on the polling task's waker, so it can be consumed on any
+/// executor; see `stream::runtime` for runtime-specific receivers.
+pub fn open_stream(selector: SourceSelector, config: StreamConfig) -> Result<SampleStream> {
+    let (tx, rx) = channel::channel(CHUNK_QUEUE_LEN);
+    start_stream(selector, config, tx)?;
+    Ok(Box::pin(rx))
+}
+
+// Open the source and start the reader thread feeding `sink`
+pub(crate) fn start_stream<S: ChunkSink>(
+    selector: SourceSelector,
+    config: StreamConfig,
+    sink: S,
+) -> Result<()> {
+    let fixed = StaticConfig {
+        sample_rate: config.sample_rate,
+        center_freq: config.center_freq,
+    };
+    let source: Box<dyn SdrReader + Send> = match selector {
+        SourceSelector::Index(index) => {
+            Box::new(open_device(RtlSdr::open_by_index(index)?, config)?)
+        }
+        SourceSelector::Serial(serial) => {
+            Box::new(open_device(RtlSdr::open_by_serial(&serial)?, config)?)
+        }
+        SourceSelector::Tcp(addr) => Box::new(TcpSource::connect(addr.as_str(), fixed)?),
+        SourceSelector::File(path) => {
+            let file = std::fs::File::open(&path).map_err(|e| io_err(&path, e))?;
+            Box::new(StdinSource::from_reader(file, fixed))
+        }
+        SourceSelector::Stdin => Box::new(StdinSource::new(fixed)),
+    };
+    let chunk_len = config.chunk_len.max(2) & !1;
+    platform::spawn("rtlsdr-stream", move || read_loop(source, chunk_len, sink))
+}
+
+fn open_device(mut sdr: RtlSdr, config: StreamConfig) -> Result<RtlSdr> {
+    sdr.set_sample_rate(config.sample_rate)?;
+    sdr.set_center_freq(config.center_freq)?;
+    sdr.reset_buffer()?;
+    Ok(sdr)
+}
+
+fn read_loop<S: ChunkSink>(mut source: Box<dyn SdrReader + Send>, chunk_len: usize, sink: S) {
+    let mut first_sample = 0;
+    loop {
+        let mut data = vec![0; chunk_len];
+        let chunk = match source.read_iq(&mut data) {
+            Ok(0) => return,
+            Ok(len) => {
+                data.truncate(len);
+                let chunk = SampleChunk {
+                    data,
+                    first_sample,
+                    sample_rate: source.sample_rate(),
+                    center_freq: source.center_freq(),
+                };
+                first_sample += len as u64 / 2;
+                Ok(chunk)
+            }
+            Err(e) => Err(e),
+        };
+        let failed = chunk.is_err();
+        // Sending fails once the stream has been dropped
+        if !sink.send_chunk(chunk) || failed {
+            return;
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Channel power logging for interference monitoring: average and peak power of a
+//! list of channels, aggregated over fixed windows and written as CSV or JSON lines.
+//!
+//! Channels spanning up to `SINGLE_TUNE_SPAN` of the sample rate are measured
+//! together from one tuning, splitting the band into channels with an FFT. Wider sets
+//! are visited in turn, retuning for every channel. Either way a channel sits clear of
+//! the DC spike.
+use std::io::Write;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::convert::DEFAULT_DC_OFFSET;
+use crate::dsp::fft::{fft, hann};
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::{platform, RtlSdr, DEFAULT_BUF_LENGTH};
+
+/// Largest span of channels, as a fraction of the sample rate, measured without
+/// retuning
+pub const SINGLE_TUNE_SPAN: f64 = 0.35;
+
+// Distance of the nearest channel edge from DC, as a fraction of the sample rate
+const DC_CLEARANCE: f64 = 0.05;
+
+/// Settings for `RtlSdr::channel_power_logger`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelPowerConfig {
+    /// Channel center frequencies in Hz
+    pub channels: Vec<u32>,
+    /// Bandwidth measured around each center, in Hz
+    pub channel_width: u32,
+    /// Aggregation window; every channel gets one record per window
+    pub window: Duration,
+    /// FFT length (a power of two); sets the frequency resolution of the channel
+    /// edges
+    pub fft_len: usize,
+    /// Blocks discarded after each retune
+    pub settle_blocks: usize,
+}
+
+impl Default for ChannelPowerConfig {
+    fn default() -> Self {
+        ChannelPowerConfig {
+            channels: Vec::new(),
+            channel_width: 12_500,
+            window: Duration::from_secs(60),
+            fft_len: 1024,
+            settle_blocks: 1,
+        }
+    }
+}
+
+/// Output format of a `ChannelPowerLogger`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PowerFormat {
+    /// One row per channel and window, after a header row
+    #[default]
+    Csv,
+    /// One JSON object per channel and window
+    JsonLines,
+}
+
+/// Power of one channel over one window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelPower {
+    /// Seconds since the Unix epoch at the end of the window
+    pub timestamp: f64,
+    /// Channel center frequency in Hz
+    pub freq: u32,
+    /// Mean power in dB relative to full scale
+    pub avg_dbfs: f32,
+    /// Highest single measurement in the window, in dBFS
+    pub max_dbfs: f32,
+    /// Measurements aggregated, one per block read on the channel
+    pub measurements: u32,
+}
+
+impl ChannelPower {
+    fn write_to<W: Write>(&self, format: PowerFormat, out: &mut W) -> std::io::Result<()> {
+        match format {
+            PowerFormat::Csv => writeln!(
+                out,
+                "{:.3},{},{:.2},{:.2},{}",
+                self.timestamp, self.freq, self.avg_dbfs, self.max_dbfs, self.measurements
+            ),
+            PowerFormat::JsonLines => writeln!(
+                out,
+                "{{\"timestamp\":{:.3},\"freq\":{},\"avg_dbfs\":{:.2},\"max_dbfs\":{:.2},\"measurements\":{}}}",
+                self.timestamp, self.freq, self.avg_dbfs, self.max_dbfs, self.measurements
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Aggregate {
+    sum: f64,
+    max: f64,
+    count: u32,
+}
+
+/// Logs channel power window by window, created by `RtlSdr::channel_power_logger`
+pub struct ChannelPowerLogger<'a, W: Write> {
+    sdr: &'a mut RtlSdr,
+    config: ChannelPowerConfig,
+    format: PowerFormat,
+    out: W,
+    // Tuning shared by all channels, or `None` to retune for each
+    center: Option<u32>,
+    // Settle blocks still to discard after the shared tuning
+    settle: usize,
+    window: Vec<f32>,
+    // Sum of |X|^2 over the window of one full-scale sample: N * sum(w^2)
+    norm: f64,
+    re: Vec<f32>,
+    im: Vec<f32>,
+    spectrum: Vec<f64>,
+    buf: Vec<u8>,
+}
+
+impl<'a, W: Write> ChannelPowerLogger<'a, W> {
+    pub(crate) fn new(
+        sdr: &'a mut RtlSdr,
+        config: ChannelPowerConfig,
+        format: PowerFormat,
+        mut out: W,
+    ) -> Result<Self> {
+        let rate = sdr.get_sample_rate();
+        if rate == 0 {
+            return Err(RtlsdrErr(
+                "Set a sample rate before logging channel power".to_string(),
+            ));
+        }
+        if config.channels.is_empty() {
+            return Err(RtlsdrErr("No channels to log".to_string()));
+        }
+        if !config.fft_len.is_power_of_two() || config.fft_len * 2 > DEFAULT_BUF_LENGTH {
+            return Err(RtlsdrErr(format!(
+                "FFT length {} is not a power of two up to {}",
+                config.fft_len,
+                DEFAULT_BUF_LENGTH / 2
+            )));
+        }
+        if config.channel_width == 0 || config.channel_width > rate / 2 {
+            return Err(RtlsdrErr(format!(
+                "Channel width {} Hz must be nonzero and at most half the sample rate",
+                config.channel_width
+            )));
+        }
+        let half_width = config.channel_width / 2;
+        let lowest = config
+            .channels
+            .iter()
+            .min()
+            .unwrap()
+            .saturating_sub(half_width);
+        let highest = config
+            .channels
+            .iter()
+            .max()
+            .unwrap()
+            .saturating_add(half_width);
+        let center = if (highest - lowest) as f64 <= SINGLE_TUNE_SPAN * rate as f64 {
+            let center = lowest.saturating_sub((DC_CLEARANCE * rate as f64) as u32);
+            sdr.set_center_freq(center)?;
+            Some(center)
+        } else {
+            None
+        };
+        if format == PowerFormat::Csv {
+            writeln!(out, "timestamp,freq,avg_dbfs,max_dbfs,measurements").map_err(write_err)?;
+        }
+        let window = hann(config.fft_len);
+        let norm = config.fft_len as f64 * window.iter().map(|w| (w * w) as f64).sum::<f64>();
+        Ok(ChannelPowerLogger {
+            sdr,
+            format,
+            out,
+            center,
+            settle: config.settle_blocks,
+            norm,
+            re: vec![0.0; config.fft_len],
+            im: vec![0.0; config.fft_len],
+            spectrum: vec![0.0; config.fft_len],
+            window,
+            config,
+            buf: vec![0; DEFAULT_BUF_LENGTH],
+        })
+    }
+
+    /// Whether all channels are measured from a single tuning
+    pub fn is_single_tune(&self) -> bool {
+        self.center.is_some()
+    }
+
+    /// Measure for one window, write a record per channel and return them
+    pub fn next_window(&mut self) -> Result<Vec<ChannelPower>> {
+        let end = platform::now() + self.config.window;
+        let mut aggregates = vec![Aggregate::default(); self.config.channels.len()];
+        while self.center.is_some() && self.settle > 0 {
+            self.sdr.read_sync(&mut self.buf)?;
+            self.settle -= 1;
+        }
+        // At least one measurement per channel, however short the window
+        loop {
+            match self.center {
+                Some(center) => {
+                    self.read_spectrum()?;
+                    for (ch, agg) in aggregates.iter_mut().enumerate() {
+                        let power = self.channel_power(self.config.channels[ch], center);
+                        add(agg, power);
+                    }
+                }
+                None => {
+                    for (ch, agg) in aggregates.iter_mut().enumerate() {
+                        // Put the channel a quarter of the sample rate above DC
+                        let freq = self.config.channels[ch];
+                        let center = freq.saturating_sub(self.sdr.get_sample_rate() / 4);
+                        self.sdr.set_center_freq(center)?;
+                        for _ in 0..self.config.settle_blocks {
+                            self.sdr.read_sync(&mut self.buf)?;
+                        }
+                        self.read_spectrum()?;
+                        let power = self.channel_power(freq, center);
+                        add(agg, power);
+                    }
+                }
+            }
+            if platform::now() >= end {
+                break;
+            }
+        }
+        let timestamp = platform::wall_clock()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let records: Vec<ChannelPower> = self
+            .config
+            .channels
+            .iter()
+            .zip(aggregates.iter())
+            .map(|(freq, agg)| ChannelPower {
+                timestamp,
+                freq: *freq,
+                avg_dbfs: to_db(agg.sum / agg.count as f64),
+                max_dbfs: to_db(agg.max),
+                measurements: agg.count,
+            })
+            .collect();
+        for record in records.iter() {
+            record
+                .write_to(self.format, &mut self.out)
+                .map_err(write_err)?;
+        }
+        self.out.flush().map_err(write_err)?;
+        Ok(records)
+    }
+
+    /// Stop logging and hand back the writer
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    // Average power spectrum of one block, in `spectrum`
+    fn read_spectrum(&mut self) -> Result<()> {
+        let len = self.sdr.read_sync(&mut self.buf)?;
+        let n = self.config.fft_len;
+        self.spectrum.iter_mut().for_each(|p| *p = 0.0);
+        let mut frames = 0;
+        for frame in self.buf[..len].chunks_exact(n * 2) {
+            for (i, iq) in frame.chunks_exact(2).enumerate() {
+                self.re[i] =
+                    (iq[0] as f32 - DEFAULT_DC_OFFSET) / DEFAULT_DC_OFFSET * self.window[i];
+                self.im[i] =
+                    (iq[1] as f32 - DEFAULT_DC_OFFSET) / DEFAULT_DC_OFFSET * self.window[i];
+            }
+            fft(&mut self.re, &mut self.im);
+            for (p, (r, i)) in self
+                .spectrum
+                .iter_mut()
+                .zip(self.re.iter().zip(self.im.iter()))
+            {
+                *p += (r * r + i * i) as f64;
+            }
+            frames += 1;
+        }
+        if frames == 0 {
+            return Err(RtlsdrErr(format!("Short read of {} bytes", len)));
+        }
+        self.spectrum
+            .iter_mut()
+            .for_each(|p| *p /= frames as f64 * self.norm);
+        Ok(())
+    }
+
+    // Power in the bins covering `freq` ± half the channel width, tuned to `center`
+    fn channel_power(&self, freq: u32, center: u32) -> f64 {
+        let n = self.config.fft_len as i64;
+        let bin_hz = self.sdr.get_sample_rate() as f64 / n as f64;
+        let offset = freq as f64 - center as f64;
+        let half_width = self.config.channel_width as f64 / 2.0;
+        let first = ((offset - half_width) / bin_hz).round() as i64;
+        let last = (((offset + half_width) / bin_hz).round() as i64).max(first);
+        (first..=last)
+            .map(|k| self.spectrum[k.rem_euclid(n) as usize])
+            .sum()
+    }
+}
+
+impl<W: Write> Iterator for ChannelPowerLogger<'_, W> {
+    type Item = Result<Vec<ChannelPower>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_window())
+    }
+}
+
+fn add(agg: &mut Aggregate, power: f64) {
+    agg.sum += power;
+    agg.max = agg.max.max(power);
+    agg.count += 1;
+}
+
+fn to_db(power: f64) -> f32 {
+    (10.0 * power.max(1e-20).log10()) as f32
+}
+
+fn write_err(e: std::io::Error) -> crate::error::RtlsdrError {
+    RtlsdrErr(format!("Power log write failed: {}", e))
+}
@@ -0,0 +1,111 @@
+use std::time::UNIX_EPOCH;
+
+use crate::error::Result;
+use crate::platform;
+use crate::{RtlSdr, DEFAULT_BUF_LENGTH};
+
+/// A rising PPS edge, located to within one block of samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PpsEdge {
+    /// UTC second the pulse marks, from the host clock rounded to the nearest second.
+    /// Needs a host clock within half a second of UTC, e.g. NTP synced.
+    pub utc_second: u64,
+    /// Sample indices (since the tagger was created) the edge falls between
+    pub earliest_sample: u64,
+    pub latest_sample: u64,
+}
+
+impl PpsEdge {
+    /// Best estimate of the sample the edge arrived at
+    pub fn sample(&self) -> u64 {
+        self.earliest_sample + (self.latest_sample - self.earliest_sample) / 2
+    }
+
+    /// Half the width of the window the edge is known to lie in, in samples
+    pub fn uncertainty(&self) -> u64 {
+        (self.latest_sample - self.earliest_sample) / 2
+    }
+}
+
+/// Tags blocks with the rising edges of a GPS PPS signal wired to a GPIO pin, created
+/// by `RtlSdr::pps_tagger`. For TDOA and other experiments that need absolute timing.
+///
+/// The pin is polled after every USB transfer, so an edge is placed within the block
+/// read when it was first seen. Smaller blocks (`set_block_len`) narrow that window at
+/// the cost of more control transfers. USB latency shifts every edge by about the
+/// same amount and cancels out between receivers built the same way.
+pub struct PpsTagger<'a> {
+    sdr: &'a RtlSdr,
+    pin: u8,
+    level: bool,
+    next_sample: u64,
+    last_edge: Option<PpsEdge>,
+    buf: Vec<u8>,
+}
+
+impl<'a> PpsTagger<'a> {
+    pub(crate) fn new(sdr: &'a RtlSdr, pin: u8) -> Result<Self> {
+        sdr.sdr.set_gpio_input(pin)?;
+        let level = sdr.sdr.get_gpio_input(pin)?;
+        Ok(PpsTagger {
+            sdr,
+            pin,
+            level,
+            next_sample: 0,
+            last_edge: None,
+            buf: vec![0; DEFAULT_BUF_LENGTH],
+        })
+    }
+
+    /// Bytes per block, rounded up to a multiple of 512
+    pub fn set_block_len(&mut self, len: usize) {
+        self.buf = vec![0; len.max(1).next_multiple_of(512)];
+    }
+
+    /// Read the next block, with the PPS edge seen while it was captured, if any.
+    /// The returned slice is valid until the next call.
+    pub fn read(&mut self) -> Result<(&[u8], Option<PpsEdge>)> {
+        let len = self.sdr.read_sync(&mut self.buf)?;
+        let level = self.sdr.sdr.get_gpio_input(self.pin)?;
+        let first = self.next_sample;
+        self.next_sample += len as u64 / 2;
+        let edge = if level && !self.level {
+            let now = platform::wall_clock()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs_f64())
+                .unwrap_or(0.0);
+            let edge = PpsEdge {
+                utc_second: now.round() as u64,
+                earliest_sample: first,
+                latest_sample: self.next_sample,
+            };
+            self.last_edge = Some(edge);
+            Some(edge)
+        } else {
+            None
+        };
+        self.level = level;
+        Ok((&self.buf[..len], edge))
+    }
+
+    /// Index of the next sample `read` returns
+    pub fn next_sample(&self) -> u64 {
+        self.next_sample
+    }
+
+    pub fn last_edge(&self) -> Option<PpsEdge> {
+        self.last_edge
+    }
+
+    /// UTC time of `sample` in seconds since the Unix epoch, extrapolated from the last
+    /// PPS edge at the current sample rate. `None` until the first edge.
+    pub fn sample_time(&self, sample: u64) -> Option<f64> {
+        let edge = self.last_edge?;
+        let rate = self.sdr.get_sample_rate();
+        if rate == 0 {
+            return None;
+        }
+        let offset = sample as f64 - edge.sample() as f64;
+        Some(edge.utc_second as f64 + offset / rate as f64)
+    }
+}
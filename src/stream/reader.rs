@@ -0,0 +1,407 @@
+use core::time::Duration;
+
+use super::StreamStats;
+use crate::detect::OverloadDetector;
+use crate::error::Result;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::{platform, RtlSdr, DEFAULT_BUF_LENGTH};
+
+// Bulk reads must be a multiple of the USB packet size
+const TRANSFER_GRANULARITY: usize = 512;
+/// Raw values collected per `StreamStats::sample_histogram` update by default
+pub const DEFAULT_HISTOGRAM_WINDOW: u64 = 1 << 22;
+
+/// Limits for `Reader::set_adaptive`.
+///
+/// The reader compares the time the consumer spends between transfers with the time
+/// a transfer's samples cover. Once the consumer takes longer than `grow_above` of
+/// that, the device FIFO is about to overflow and the transfer length is doubled,
+/// so fewer, larger transfers cut the per-transfer overhead. After `settle`
+/// transfers in a row below `shrink_below` it is halved again to bring latency back
+/// down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveTransfer {
+    /// Smallest transfer length, in bytes, rounded up to a multiple of 512
+    pub min_len: usize,
+    /// Largest transfer length, in bytes, rounded down to a multiple of 512 (but not
+    /// below `min_len`)
+    pub max_len: usize,
+    /// Load (consumer time / transfer time) above which transfers grow
+    pub grow_above: f64,
+    /// Load below which transfers may shrink
+    pub shrink_below: f64,
+    /// Consecutive transfers below `shrink_below` before shrinking
+    pub settle: u32,
+}
+
+impl Default for AdaptiveTransfer {
+    fn default() -> Self {
+        AdaptiveTransfer {
+            min_len: 16 * 1024,
+            max_len: 4 * DEFAULT_BUF_LENGTH,
+            grow_above: 0.8,
+            shrink_below: 0.3,
+            settle: 64,
+        }
+    }
+}
+
+impl AdaptiveTransfer {
+    // The limits in whole USB packets
+    fn aligned(self) -> Self {
+        let min_len = self.min_len.max(1).next_multiple_of(TRANSFER_GRANULARITY);
+        let max_len = (self.max_len - self.max_len % TRANSFER_GRANULARITY).max(min_len);
+        AdaptiveTransfer {
+            min_len,
+            max_len,
+            ..self
+        }
+    }
+
+    // Length to grow a `len` byte transfer to, within aligned limits
+    fn grown(&self, len: usize) -> usize {
+        (len * 2).min(self.max_len)
+    }
+
+    // Length to shrink a `len` byte transfer to, within aligned limits
+    fn shrunk(&self, len: usize) -> usize {
+        (len / 2)
+            .next_multiple_of(TRANSFER_GRANULARITY)
+            .max(self.min_len)
+    }
+}
+
+/// Blocking sample reader that can be paused and resumed, created by `RtlSdr::reader`.
+///
+/// While paused no bulk transfers are made, but the device stays open and configured,
+/// which is much cheaper than closing and reopening for duty-cycled captures.
+pub struct Reader<'a> {
+    sdr: &'a mut RtlSdr,
+    paused: bool,
+    standby: bool,
+    // Last USB transfer; bytes in `pending` have not been handed out yet
+    transfer: Vec<u8>,
+    pending: core::ops::Range<usize>,
+    chunk_samples: usize,
+    bytes_read: u64,
+    overload: OverloadDetector,
+    on_overload: Option<Box<dyn FnMut(u64) + Send + 'a>>,
+    histogram: [u64; 256],
+    histogram_count: u64,
+    histogram_window: u64,
+    last_histogram: Vec<u64>,
+    adaptive: Option<AdaptiveTransfer>,
+    // When the previous transfer returned, to time the consumer
+    last_transfer: Option<Duration>,
+    idle_transfers: u32,
+    overruns: u64,
+    resizes: u64,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(sdr: &'a mut RtlSdr) -> Result<Self> {
+        sdr.reset_buffer()?;
+        Ok(Reader {
+            sdr,
+            paused: false,
+            standby: false,
+            transfer: vec![0; DEFAULT_BUF_LENGTH],
+            pending: 0..0,
+            chunk_samples: 0,
+            bytes_read: 0,
+            overload: OverloadDetector::default(),
+            on_overload: None,
+            histogram: [0; 256],
+            histogram_count: 0,
+            histogram_window: DEFAULT_HISTOGRAM_WINDOW,
+            last_histogram: Vec::new(),
+            adaptive: None,
+            last_transfer: None,
+            idle_transfers: 0,
+            overruns: 0,
+            resizes: 0,
+        })
+    }
+
+    /// Set the size of the USB transfers used by `read_chunk`, rounded up to a multiple
+    /// of 512 bytes. Buffered samples are kept.
+    pub fn set_transfer_len(&mut self, len: usize) {
+        let len = len.max(1).next_multiple_of(TRANSFER_GRANULARITY);
+        let pending = self.transfer[self.pending.clone()].to_vec();
+        self.transfer = vec![0; len.max(pending.len())];
+        self.transfer[..pending.len()].copy_from_slice(&pending);
+        self.pending = 0..pending.len();
+    }
+
+    pub fn transfer_len(&self) -> usize {
+        self.transfer.len()
+    }
+
+    /// Let `read_chunk` resize its transfers between `limits.min_len` and
+    /// `limits.max_len` to keep up with a slow consumer, trading latency for CPU
+    /// headroom. `None` keeps the transfer length fixed at its current value.
+    pub fn set_adaptive(&mut self, limits: Option<AdaptiveTransfer>) {
+        let limits = limits.map(AdaptiveTransfer::aligned);
+        self.adaptive = limits;
+        self.idle_transfers = 0;
+        if let Some(limits) = limits {
+            let len = self.transfer.len().clamp(limits.min_len, limits.max_len);
+            if len != self.transfer.len() {
+                self.set_transfer_len(len);
+            }
+        }
+    }
+
+    /// Number of I/Q samples covering `duration` at the current sample rate
+    pub fn samples_for(&self, duration: Duration) -> usize {
+        (self.sdr.get_sample_rate() as f64 * duration.as_secs_f64()).round() as usize
+    }
+
+    /// Read raw interleaved u8 I/Q into `buf`, see `RtlSdr::read_sync`.
+    /// Samples buffered by `read_chunk` are returned first.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.paused {
+            return Err(RtlsdrErr("Reader is paused".to_string()));
+        }
+        if !self.pending.is_empty() {
+            let len = buf.len().min(self.pending.len());
+            let start = self.pending.start;
+            buf[..len].copy_from_slice(&self.transfer[start..start + len]);
+            self.pending.start += len;
+            return Ok(len);
+        }
+        self.track_load(buf.len());
+        let len = self.sdr.read_sync(buf)?;
+        self.last_transfer = Some(platform::now());
+        self.account(&buf[..len]);
+        Ok(len)
+    }
+
+    /// Read exactly `samples` I/Q samples (`2 * samples` bytes), appending them to `out`.
+    /// USB transfers of `transfer_len` bytes are made as needed and leftover samples are
+    /// kept for the next chunk, so chunk boundaries don't depend on the transfer size.
+    pub fn read_chunk(&mut self, samples: usize, out: &mut Vec<u8>) -> Result<()> {
+        if self.paused {
+            return Err(RtlsdrErr("Reader is paused".to_string()));
+        }
+        self.chunk_samples = samples;
+        let mut needed = samples * 2;
+        out.reserve(needed);
+        while needed > 0 {
+            if self.pending.is_empty() {
+                if let Some(load) = self.track_load(self.transfer.len()) {
+                    self.adapt(load);
+                }
+                let len = self.sdr.read_sync(&mut self.transfer)?;
+                self.last_transfer = Some(platform::now());
+                let transfer = std::mem::take(&mut self.transfer);
+                self.account(&transfer[..len]);
+                self.transfer = transfer;
+                if len == 0 {
+                    return Err(RtlsdrErr("Device returned no samples".to_string()));
+                }
+                self.pending = 0..len;
+            }
+            let len = needed.min(self.pending.len());
+            let start = self.pending.start;
+            out.extend_from_slice(&self.transfer[start..start + len]);
+            self.pending.start += len;
+            needed -= len;
+        }
+        Ok(())
+    }
+
+    /// Stop reading. With `tuner_standby` the tuner is also powered down to save power;
+    /// it is re-initialized with the current settings on `resume`.
+    pub fn pause(&mut self, tuner_standby: bool) -> Result<()> {
+        if self.paused {
+            return Ok(());
+        }
+        if tuner_standby {
+            self.sdr.sdr.tuner_standby()?;
+        }
+        self.paused = true;
+        self.standby = tuner_standby;
+        Ok(())
+    }
+
+    /// Continue reading after `pause`. Samples buffered by the device while paused are
+    /// discarded.
+    pub fn resume(&mut self) -> Result<()> {
+        if !self.paused {
+            return Ok(());
+        }
+        if self.standby {
+            self.sdr.sdr.tuner_wakeup()?;
+            self.standby = false;
+        }
+        self.sdr.reset_buffer()?;
+        self.pending = 0..0;
+        self.last_transfer = None;
+        self.paused = false;
+        Ok(())
+    }
+
+    /// Call `callback` with the total overload count whenever new ADC overloads are seen
+    pub fn on_overload<F: FnMut(u64) + Send + 'a>(&mut self, callback: F) {
+        self.on_overload = Some(Box::new(callback));
+    }
+
+    /// Count consecutive clipped values as an overload from `min_run` on. Resets the
+    /// overload counters.
+    pub fn set_overload_run(&mut self, min_run: usize) {
+        self.overload = OverloadDetector::new(min_run);
+    }
+
+    /// Number of raw values collected before the sample histogram is updated
+    pub fn set_histogram_window(&mut self, values: u64) {
+        self.histogram_window = values.max(1);
+    }
+
+    // Time the consumer took since the last transfer against the time `len` bytes
+    // cover, counting an overrun if the device FIFO must have overflowed
+    fn track_load(&mut self, len: usize) -> Option<f64> {
+        let rate = self.sdr.get_sample_rate();
+        let (Some(last), true) = (self.last_transfer, rate > 0) else {
+            return None;
+        };
+        let away = (platform::now() - last).as_secs_f64();
+        let span = len as f64 / 2.0 / rate as f64;
+        if away > span {
+            self.overruns += 1;
+        }
+        Some(away / span)
+    }
+
+    // Resize the transfer for the load measured by `track_load`
+    fn adapt(&mut self, load: f64) {
+        let Some(limits) = self.adaptive else {
+            return;
+        };
+        let len = self.transfer.len();
+        if load > limits.grow_above {
+            self.idle_transfers = 0;
+            if len < limits.max_len {
+                let new_len = limits.grown(len);
+                debug!(
+                    "Consumer load {:.2}, growing transfers to {} bytes",
+                    load, new_len
+                );
+                self.resize(new_len);
+            }
+        } else if load < limits.shrink_below {
+            self.idle_transfers += 1;
+            if self.idle_transfers >= limits.settle && len > limits.min_len {
+                let new_len = limits.shrunk(len);
+                debug!(
+                    "Consumer load {:.2}, shrinking transfers to {} bytes",
+                    load, new_len
+                );
+                self.resize(new_len);
+            }
+        } else {
+            self.idle_transfers = 0;
+        }
+    }
+
+    fn resize(&mut self, len: usize) {
+        self.set_transfer_len(len);
+        self.idle_transfers = 0;
+        self.resizes += 1;
+    }
+
+    // Bookkeeping for every block read from the device
+    fn account(&mut self, data: &[u8]) {
+        self.bytes_read += data.len() as u64;
+        if self.overload.process(data) > 0 {
+            let total = self.overload.overloads();
+            if let Some(callback) = self.on_overload.as_mut() {
+                callback(total);
+            }
+        }
+        for x in data {
+            self.histogram[*x as usize] += 1;
+        }
+        self.histogram_count += data.len() as u64;
+        if self.histogram_count >= self.histogram_window {
+            self.last_histogram = self.histogram.to_vec();
+            self.histogram = [0; 256];
+            self.histogram_count = 0;
+        }
+    }
+
+    /// Current buffering state and latency estimate
+    pub fn stream_stats(&self) -> StreamStats {
+        StreamStats {
+            transfer_len: self.transfer.len(),
+            // Reads are synchronous, so only one transfer is ever outstanding
+            queued_transfers: 1,
+            buffered_samples: self.pending.len() / 2,
+            chunk_samples: self.chunk_samples,
+            bytes_read: self.bytes_read,
+            clipped_samples: self.overload.clipped(),
+            overloads: self.overload.overloads(),
+            sample_histogram: self.last_histogram.clone(),
+            overruns: self.overruns,
+            transfer_resizes: self.resizes,
+            ..Default::default()
+        }
+        .with_latency(self.sdr.get_sample_rate())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// The device being read, e.g. to retune between reads
+    pub fn sdr(&mut self) -> &mut RtlSdr {
+        self.sdr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adaptive_lengths_stay_within_unaligned_limits() {
+        let limits = AdaptiveTransfer {
+            min_len: 1000,
+            max_len: 5000,
+            ..Default::default()
+        }
+        .aligned();
+        assert_eq!((limits.min_len, limits.max_len), (1024, 4608));
+
+        let mut len = limits.min_len;
+        for _ in 0..4 {
+            len = limits.grown(len);
+            assert!(
+                len <= 5000 && len.is_multiple_of(TRANSFER_GRANULARITY),
+                "{}",
+                len
+            );
+        }
+        assert_eq!(len, 4608);
+        for _ in 0..4 {
+            len = limits.shrunk(len);
+            assert!(
+                len >= 1000 && len.is_multiple_of(TRANSFER_GRANULARITY),
+                "{}",
+                len
+            );
+        }
+        assert_eq!(len, 1024);
+    }
+
+    #[test]
+    fn max_below_one_packet_is_raised_to_min() {
+        let limits = AdaptiveTransfer {
+            min_len: 100,
+            max_len: 300,
+            ..Default::default()
+        }
+        .aligned();
+        assert_eq!((limits.min_len, limits.max_len), (512, 512));
+    }
+}
@@ -0,0 +1,200 @@
+//! Keeping a device streaming across host suspend and USB re-enumeration.
+//!
+//! When a laptop sleeps, the dongle usually loses power or is reset by the host
+//! controller, and on resume it comes back with a new address and default registers.
+//! `ResumingReader` notices this (a read failing because the device went away, or the
+//! wall clock jumping ahead of the monotonic clock), reopens the device by serial and
+//! puts the previous configuration back.
+use core::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::error::Result;
+use crate::error::RtlsdrError::{self, RtlsdrErr};
+use crate::observer::ConfigEvent;
+use crate::platform;
+use crate::rtlsdr::{RtlSdr as Sdr, BIAS_TEE_GPIO};
+use crate::{DirectSampleMode, IfMode, OpenOptions, RtlSdr};
+
+/// How long to wait for the device to come back by default
+pub const DEFAULT_RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+// Wall clock running ahead of the monotonic clock by more than this means the host slept
+const SUSPEND_THRESHOLD: Duration = Duration::from_secs(2);
+const RECONNECT_POLL: Duration = Duration::from_millis(500);
+
+/// Reported by `ResumingReader` as it recovers the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResumeEvent {
+    /// The host was asleep for about `slept`
+    Suspended { slept: Duration },
+    /// A read failed because the device went away
+    Disconnected,
+    /// The device was reopened and reconfigured after `downtime`
+    Reconnected { downtime: Duration },
+}
+
+/// Callback invoked for each `ResumeEvent`
+pub type ResumeCallback = Box<dyn FnMut(&ResumeEvent) + Send>;
+
+/// Sample reader that reopens and reconfigures the device after suspend/resume or
+/// re-enumeration, created by `RtlSdr::resuming_reader`
+pub struct ResumingReader {
+    sdr: RtlSdr,
+    serial: String,
+    // Last state of each bias tee GPIO switched
+    bias_tees: Arc<Mutex<Vec<(u8, bool)>>>,
+    reconnect_timeout: Duration,
+    on_event: Option<ResumeCallback>,
+    last_mono: Duration,
+    last_wall: SystemTime,
+}
+
+impl ResumingReader {
+    pub(crate) fn new(mut sdr: RtlSdr) -> Result<Self> {
+        let serial = sdr.get_usb_strings()?.serial;
+        if serial.is_empty() {
+            return Err(RtlsdrErr(
+                "Device has no serial number to find it again by".to_string(),
+            ));
+        }
+        // GPIO state isn't cached by the driver, so follow it through the observers
+        let on = sdr.sdr.get_gpio(BIAS_TEE_GPIO)?;
+        let bias_tees = Arc::new(Mutex::new(vec![(BIAS_TEE_GPIO, on)]));
+        let tracked = bias_tees.clone();
+        sdr.add_config_observer(move |event| {
+            if let ConfigEvent::BiasTee { pin, new, .. } = event {
+                let mut tracked = tracked.lock().unwrap_or_else(|e| e.into_inner());
+                match tracked.iter_mut().find(|(p, _)| p == pin) {
+                    Some((_, on)) => *on = *new,
+                    None => tracked.push((*pin, *new)),
+                }
+            }
+        });
+        sdr.reset_buffer()?;
+        Ok(ResumingReader {
+            sdr,
+            serial,
+            bias_tees,
+            reconnect_timeout: DEFAULT_RECONNECT_TIMEOUT,
+            on_event: None,
+            last_mono: platform::now(),
+            last_wall: platform::wall_clock(),
+        })
+    }
+
+    /// How long `read` waits for the device to reappear before giving up
+    pub fn set_reconnect_timeout(&mut self, timeout: Duration) {
+        self.reconnect_timeout = timeout;
+    }
+
+    /// Call `callback` on every suspend, disconnect and reconnect
+    pub fn on_event<F: FnMut(&ResumeEvent) + Send + 'static>(&mut self, callback: F) {
+        self.on_event = Some(Box::new(callback));
+    }
+
+    /// Read samples, recovering the device first if it was lost. Samples captured
+    /// around the outage are gone; the first read after a reconnect starts fresh.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if let Some(slept) = self.slept() {
+            self.emit(ResumeEvent::Suspended { slept });
+            self.reconnect()?;
+        }
+        let result = match self.sdr.read_sync(buf) {
+            Err(e) if is_disconnect(&e) => {
+                self.emit(ResumeEvent::Disconnected);
+                self.reconnect()?;
+                self.sdr.read_sync(buf)
+            }
+            result => result,
+        };
+        self.last_mono = platform::now();
+        self.last_wall = platform::wall_clock();
+        result
+    }
+
+    /// The device, e.g. to retune. Changes made here are restored after a reconnect.
+    pub fn sdr(&mut self) -> &mut RtlSdr {
+        &mut self.sdr
+    }
+
+    pub fn into_inner(self) -> RtlSdr {
+        self.sdr
+    }
+
+    // Time the host spent asleep since the last read, if it did
+    fn slept(&self) -> Option<Duration> {
+        let mono = platform::now() - self.last_mono;
+        let wall = platform::wall_clock().duration_since(self.last_wall).ok()?;
+        let slept = wall.checked_sub(mono)?;
+        (slept > SUSPEND_THRESHOLD).then_some(slept)
+    }
+
+    fn reconnect(&mut self) -> Result<()> {
+        let start = platform::now();
+        let mut last_err = None;
+        while platform::now() - start < self.reconnect_timeout {
+            match OpenOptions::new()
+                .init_attempts(1)
+                .open_by_serial(&self.serial)
+            {
+                Ok(reopened) => {
+                    let old = std::mem::replace(&mut self.sdr.sdr, reopened.sdr);
+                    self.restore(&old)?;
+                    let downtime = platform::now() - start;
+                    self.emit(ResumeEvent::Reconnected { downtime });
+                    return Ok(());
+                }
+                Err(e) => last_err = Some(e),
+            }
+            platform::sleep(RECONNECT_POLL);
+        }
+        Err(RtlsdrErr(format!(
+            "Device {} did not come back within {:?}: {}",
+            self.serial,
+            self.reconnect_timeout,
+            last_err.map(|e| e.to_string()).unwrap_or_default()
+        )))
+    }
+
+    // Apply the settings cached by the lost device to the reopened one
+    fn restore(&mut self, old: &Sdr) -> Result<()> {
+        let sdr = &mut self.sdr.sdr;
+        if old.get_direct_sampling() != DirectSampleMode::Off {
+            sdr.set_direct_sampling(old.get_direct_sampling())?;
+        }
+        sdr.set_sample_rate(old.get_sample_rate())?;
+        sdr.set_freq_correction(old.get_freq_correction())?;
+        if old.get_if_mode() != IfMode::LowIf {
+            sdr.set_if_mode(old.get_if_mode())?;
+        }
+        sdr.set_center_freq(old.get_center_freq())?;
+        sdr.set_tuner_agc(old.get_tuner_agc())?;
+        sdr.set_tuner_gain(old.get_tuner_gain())?;
+        let bias_tees = self
+            .bias_tees
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        for (pin, on) in bias_tees {
+            sdr.set_bias_tee_gpio(pin, on)?;
+        }
+        sdr.reset_buffer()
+    }
+
+    fn emit(&mut self, event: ResumeEvent) {
+        warn!("{:?}", event);
+        if let Some(callback) = self.on_event.as_mut() {
+            callback(&event);
+        }
+    }
+}
+
+// Errors libusb returns once the device has been unplugged or reset by the host
+fn is_disconnect(e: &RtlsdrError) -> bool {
+    matches!(
+        e,
+        RtlsdrError::Usb(
+            rusb::Error::NoDevice | rusb::Error::Io | rusb::Error::Pipe | rusb::Error::NotFound
+        )
+    )
+}
@@ -0,0 +1,61 @@
+//! Delivering `open_stream` chunks through a specific async runtime's channels.
+//!
+//! `open_stream` itself is runtime-agnostic. These adapters are for applications that
+//! would rather `select!` on their runtime's own receiver type.
+use super::channel;
+use super::open::SampleChunk;
+#[cfg(any(feature = "async-tokio", feature = "async-std"))]
+use super::open::{start_stream, SourceSelector, StreamConfig};
+use crate::error::Result;
+
+/// Where the reader thread puts chunks
+pub(crate) trait ChunkSink: Send + 'static {
+    /// Hand over a chunk, blocking while the consumer is behind. Returns false once
+    /// the consumer has gone away.
+    fn send_chunk(&self, chunk: Result<SampleChunk>) -> bool;
+}
+
+impl ChunkSink for channel::Sender<Result<SampleChunk>> {
+    fn send_chunk(&self, chunk: Result<SampleChunk>) -> bool {
+        self.send_blocking(chunk)
+    }
+}
+
+#[cfg(feature = "async-tokio")]
+impl ChunkSink for tokio::sync::mpsc::Sender<Result<SampleChunk>> {
+    fn send_chunk(&self, chunk: Result<SampleChunk>) -> bool {
+        self.blocking_send(chunk).is_ok()
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl ChunkSink for async_channel::Sender<Result<SampleChunk>> {
+    fn send_chunk(&self, chunk: Result<SampleChunk>) -> bool {
+        self.send_blocking(chunk).is_ok()
+    }
+}
+
+/// `open_stream`, delivering into a tokio channel holding up to `queue_len` chunks
+#[cfg(feature = "async-tokio")]
+pub fn open_stream_tokio(
+    selector: SourceSelector,
+    config: StreamConfig,
+    queue_len: usize,
+) -> Result<tokio::sync::mpsc::Receiver<Result<SampleChunk>>> {
+    let (tx, rx) = tokio::sync::mpsc::channel(queue_len.max(1));
+    start_stream(selector, config, tx)?;
+    Ok(rx)
+}
+
+/// `open_stream`, delivering into an `async-channel` receiver (as used by async-std
+/// and smol) holding up to `queue_len` chunks
+#[cfg(feature = "async-std")]
+pub fn open_stream_async_channel(
+    selector: SourceSelector,
+    config: StreamConfig,
+    queue_len: usize,
+) -> Result<async_channel::Receiver<Result<SampleChunk>>> {
+    let (tx, rx) = async_channel::bounded(queue_len.max(1));
+    start_stream(selector, config, tx)?;
+    Ok(rx)
+}
@@ -0,0 +1,117 @@
+//! Crystal warm-up tracking. A cold dongle drifts by up to a few ppm during its first
+//! minutes, enough to walk a narrowband digital signal out of its decoder's capture
+//! range. `SettleTracker` measures a reference carrier repeatedly and reports the
+//! device as settled once consecutive measurements agree.
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::{platform, RtlSdr};
+
+/// Settings for `RtlSdr::settle_tracker`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SettleConfig {
+    /// Time between measurements
+    pub interval: Duration,
+    /// Largest change between consecutive measurements still counted as stable, in ppm
+    pub tolerance_ppm: f64,
+    /// Stable measurements in a row needed to report the device as settled
+    pub stable_measurements: u32,
+}
+
+impl Default for SettleConfig {
+    fn default() -> Self {
+        SettleConfig {
+            interval: Duration::from_secs(10),
+            tolerance_ppm: 0.1,
+            stable_measurements: 3,
+        }
+    }
+}
+
+/// One measurement of a `SettleTracker`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SettleStatus {
+    /// Time since the tracker was created
+    pub elapsed: Duration,
+    /// Frequency error against the reference, as `FreqAccuracy::error_ppm`
+    pub error_ppm: f64,
+    /// Change since the previous measurement, in ppm; 0 for the first one
+    pub drift_ppm: f64,
+    /// Reference carrier level above the noise, in dB
+    pub snr_db: f32,
+    /// Whether the last `stable_measurements` measurements stayed within tolerance
+    pub settled: bool,
+}
+
+/// Repeated frequency error measurements against a reference carrier, created by
+/// `RtlSdr::settle_tracker`
+pub struct SettleTracker<'a> {
+    sdr: &'a mut RtlSdr,
+    reference_freq: u32,
+    config: SettleConfig,
+    start: Duration,
+    last_ppm: Option<f64>,
+    stable: u32,
+}
+
+impl<'a> SettleTracker<'a> {
+    pub(crate) fn new(sdr: &'a mut RtlSdr, reference_freq: u32, config: SettleConfig) -> Self {
+        SettleTracker {
+            sdr,
+            reference_freq,
+            config,
+            start: platform::now(),
+            last_ppm: None,
+            stable: 0,
+        }
+    }
+
+    /// Measure now, without waiting for the interval
+    pub fn measure(&mut self) -> Result<SettleStatus> {
+        let accuracy = self.sdr.measure_freq_accuracy(self.reference_freq)?;
+        let drift_ppm = self.last_ppm.map_or(0.0, |last| accuracy.error_ppm - last);
+        if self.last_ppm.is_some() && drift_ppm.abs() <= self.config.tolerance_ppm {
+            self.stable += 1;
+        } else {
+            self.stable = 0;
+        }
+        self.last_ppm = Some(accuracy.error_ppm);
+        let status = SettleStatus {
+            elapsed: platform::now() - self.start,
+            error_ppm: accuracy.error_ppm,
+            drift_ppm,
+            snr_db: accuracy.snr_db,
+            settled: self.stable >= self.config.stable_measurements,
+        };
+        debug!("Warm-up: {:?}", status);
+        Ok(status)
+    }
+
+    /// Wait for the interval, then measure
+    pub fn next_status(&mut self) -> Result<SettleStatus> {
+        if self.last_ppm.is_some() {
+            platform::sleep(self.config.interval);
+        }
+        self.measure()
+    }
+
+    /// Measure until the device has settled or `timeout` has passed, returning the
+    /// last measurement either way
+    pub fn wait_settled(&mut self, timeout: Duration) -> Result<SettleStatus> {
+        let end = platform::now() + timeout;
+        loop {
+            let status = self.next_status()?;
+            if status.settled || platform::now() + self.config.interval > end {
+                return Ok(status);
+            }
+        }
+    }
+}
+
+impl Iterator for SettleTracker<'_> {
+    type Item = Result<SettleStatus>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_status())
+    }
+}
@@ -0,0 +1,288 @@
+//! Sharing one capture with several local processes through a memory-mapped ring
+//! buffer, enabled with the `shm` feature.
+//!
+//! A capture daemon writes with `ShmSink`; each decoder opens the same file with
+//! `ShmSource`, which implements `SdrReader`. Put the file on a tmpfs such as
+//! `/dev/shm` so it never touches the disk. The writer never waits for readers: a
+//! reader that falls more than the ring's capacity behind skips ahead and counts
+//! the bytes it lost.
+//!
+//! Layout, little-endian:
+//!
+//! | offset | type     | field                                        |
+//! |--------|----------|----------------------------------------------|
+//! | 0      | [u8; 8]  | magic `RTLSDRSH`                             |
+//! | 8      | u32      | version (1)                                  |
+//! | 12     | u32      | header length (64)                           |
+//! | 16     | u64      | ring capacity in bytes                       |
+//! | 24     | u64      | bytes written and complete                   |
+//! | 32     | u64      | bytes being written (>= the above)           |
+//! | 40     | u32      | sample rate                                  |
+//! | 44     | u32      | center frequency                             |
+//! | 48     | u32      | 1 once the writer has closed                 |
+//!
+//! The ring follows the header; byte `n` of the stream is at `64 + n % capacity`.
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+
+use memmap2::{Mmap, MmapMut};
+
+use super::source::SdrReader;
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::error::{io_err, Result};
+use crate::platform;
+
+const MAGIC: &[u8; 8] = b"RTLSDRSH";
+pub(crate) const VERSION: u32 = 1;
+const HEADER_LEN: usize = 64;
+const CAPACITY: usize = 16;
+const COMMITTED: usize = 24;
+const RESERVED: usize = 32;
+const SAMPLE_RATE: usize = 40;
+const CENTER_FREQ: usize = 44;
+const CLOSED: usize = 48;
+
+// How often an idle reader checks for new data
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// How long `ShmSource` waits for new data by default before reporting an error
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// SAFETY (for the accessors below): the mapping is page aligned and at least
+// HEADER_LEN long, and every offset passed in is a multiple of the field's size.
+fn atomic_u64<'a>(base: *const u8, offset: usize) -> &'a AtomicU64 {
+    unsafe { &*(base.add(offset) as *const AtomicU64) }
+}
+
+fn atomic_u32<'a>(base: *const u8, offset: usize) -> &'a AtomicU32 {
+    unsafe { &*(base.add(offset) as *const AtomicU32) }
+}
+/// Writing side of the ring buffer
+pub struct ShmSink {
+    path: PathBuf,
+    map: MmapMut,
+    capacity: u64,
+}
+
+impl ShmSink {
+    /// Create (or truncate) `path` with a ring of `capacity` bytes, rounded up to a
+    /// whole number of I/Q pairs
+    pub fn create<P: AsRef<Path>>(path: P, capacity: usize) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let capacity = capacity.max(2).next_multiple_of(2);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .map_err(|e| io_err(&path, e))?;
+        file.set_len((HEADER_LEN + capacity) as u64)
+            .map_err(|e| io_err(&path, e))?;
+        // SAFETY: the file was just sized by us; other processes only ever read it
+        let mut map = unsafe { MmapMut::map_mut(&file) }.map_err(|e| io_err(&path, e))?;
+        map[0..8].copy_from_slice(MAGIC);
+        map[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        map[12..16].copy_from_slice(&(HEADER_LEN as u32).to_le_bytes());
+        map[CAPACITY..CAPACITY + 8].copy_from_slice(&(capacity as u64).to_le_bytes());
+        Ok(ShmSink {
+            path,
+            map,
+            capacity: capacity as u64,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Capture settings reported to readers
+    pub fn set_metadata(&self, sample_rate: u32, center_freq: u32) {
+        atomic_u32(self.map.as_ptr(), SAMPLE_RATE).store(sample_rate, Ordering::Release);
+        atomic_u32(self.map.as_ptr(), CENTER_FREQ).store(center_freq, Ordering::Release);
+    }
+
+    /// Append raw interleaved I/Q. A trailing odd byte is dropped so readers always
+    /// start on a pair.
+    pub fn write(&mut self, buf: &[u8]) {
+        let base = self.map.as_ptr();
+        let buf = &buf[..buf.len() & !1];
+        // Only the last `capacity` bytes of an oversized buffer can survive anyway.
+        // Skip whole pairs so I and Q keep their places.
+        let skip = ((buf.len() as u64).saturating_sub(self.capacity) as usize + 1) & !1;
+        let buf = &buf[skip..];
+        let start = atomic_u64(base, COMMITTED).load(Ordering::Relaxed) + skip as u64;
+        let end = start + buf.len() as u64;
+        // Readers check this after copying, to detect bytes overwritten under them
+        atomic_u64(base, RESERVED).store(end, Ordering::Release);
+        std::sync::atomic::fence(Ordering::Release);
+        let offset = (start % self.capacity) as usize;
+        let first = buf.len().min(self.capacity as usize - offset);
+        let ring = &mut self.map[HEADER_LEN..];
+        ring[offset..offset + first].copy_from_slice(&buf[..first]);
+        ring[..buf.len() - first].copy_from_slice(&buf[first..]);
+        atomic_u64(base, COMMITTED).store(end, Ordering::Release);
+    }
+}
+
+impl Drop for ShmSink {
+    fn drop(&mut self) {
+        atomic_u32(self.map.as_ptr(), CLOSED).store(1, Ordering::Release);
+    }
+}
+
+/// Reading side of the ring buffer. Starts at the live end of the stream.
+pub struct ShmSource {
+    path: PathBuf,
+    map: Mmap,
+    capacity: u64,
+    pos: u64,
+    lost: u64,
+    idle_timeout: Duration,
+}
+
+impl ShmSource {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path).map_err(|e| io_err(&path, e))?;
+        // SAFETY: the writer only grows the counters and rewrites ring bytes, which
+        // we copy out and validate against the counters
+        let map = unsafe { Mmap::map(&file) }.map_err(|e| io_err(&path, e))?;
+        if map.len() < HEADER_LEN || &map[0..8] != MAGIC {
+            return Err(RtlsdrErr(format!(
+                "{} is not an rtlsdr shared memory ring",
+                path.display()
+            )));
+        }
+        let version = u32::from_le_bytes(map[8..12].try_into().unwrap());
+        if version != VERSION {
+            return Err(RtlsdrErr(format!(
+                "{}: unsupported ring version {}",
+                path.display(),
+                version
+            )));
+        }
+        let capacity = u64::from_le_bytes(map[CAPACITY..CAPACITY + 8].try_into().unwrap());
+        if capacity == 0 || map.len() < HEADER_LEN + capacity as usize {
+            return Err(RtlsdrErr(format!("{}: truncated ring", path.display())));
+        }
+        let pos = atomic_u64(map.as_ptr(), COMMITTED).load(Ordering::Acquire);
+        Ok(ShmSource {
+            path,
+            map,
+            capacity,
+            pos,
+            lost: 0,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        })
+    }
+
+    /// How long `read_iq` waits for the writer before failing
+    pub fn set_idle_timeout(&mut self, timeout: Duration) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Bytes skipped because this reader fell behind the writer
+    pub fn lost_bytes(&self) -> u64 {
+        self.lost
+    }
+}
+
+// Move a lagging reader up to `to`, counting what it missed
+fn skip_to(path: &Path, pos: &mut u64, lost: &mut u64, to: u64) {
+    warn!(
+        "{}: reader overrun, skipped {} bytes",
+        path.display(),
+        to - *pos
+    );
+    *lost += to - *pos;
+    *pos = to;
+}
+
+impl SdrReader for ShmSource {
+    fn read_iq(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let ShmSource {
+            path,
+            map,
+            capacity,
+            pos,
+            lost,
+            idle_timeout,
+        } = self;
+        let capacity = *capacity;
+        let base = map.as_ptr();
+        let ring = &map[HEADER_LEN..HEADER_LEN + capacity as usize];
+        let idle_since = platform::now();
+        loop {
+            let committed = atomic_u64(base, COMMITTED).load(Ordering::Acquire);
+            if committed == *pos {
+                if atomic_u32(base, CLOSED).load(Ordering::Acquire) != 0 {
+                    return Ok(0);
+                }
+                if platform::now() - idle_since > *idle_timeout {
+                    return Err(RtlsdrErr(format!(
+                        "{}: no data from the writer for {:?}",
+                        path.display(),
+                        *idle_timeout
+                    )));
+                }
+                platform::sleep(POLL_INTERVAL);
+                continue;
+            }
+            if committed - *pos > capacity {
+                skip_to(path, pos, lost, committed - capacity);
+            }
+            let n = ((committed - *pos) as usize).min(buf.len()) & !1;
+            if n == 0 {
+                return Ok(0);
+            }
+            let offset = (*pos % capacity) as usize;
+            let first = n.min(capacity as usize - offset);
+            buf[..first].copy_from_slice(&ring[offset..offset + first]);
+            buf[first..n].copy_from_slice(&ring[..n - first]);
+            std::sync::atomic::fence(Ordering::Acquire);
+            // Bytes from `pos` on are being replaced once the writer reserves past
+            // `pos + capacity`
+            let reserved = atomic_u64(base, RESERVED).load(Ordering::Acquire);
+            if reserved > *pos + capacity {
+                skip_to(path, pos, lost, reserved - capacity);
+                continue;
+            }
+            *pos += n as u64;
+            return Ok(n);
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        atomic_u32(self.map.as_ptr(), SAMPLE_RATE).load(Ordering::Acquire)
+    }
+
+    fn center_freq(&self) -> u32 {
+        atomic_u32(self.map.as_ptr(), CENTER_FREQ).load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn odd_oversized_write_keeps_pairs_aligned() {
+        let path = std::env::temp_dir().join(format!("rtlsdr-shm-test-{}", std::process::id()));
+        let mut sink = ShmSink::create(&path, 4).unwrap();
+        let mut source = ShmSource::open(&path).unwrap();
+
+        // Nine bytes into a four byte ring: the odd byte is dropped, then the first
+        // two pairs are skipped
+        sink.write(&[0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        let mut buf = [0; 8];
+        assert_eq!(source.read_iq(&mut buf).unwrap(), 4);
+        assert_eq!(buf[..4], [4, 5, 6, 7]);
+        assert_eq!(source.lost_bytes(), 4);
+
+        drop(sink);
+        assert_eq!(source.read_iq(&mut buf).unwrap(), 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
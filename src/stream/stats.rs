@@ -0,0 +1,47 @@
+use core::time::Duration;
+
+/// Snapshot of a reader's buffering, returned by `Reader::stream_stats`
+#[derive(Debug, Clone, Default)]
+pub struct StreamStats {
+    /// Bytes requested per USB transfer
+    pub transfer_len: usize,
+    /// Transfers queued on the device at once
+    pub queued_transfers: usize,
+    /// Samples read from the device but not yet handed to the consumer
+    pub buffered_samples: usize,
+    /// Size of the last chunk returned by `read_chunk`, in samples
+    pub chunk_samples: usize,
+    /// Total bytes read from the device
+    pub bytes_read: u64,
+    /// Raw values at either end of the ADC range
+    pub clipped_samples: u64,
+    /// Runs of clipped values, see `detect::OverloadDetector`
+    pub overloads: u64,
+    /// Transfers started after the consumer had been away longer than the previous
+    /// transfer lasted, so the device FIFO overflowed and samples were lost
+    pub overruns: u64,
+    /// Transfer length changes made by the adaptive mode, see `Reader::set_adaptive`
+    pub transfer_resizes: u64,
+    /// Count of each raw value (index 0..=255) over the last completed histogram window,
+    /// empty until the first window completes. Useful for spotting DC bias, clipping and
+    /// stuck ADC bits without recording raw I/Q.
+    pub sample_histogram: Vec<u64>,
+    /// Rough time from a sample hitting the ADC until the consumer receives it.
+    /// Counts filling the queued transfers, samples waiting in the reader and
+    /// collecting a full chunk; USB and host scheduling delays are not included.
+    pub estimated_latency: Duration,
+}
+
+impl StreamStats {
+    /// Fill in `estimated_latency` for `sample_rate`
+    pub(crate) fn with_latency(mut self, sample_rate: u32) -> Self {
+        if sample_rate == 0 {
+            return self;
+        }
+        let samples = self.queued_transfers * self.transfer_len / 2
+            + self.buffered_samples
+            + self.chunk_samples;
+        self.estimated_latency = Duration::from_secs_f64(samples as f64 / sample_rate as f64);
+        self
+    }
+}
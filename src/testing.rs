@@ -0,0 +1,217 @@
+//! Scripted USB faults for testing recovery code against an attached device. A
+//! `FaultScript` installed with `RtlSdr::inject_faults` makes chosen bulk transfers time
+//! out or come back short, or makes the device disappear, so applications can exercise
+//! their error paths without pulling the plug by hand.
+//!
+//! Bulk transfers from `read_sync` and `read_async` are counted from 0 once the script
+//! is installed. After `Disappear` fires, control and bulk transfers alike fail with
+//! `NoDevice`, as they do for an unplugged device.
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::error::Result;
+
+// Callback of a queued bulk read, given each completed transfer
+type Completion<'a> = &'a mut dyn FnMut(&mut [u8]) -> bool;
+
+/// Failure injected by a `FaultScript`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The transfer times out
+    Timeout,
+    /// Only this many bytes arrive
+    ShortRead(usize),
+    /// The device is unplugged: this and every later transfer fails with `NoDevice`
+    Disappear,
+}
+
+/// Faults keyed by bulk transfer number (0-based)
+#[derive(Debug, Default)]
+pub struct FaultScript {
+    faults: Mutex<Vec<(usize, Fault)>>,
+    transfers: AtomicUsize,
+    gone: AtomicBool,
+}
+
+impl FaultScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inject `fault` on bulk transfer `n`
+    pub fn on_transfer(self, n: usize, fault: Fault) -> Self {
+        self.faults.lock().unwrap().push((n, fault));
+        self
+    }
+
+    /// Whether a `Disappear` fault has fired
+    pub fn gone(&self) -> bool {
+        self.gone.load(Ordering::SeqCst)
+    }
+
+    /// Bulk transfers made so far
+    pub fn transfers(&self) -> usize {
+        self.transfers.load(Ordering::SeqCst)
+    }
+
+    /// Fail a control transfer once the device has disappeared
+    pub(crate) fn control(&self) -> Result<()> {
+        if self.gone() {
+            return Err(rusb::Error::NoDevice.into());
+        }
+        Ok(())
+    }
+
+    /// Count a bulk transfer and return the fault scripted for it, if any. Once the
+    /// device has disappeared every transfer gets `Disappear`.
+    pub(crate) fn next_fault(&self) -> Option<Fault> {
+        let n = self.transfers.fetch_add(1, Ordering::SeqCst);
+        if self.gone() {
+            return Some(Fault::Disappear);
+        }
+        let fault = self
+            .faults
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(at, _)| *at == n)
+            .map(|(_, fault)| *fault);
+        if fault == Some(Fault::Disappear) {
+            self.gone.store(true, Ordering::SeqCst);
+        }
+        fault
+    }
+
+    /// Run one bulk transfer through the script: `read` is the real transfer, skipped
+    /// when the script fails this one
+    pub(crate) fn bulk<F: FnOnce(&mut [u8]) -> Result<usize>>(
+        &self,
+        buf: &mut [u8],
+        read: F,
+    ) -> Result<usize> {
+        match self.next_fault() {
+            Some(Fault::Timeout) => Err(rusb::Error::Timeout.into()),
+            Some(Fault::Disappear) => Err(rusb::Error::NoDevice.into()),
+            Some(Fault::ShortRead(len)) => {
+                let len = len.min(buf.len());
+                read(&mut buf[..len])
+            }
+            None => read(buf),
+        }
+    }
+
+    /// Run queued bulk transfers through the script: `read` is the real transfer loop,
+    /// handing each completed buffer to the callback it is given. A failed transfer
+    /// stops the loop and `bulk_async` returns the error the real one would give.
+    pub(crate) fn bulk_async<R, F>(&self, read: R, mut callback: F) -> Result<()>
+    where
+        R: FnOnce(Completion<'_>) -> Result<()>,
+        F: FnMut(&mut [u8]) -> bool,
+    {
+        let mut failed = None;
+        read(&mut |buf| match self.next_fault() {
+            None => callback(buf),
+            Some(Fault::ShortRead(len)) => {
+                let len = len.min(buf.len());
+                callback(&mut buf[..len])
+            }
+            Some(fault) => {
+                failed = Some(fault);
+                false
+            }
+        })?;
+        match failed {
+            Some(Fault::Timeout) => Err(rusb::Error::Timeout.into()),
+            Some(_) => Err(rusb::Error::NoDevice.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::RtlsdrError;
+
+    fn fill(buf: &mut [u8]) -> Result<usize> {
+        buf.fill(0x7f);
+        Ok(buf.len())
+    }
+
+    fn usb_err<T: std::fmt::Debug>(result: Result<T>) -> rusb::Error {
+        match result {
+            Err(RtlsdrError::Usb(e)) => e,
+            other => panic!("expected a USB error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn faults_fire_on_their_transfer() {
+        let script = FaultScript::new()
+            .on_transfer(1, Fault::Timeout)
+            .on_transfer(2, Fault::ShortRead(100))
+            .on_transfer(4, Fault::Disappear);
+        let mut buf = [0; 512];
+
+        assert_eq!(script.bulk(&mut buf, fill).unwrap(), 512);
+        assert_eq!(usb_err(script.bulk(&mut buf, fill)), rusb::Error::Timeout);
+        assert_eq!(script.bulk(&mut buf, fill).unwrap(), 100);
+        assert_eq!(script.bulk(&mut buf, fill).unwrap(), 512);
+        assert!(script.control().is_ok());
+
+        assert_eq!(usb_err(script.bulk(&mut buf, fill)), rusb::Error::NoDevice);
+        assert!(script.gone());
+        // Nothing comes back once the device is gone
+        assert_eq!(usb_err(script.bulk(&mut buf, fill)), rusb::Error::NoDevice);
+        assert!(matches!(
+            script.control(),
+            Err(RtlsdrError::Usb(rusb::Error::NoDevice))
+        ));
+        assert_eq!(script.transfers(), 6);
+    }
+
+    // Stand-in for `transfer::read_async`: hands `transfers` full buffers to the
+    // callback until it asks to stop
+    fn queued(transfers: usize) -> impl FnOnce(Completion<'_>) -> Result<()> {
+        move |callback| {
+            let mut buf = [0x7f; 512];
+            for _ in 0..transfers {
+                if !callback(&mut buf) {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn async_faults_end_the_read_with_usb_errors() {
+        let script = FaultScript::new()
+            .on_transfer(1, Fault::ShortRead(100))
+            .on_transfer(2, Fault::Timeout);
+        let mut lens = Vec::new();
+        let result = script.bulk_async(queued(5), |buf| {
+            lens.push(buf.len());
+            true
+        });
+        assert_eq!(usb_err(result), rusb::Error::Timeout);
+        assert_eq!(lens, [512, 100]);
+
+        let script = FaultScript::new().on_transfer(1, Fault::Disappear);
+        let result = script.bulk_async(queued(5), |_| true);
+        // What `stream::resume` treats as an unplugged device
+        assert_eq!(usb_err(result), rusb::Error::NoDevice);
+        assert!(script.gone());
+
+        let script = FaultScript::new();
+        assert!(script.bulk_async(queued(3), |_| true).is_ok());
+        assert_eq!(script.transfers(), 3);
+    }
+
+    #[test]
+    fn short_read_is_capped_at_the_buffer() {
+        let script = FaultScript::new().on_transfer(0, Fault::ShortRead(4096));
+        let mut buf = [0; 512];
+        assert_eq!(script.bulk(&mut buf, fill).unwrap(), 512);
+    }
+}
@@ -0,0 +1,116 @@
+//! Custom gain partition tables for R820T-family tuners.
+//!
+//! A table lists, for each selectable gain, how it is split between the LNA, mixer
+//! and VGA stages. The file format is one entry per line, whitespace separated:
+//!
+//! ```text
+//! # gain (tenths of dB)  lna  mixer  vga
+//! 0    0  0  8
+//! 9    1  0  8
+//! 14   1  1  8
+//! ```
+//!
+//! Each stage index is 0-15. Blank lines and text after `#` are ignored.
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::RtlsdrError::RtlsdrErr;
+use crate::error::{io_err, Result};
+
+const MAX_STAGE_INDEX: u8 = 15;
+
+/// How one gain setting is split between the tuner stages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GainPartition {
+    /// Total gain in tenths of a dB, as reported by `get_tuner_gains`
+    pub gain: i32,
+    pub lna: u8,
+    pub mixer: u8,
+    pub vga: u8,
+}
+
+/// Gain partitions sorted by total gain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GainTable {
+    entries: Vec<GainPartition>,
+}
+
+impl GainTable {
+    pub fn new(mut entries: Vec<GainPartition>) -> Result<Self> {
+        if entries.is_empty() {
+            return Err(RtlsdrErr("Gain table has no entries".to_string()));
+        }
+        if let Some(bad) = entries.iter().find(|e| {
+            e.lna > MAX_STAGE_INDEX || e.mixer > MAX_STAGE_INDEX || e.vga > MAX_STAGE_INDEX
+        }) {
+            return Err(RtlsdrErr(format!(
+                "Gain table entry {} has a stage index above {}",
+                bad.gain, MAX_STAGE_INDEX
+            )));
+        }
+        entries.sort_by_key(|e| e.gain);
+        entries.dedup_by_key(|e| e.gain);
+        Ok(GainTable { entries })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| io_err(path, e))?;
+        text.parse()
+            .map_err(|e| RtlsdrErr(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn entries(&self) -> &[GainPartition] {
+        &self.entries
+    }
+
+    pub fn gains(&self) -> Vec<i32> {
+        self.entries.iter().map(|e| e.gain).collect()
+    }
+
+    /// Entry with the total gain closest to `gain`
+    pub fn nearest(&self, gain: i32) -> &GainPartition {
+        self.entries
+            .iter()
+            .min_by_key(|e| (e.gain - gain).abs())
+            .expect("gain table is never empty")
+    }
+}
+
+impl FromStr for GainTable {
+    type Err = crate::error::RtlsdrError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        for (num, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            match parse_entry(line) {
+                Some(entry) => entries.push(entry),
+                None => {
+                    return Err(RtlsdrErr(format!(
+                        "line {}: expected \"gain lna mixer vga\", got \"{}\"",
+                        num + 1,
+                        line
+                    )))
+                }
+            }
+        }
+        GainTable::new(entries)
+    }
+}
+
+fn parse_entry(line: &str) -> Option<GainPartition> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    match fields[..] {
+        [gain, lna, mixer, vga] => Some(GainPartition {
+            gain: gain.parse().ok()?,
+            lna: lna.parse().ok()?,
+            mixer: mixer.parse().ok()?,
+            vga: vga.parse().ok()?,
+        }),
+        _ => None,
+    }
+}
@@ -1,10 +1,72 @@
+pub mod gain_table;
 pub mod r820t;
 use crate::device::Device;
 use crate::error::Result;
 use crate::TunerGain;
+pub use gain_table::{GainPartition, GainTable};
 
 pub const KNOWN_TUNERS: [TunerInfo; 1] = [r820t::TUNER_INFO];
 
+/// Tuner chips found on RTL2832U boards. Only the R820T family is driven by this
+/// crate; the others can still be named, e.g. in `Requirements`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TunerType {
+    R820T,
+    R828D,
+    E4000,
+    FC0012,
+    FC0013,
+    FC2580,
+    Unknown,
+}
+
+impl TunerType {
+    /// Type for an id as used in `TunerInfo::id` and `UsbDeviceSignature::tuner`
+    pub fn from_id(id: &str) -> TunerType {
+        match id {
+            "r820t" => TunerType::R820T,
+            "r828d" => TunerType::R828D,
+            "e4000" => TunerType::E4000,
+            "fc0012" => TunerType::FC0012,
+            "fc0013" => TunerType::FC0013,
+            "fc2580" => TunerType::FC2580,
+            _ => TunerType::Unknown,
+        }
+    }
+
+    /// Published tuning ranges of the chip, empty for `Unknown`
+    pub fn freq_ranges(&self) -> Vec<(u32, u32)> {
+        match self {
+            TunerType::R820T | TunerType::R828D => {
+                vec![(r820t::TUNER_INFO.min_freq, r820t::TUNER_INFO.max_freq)]
+            }
+            // The E4000 PLL can't lock between its two bands
+            TunerType::E4000 => vec![(52_000_000, 1_100_000_000), (1_250_000_000, 2_200_000_000)],
+            TunerType::FC0012 => vec![(22_000_000, 948_600_000)],
+            TunerType::FC0013 => vec![(22_000_000, 1_100_000_000)],
+            TunerType::FC2580 => vec![(146_000_000, 308_000_000), (438_000_000, 924_000_000)],
+            TunerType::Unknown => Vec::new(),
+        }
+    }
+}
+
+/// Individually controllable gain stages, see `RtlSdr::set_lna_gain` and friends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GainStage {
+    Lna,
+    Mixer,
+    Vga,
+}
+
+/// What the tuner of an open device supports, returned by `RtlSdr::tuner_capabilities`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TunerCapabilities {
+    pub tuner: TunerType,
+    /// Tunable center frequencies in Hz without direct sampling, as sorted, disjoint
+    /// inclusive intervals
+    pub freq_ranges: Vec<(u32, u32)>,
+}
+
 #[derive(Debug, Clone, Copy)]
 
 pub struct TunerInfo {
@@ -13,6 +75,9 @@ pub struct TunerInfo {
     pub i2c_addr: u8,
     pub check_addr: u8,
     pub check_val: u8,
+    /// Tunable range in Hz
+    pub min_freq: u32,
+    pub max_freq: u32,
     // pub gains: Vec<i8>,
 }
 
@@ -24,9 +89,28 @@ pub trait Tuner: std::fmt::Debug + Send {
     fn get_gains(&self) -> Result<Vec<i32>>;
     fn read_gain(&self, handle: &Device) -> Result<i32>;
     fn set_gain(&mut self, handle: &Device, gain: TunerGain) -> Result<()>;
+    /// Enable or disable the tuner's hardware AGC loops, independent of the gain mode
+    fn set_agc(&mut self, handle: &Device, on: bool) -> Result<()>;
+    /// Replace the built-in gain partitions (`None` restores them). Takes effect on
+    /// the next manual `set_gain`.
+    fn set_gain_table(&mut self, table: Option<GainTable>) -> Result<()>;
+    /// Set one gain stage to a register index (0-15 on the R820T family), switching
+    /// that stage's AGC off. Kept until the next `set_gain`, except for the VGA.
+    fn set_stage_gain(&mut self, handle: &Device, stage: GainStage, index: u8) -> Result<()>;
+    /// Set IF gain stage `stage` (numbered from 1) to the supported gain nearest
+    /// `gain`, in tenths of a dB. Kept across later `set_gain` calls.
+    fn set_if_gain(&mut self, handle: &Device, stage: u8, gain: i32) -> Result<()>;
     fn set_freq(&mut self, handle: &Device, freq: u32) -> Result<()>;
     fn set_bandwidth(&mut self, handle: &Device, bw: u32, rate: u32) -> Result<()>;
+    /// IF filter bandwidth last programmed by `set_bandwidth`, in Hz (0 before that)
+    fn get_bandwidth(&self) -> Result<u32>;
+    /// Filter bandwidths `set_bandwidth` can select, in Hz, ascending
+    fn get_bandwidths(&self) -> Result<Vec<u32>>;
+    /// Bandwidth to request when the bandwidth follows the sample rate
+    fn auto_bandwidth(&self, rate: u32) -> u32;
     fn get_if_freq(&self) -> Result<u32>;
+    /// Whether the tuner can deliver I/Q at zero IF instead of a single low-IF output
+    fn supports_zero_if(&self) -> bool;
     fn get_xtal_freq(&self) -> Result<u32>;
     fn set_xtal_freq(&mut self, freq: u32) -> Result<()>;
     fn exit(&mut self, handle: &Device) -> Result<()>;
@@ -44,6 +128,8 @@ impl Tuner for NoTuner {
             i2c_addr: 0,
             check_addr: 0,
             check_val: 0,
+            min_freq: 0,
+            max_freq: u32::MAX,
         })
     }
     fn get_gains(&self) -> Result<Vec<i32>> {
@@ -55,12 +141,33 @@ impl Tuner for NoTuner {
     fn set_gain(&mut self, _handle: &Device, _gain: TunerGain) -> Result<()> {
         Ok(())
     }
+    fn set_agc(&mut self, _handle: &Device, _on: bool) -> Result<()> {
+        Ok(())
+    }
+    fn set_gain_table(&mut self, _table: Option<GainTable>) -> Result<()> {
+        Ok(())
+    }
+    fn set_stage_gain(&mut self, _handle: &Device, _stage: GainStage, _index: u8) -> Result<()> {
+        Ok(())
+    }
+    fn set_if_gain(&mut self, _handle: &Device, _stage: u8, _gain: i32) -> Result<()> {
+        Ok(())
+    }
     fn set_freq(&mut self, _handle: &Device, _freq: u32) -> Result<()> {
         Ok(())
     }
     fn set_bandwidth(&mut self, _handle: &Device, _bw: u32, _rate: u32) -> Result<()> {
         Ok(())
     }
+    fn get_bandwidth(&self) -> Result<u32> {
+        Ok(0)
+    }
+    fn get_bandwidths(&self) -> Result<Vec<u32>> {
+        Ok(vec![])
+    }
+    fn auto_bandwidth(&self, rate: u32) -> u32 {
+        rate
+    }
     fn get_xtal_freq(&self) -> Result<u32> {
         Ok(0)
     }
@@ -70,6 +177,9 @@ impl Tuner for NoTuner {
     fn get_if_freq(&self) -> Result<u32> {
         Ok(0)
     }
+    fn supports_zero_if(&self) -> bool {
+        false
+    }
     fn exit(&mut self, _handle: &Device) -> Result<()> {
         Ok(())
     }
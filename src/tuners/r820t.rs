@@ -1,4 +1,5 @@
-use super::{Tuner, TunerGain, TunerInfo};
+use super::{GainStage, GainTable, Tuner, TunerGain, TunerInfo};
+use crate::device::sequence::{self, RegSpace, RegWrite, Sequence, FULL_MASK};
 use crate::device::Device;
 use crate::error::Result;
 use crate::error::RtlsdrError::RtlsdrErr;
@@ -24,16 +25,51 @@ const REG_INIT: [u8; NUM_CACHE_REGS] = [
     0x54, 0xae, 0x4a, 0xc0, /* 1c to 1f */
 ];
 
+const INIT_WRITES: [RegWrite; NUM_CACHE_REGS] = {
+    let mut writes = [RegWrite::tuner(0, 0); NUM_CACHE_REGS];
+    let mut i = 0;
+    while i < NUM_CACHE_REGS {
+        writes[i] = RegWrite::tuner((RW_REG_START + i) as u16, REG_INIT[i] as u16);
+        i += 1;
+    }
+    writes
+};
+
+/// Default `Sequence::R820tInit`: `REG_INIT` one register at a time
+pub(crate) const INIT_SEQUENCE: &[RegWrite] = &INIT_WRITES;
+
+/// Default `Sequence::R820tTune`
+pub(crate) const TUNE_SEQUENCE: &[RegWrite] = &[
+    // Mixer and image settings cleared for every band
+    RegWrite::tuner(0x08, 0x00).masked(0x3f),
+    RegWrite::tuner(0x09, 0x00).masked(0x3f),
+];
+
+/// Default `Sequence::R820tStandby`
+pub(crate) const STANDBY_SEQUENCE: &[RegWrite] = &[
+    RegWrite::tuner(0x06, 0xb1),
+    RegWrite::tuner(0x05, 0xa0),
+    RegWrite::tuner(0x07, 0x3a),
+    RegWrite::tuner(0x08, 0x40),
+    RegWrite::tuner(0x09, 0xc0),
+    RegWrite::tuner(0x0a, 0x36),
+    RegWrite::tuner(0x0c, 0x35),
+    RegWrite::tuner(0x0f, 0x68),
+    RegWrite::tuner(0x11, 0x03),
+    RegWrite::tuner(0x17, 0xf4),
+    RegWrite::tuner(0x19, 0x0c),
+];
+
 /* measured with a Racal 6103E GSM test set at 928 MHz with -60 dBm
 * input power, for raw results see:
 * http://steve-m.de/projects/rtl-sdr/gain_measurement/r820t/
 */
-const _VGA_BASE_GAIN: i32 = -47;
+const VGA_BASE_GAIN: i32 = -47;
 const GAINS: [i32; 29] = [
     0, 9, 14, 27, 37, 77, 87, 125, 144, 157, 166, 197, 207, 229, 254, 280, 297, 328, 338, 364, 372,
     386, 402, 421, 434, 439, 445, 480, 496,
 ];
-const _R82XX_VGA_GAIN_STEPS: [i32; 16] = [
+const R82XX_VGA_GAIN_STEPS: [i32; 16] = [
     0, 26, 26, 30, 42, 35, 24, 13, 14, 32, 36, 34, 35, 37, 35, 36,
 ];
 
@@ -281,22 +317,39 @@ pub struct R820T {
     regs: [u8; NUM_CACHE_REGS],
     pub freq: u32,
     int_freq: u32,
+    // IF filter bandwidth last programmed by set_bandwidth
+    bw: u32,
     xtal_cap_sel: XtalCapValue,
     xtal: u32,
     use_predetect: bool,
     has_lock: bool,
     fil_cal_code: u8,
     init_done: bool,
+    // LNA and mixer AGC stay on under manual gain unless disabled with set_agc
+    agc: bool,
+    // Replaces the built-in LNA/mixer stepping when set
+    gain_table: Option<GainTable>,
+    // VGA index chosen with set_if_gain, overriding the fixed VGA gains of set_gain
+    vga: Option<u8>,
 }
 
 pub const TUNER_ID: &str = "r820t";
 
+const FILT_HP_BW1: i32 = 350_000;
+const FILT_HP_BW2: i32 = 380_000;
+const R82XX_IF_LOW_PASS_BW_TABLE: [i32; 10] = [
+    1_700_000, 1_600_000, 1_550_000, 1_450_000, 1_200_000, 900_000, 700_000, 550_000, 450_000,
+    350_000,
+];
+
 pub const TUNER_INFO: TunerInfo = TunerInfo {
     id: TUNER_ID,
     name: "Rafael Micro R820T",
     i2c_addr: 0x34,
     check_addr: 0x00,
     check_val: 0x69,
+    min_freq: 24_000_000,
+    max_freq: 1_766_000_000,
     // gains: vec![
     //     0, 9, 14, 27, 37, 77, 87, 125, 144, 157, 166, 197, 207, 229, 254, 280, 297, 328, 338, 364,
     //     372, 386, 402, 421, 434, 439, 445, 480, 496,
@@ -305,19 +358,27 @@ pub const TUNER_INFO: TunerInfo = TunerInfo {
 
 impl R820T {
     pub fn new(_handle: &mut Device) -> R820T {
-        let tuner = R820T {
+        R820T::with_init_regs()
+    }
+
+    // State before anything is written to the chip
+    fn with_init_regs() -> R820T {
+        R820T {
             info: TUNER_INFO,
             regs: REG_INIT,
             freq: 0,
             int_freq: 0,
+            bw: 0,
             xtal_cap_sel: XtalCapValue::XtalLowCap30p,
             xtal: 0,
             has_lock: false,
             init_done: false,
             use_predetect: false,
             fil_cal_code: 0,
-        };
-        tuner
+            agc: true,
+            gain_table: None,
+            vga: None,
+        }
     }
 }
 
@@ -331,7 +392,7 @@ impl Tuner for R820T {
         self.xtal_cap_sel = XtalCapValue::XtalHighCap0p;
 
         // Initialize registers
-        self.write_regs(handle, 0x05, &REG_INIT)?;
+        self.write_sequence(handle, &sequence::sequence(Sequence::R820tInit))?;
 
         self.set_tv_standard(handle, 3, TunerType::TunerDigitalTv)?;
         self.sysfreq_sel(
@@ -349,7 +410,10 @@ impl Tuner for R820T {
     }
 
     fn get_gains(&self) -> Result<Vec<i32>> {
-        Ok(GAINS.to_vec())
+        match &self.gain_table {
+            Some(table) => Ok(table.gains()),
+            None => Ok(GAINS.to_vec()),
+        }
     }
 
     fn read_gain(&self, handle: &Device) -> Result<i32> {
@@ -360,66 +424,35 @@ impl Tuner for R820T {
     }
 
     fn set_gain(&mut self, handle: &Device, mode: TunerGain) -> Result<()> {
-        match mode {
-            TunerGain::Auto => {
-                // LNA
-                self.write_reg_mask(handle, 0x05, 0, 0x10)?;
-                // Mixer
-                self.write_reg_mask(handle, 0x07, 0x10, 0x10)?;
-                // Set fixed VGA gain for now (26.5 dB)
-                self.write_reg_mask(handle, 0x0c, 0x0b, 0x9f)?;
-            }
-            TunerGain::Manual(gain) => {
-                let mut data: [u8; 4] = [0; 4];
-                // LNA auto off
-                self.write_reg_mask(handle, 0x05, 0x10, 0x10)?;
-                // Mixer auto off
-                self.write_reg_mask(handle, 0x07, 0, 0x10)?;
-
-                self.read_reg(handle, 0x00, &mut data, 4)?;
-
-                // Set fixed VGA gain for now (16.3 dB)
-                self.write_reg_mask(handle, 0x0c, 0x08, 0x9f)?; //init val 0x08 0x0c works well at 1.7
-
-                let mut total_gain: i32 = 0;
-                let mut mix_index: u8 = 0;
-                let mut lna_index: u8 = 0;
-                for _ in 0..15 {
-                    if total_gain >= gain {
-                        break;
-                    }
-                    lna_index += 1;
-                    total_gain += R82XX_LNA_GAIN_STEPS[lna_index as usize];
-
-                    if total_gain >= gain {
-                        break;
-                    }
-
-                    mix_index += 1;
-                    total_gain += R82XX_MIXER_GAIN_STEPS[mix_index as usize];
-                }
-                // Set LNA gain
-                self.write_reg_mask(handle, 0x05, lna_index, 0x0f)?;
+        self.apply_gain(handle, mode)
+    }
 
-                // Set mixer gain
-                self.write_reg_mask(handle, 0x07, mix_index, 0x0f)?;
+    fn set_agc(&mut self, handle: &Device, on: bool) -> Result<()> {
+        // LNA auto (bit 4 clear) and mixer auto (bit 4 set); the VGA keeps its fixed gain
+        self.write_reg_mask(handle, 0x05, if on { 0 } else { 0x10 }, 0x10)?;
+        self.write_reg_mask(handle, 0x07, if on { 0x10 } else { 0 }, 0x10)?;
+        self.agc = on;
+        Ok(())
+    }
 
-                // LNA
-                self.write_reg_mask(handle, 0x05, 0, 0x10)?;
+    fn set_gain_table(&mut self, table: Option<GainTable>) -> Result<()> {
+        self.gain_table = table;
+        Ok(())
+    }
 
-                // Mixer
-                self.write_reg_mask(handle, 0x07, 0x10, 0x10)?;
+    fn set_stage_gain(&mut self, handle: &Device, stage: GainStage, index: u8) -> Result<()> {
+        self.apply_stage_gain(handle, stage, index)
+    }
 
-                // Set fixed VGA gain for now (26.5dB)
-                self.write_reg_mask(handle, 0x0c, 0x0b, 0x9f)?;
-            }
-        }
-        Ok(())
+    fn set_if_gain(&mut self, handle: &Device, stage: u8, gain: i32) -> Result<()> {
+        self.apply_if_gain(handle, stage, gain)
     }
 
     fn set_freq(&mut self, handle: &Device, freq: u32) -> Result<()> {
         info!("set_freq - freq: {}", freq);
-        let lo_freq = freq + self.int_freq;
+        let lo_freq = freq
+            .checked_add(self.int_freq)
+            .ok_or_else(|| RtlsdrErr(format!("[R82xx] Frequency {} Hz is out of range", freq)))?;
         info!("set_freq - lo_freq: {}", lo_freq);
         self.set_mux(handle, lo_freq)?;
         self.set_pll(handle, lo_freq)?;
@@ -430,24 +463,21 @@ impl Tuner for R820T {
 
     fn set_bandwidth(&mut self, handle: &Device, bw_in: u32, _rate: u32) -> Result<()> {
         let mut bw: i32 = bw_in as i32;
-        const FILT_HP_BW1: i32 = 350_000;
-        const FILT_HP_BW2: i32 = 380_000;
-        const R82XX_IF_LOW_PASS_BW_TABLE: [i32; 10] = [
-            1_700_000, 1_600_000, 1_550_000, 1_450_000, 1_200_000, 900_000, 700_000, 550_000,
-            450_000, 350_000,
-        ];
 
         let (reg_0a, reg_0b): (u8, u8) = if bw > 7_000_000 {
             // BW: 8MHz
             self.int_freq = 4_570_000;
+            self.bw = 8_000_000;
             (0x10, 0x0b)
         } else if bw > 6_000_000 {
             // BW: 7MHz
             self.int_freq = 4_570_000;
+            self.bw = 7_000_000;
             (0x10, 0x2a)
         } else if bw > R82XX_IF_LOW_PASS_BW_TABLE[0] + FILT_HP_BW1 + FILT_HP_BW2 {
             // BW: 6MHz
             self.int_freq = 3_570_000;
+            self.bw = 6_000_000;
             (0x10, 0x6b)
         } else {
             self.int_freq = 2_300_000;
@@ -483,6 +513,7 @@ impl Tuner for R820T {
             real_bw += R82XX_IF_LOW_PASS_BW_TABLE[lp_idx];
 
             self.int_freq -= (real_bw / 2) as u32;
+            self.bw = real_bw as u32;
             (reg_0a, reg_0b)
         };
 
@@ -491,10 +522,39 @@ impl Tuner for R820T {
         Ok(())
     }
 
+    fn get_bandwidth(&self) -> Result<u32> {
+        Ok(self.bw)
+    }
+
+    fn get_bandwidths(&self) -> Result<Vec<u32>> {
+        // Each low-pass setting alone, plus one or both high-pass filters opened up,
+        // then the fixed DVB-T channel filters
+        let mut bws: Vec<u32> = R82XX_IF_LOW_PASS_BW_TABLE
+            .iter()
+            .flat_map(|lp| [*lp, lp + FILT_HP_BW1, lp + FILT_HP_BW1 + FILT_HP_BW2])
+            .map(|bw| bw as u32)
+            .chain([6_000_000, 7_000_000, 8_000_000])
+            .collect();
+        bws.sort_unstable();
+        bws.dedup();
+        Ok(bws)
+    }
+
+    fn auto_bandwidth(&self, rate: u32) -> u32 {
+        // set_bandwidth picks the narrowest filter at least this wide, so the whole
+        // sampled band passes while the filter still rejects what would alias
+        rate
+    }
+
     fn get_if_freq(&self) -> Result<u32> {
         Ok(self.int_freq)
     }
 
+    fn supports_zero_if(&self) -> bool {
+        // Single IF output; the demod does the final downconversion
+        false
+    }
+
     fn get_xtal_freq(&self) -> Result<u32> {
         Ok(self.xtal)
     }
@@ -509,22 +569,135 @@ impl Tuner for R820T {
         if !self.init_done {
             return Ok(());
         }
-        self.write_regs(handle, 0x06, &[0xb1])?;
-        self.write_regs(handle, 0x05, &[0xa0])?;
-        self.write_regs(handle, 0x07, &[0x3a])?;
-        self.write_regs(handle, 0x08, &[0x40])?;
-        self.write_regs(handle, 0x09, &[0xc0])?;
-        self.write_regs(handle, 0x0a, &[0x36])?;
-        self.write_regs(handle, 0x0c, &[0x35])?;
-        self.write_regs(handle, 0x0f, &[0x68])?;
-        self.write_regs(handle, 0x11, &[0x03])?;
-        self.write_regs(handle, 0x17, &[0xf4])?;
-        self.write_regs(handle, 0x19, &[0x0c])?;
-        Ok(())
+        self.write_sequence(handle, &sequence::sequence(Sequence::R820tStandby))
     }
 }
 
 impl R820T {
+    // Gain
+    fn apply_gain(&mut self, handle: &impl I2cBus, mode: TunerGain) -> Result<()> {
+        match mode {
+            TunerGain::Auto => {
+                // LNA
+                self.write_reg_mask(handle, 0x05, 0, 0x10)?;
+                // Mixer
+                self.write_reg_mask(handle, 0x07, 0x10, 0x10)?;
+                // Set fixed VGA gain for now (26.5 dB)
+                self.write_reg_mask(handle, 0x0c, self.vga.unwrap_or(0x0b), 0x9f)?;
+            }
+            TunerGain::Manual(gain) => {
+                let mut data: [u8; 4] = [0; 4];
+                // LNA auto off
+                self.write_reg_mask(handle, 0x05, 0x10, 0x10)?;
+                // Mixer auto off
+                self.write_reg_mask(handle, 0x07, 0, 0x10)?;
+
+                self.read_reg(handle, 0x00, &mut data, 4)?;
+
+                if let Some(entry) = self.gain_table.as_ref().map(|t| *t.nearest(gain)) {
+                    self.write_reg_mask(handle, 0x05, entry.lna, 0x0f)?;
+                    self.write_reg_mask(handle, 0x07, entry.mixer, 0x0f)?;
+                    self.write_reg_mask(handle, 0x0c, self.vga.unwrap_or(entry.vga), 0x9f)?;
+                    if self.agc {
+                        self.write_reg_mask(handle, 0x05, 0, 0x10)?;
+                        self.write_reg_mask(handle, 0x07, 0x10, 0x10)?;
+                    }
+                    return Ok(());
+                }
+
+                // Set fixed VGA gain for now (16.3 dB)
+                self.write_reg_mask(handle, 0x0c, self.vga.unwrap_or(0x08), 0x9f)?; //init val 0x08 0x0c works well at 1.7
+
+                let mut total_gain: i32 = 0;
+                let mut mix_index: u8 = 0;
+                let mut lna_index: u8 = 0;
+                for _ in 0..15 {
+                    if total_gain >= gain {
+                        break;
+                    }
+                    lna_index += 1;
+                    total_gain += R82XX_LNA_GAIN_STEPS[lna_index as usize];
+
+                    if total_gain >= gain {
+                        break;
+                    }
+
+                    mix_index += 1;
+                    total_gain += R82XX_MIXER_GAIN_STEPS[mix_index as usize];
+                }
+                // Set LNA gain
+                self.write_reg_mask(handle, 0x05, lna_index, 0x0f)?;
+
+                // Set mixer gain
+                self.write_reg_mask(handle, 0x07, mix_index, 0x0f)?;
+
+                if self.agc {
+                    // LNA
+                    self.write_reg_mask(handle, 0x05, 0, 0x10)?;
+
+                    // Mixer
+                    self.write_reg_mask(handle, 0x07, 0x10, 0x10)?;
+                }
+
+                // Set fixed VGA gain for now (26.5dB), unless overridden
+                self.write_reg_mask(handle, 0x0c, self.vga.unwrap_or(0x0b), 0x9f)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_stage_gain(
+        &mut self,
+        handle: &impl I2cBus,
+        stage: GainStage,
+        index: u8,
+    ) -> Result<()> {
+        if index > 15 {
+            return Err(RtlsdrErr(format!(
+                "{:?} gain index {} is out of range 0-15",
+                stage, index
+            )));
+        }
+        match stage {
+            GainStage::Lna => {
+                // LNA auto off
+                self.write_reg_mask(handle, 0x05, 0x10, 0x10)?;
+                self.write_reg_mask(handle, 0x05, index, 0x0f)?;
+            }
+            GainStage::Mixer => {
+                // Mixer auto off
+                self.write_reg_mask(handle, 0x07, 0, 0x10)?;
+                self.write_reg_mask(handle, 0x07, index, 0x0f)?;
+            }
+            GainStage::Vga => {
+                self.write_reg_mask(handle, 0x0c, index, 0x9f)?;
+                self.vga = Some(index);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_if_gain(&mut self, handle: &impl I2cBus, stage: u8, gain: i32) -> Result<()> {
+        // The VGA is the only IF stage
+        if stage != 1 {
+            return Err(RtlsdrErr(format!(
+                "R820T has one IF gain stage (1), not {}",
+                stage
+            )));
+        }
+        let mut level = VGA_BASE_GAIN;
+        let mut best = (0, (gain - level).abs());
+        for (index, step) in R82XX_VGA_GAIN_STEPS.iter().enumerate().skip(1) {
+            level += step;
+            if (gain - level).abs() < best.1 {
+                best = (index as u8, (gain - level).abs());
+            }
+        }
+        self.write_reg_mask(handle, 0x0c, best.0, 0x9f)?;
+        self.vga = Some(best.0);
+        Ok(())
+    }
+
     // Tuning logic
 
     fn set_mux(&mut self, handle: &Device, freq: u32) -> Result<()> {
@@ -561,14 +734,12 @@ impl R820T {
             XtalCapValue::XtalLowCap0p => range.xtal_cap0p | 0x08,
         };
         self.write_reg_mask(handle, 0x10, val, 0x0b)?;
-        self.write_reg_mask(handle, 0x08, 0x00, 0x3f)?;
-        self.write_reg_mask(handle, 0x09, 0x00, 0x3f)?;
-        Ok(())
+        self.write_sequence(handle, &sequence::sequence(Sequence::R820tTune))
     }
 
     fn set_pll(&mut self, handle: &Device, freq: u32) -> Result<()> {
         // Frequency in kHz
-        let freq_khz = (freq + 500) / 1000;
+        let freq_khz = ((freq as u64 + 500) / 1000) as u32;
         info!("freq (kHz): {}", freq_khz);
         let pll_ref = self.xtal;
         let pll_ref_khz = (self.xtal + 500) / 1000;
@@ -854,8 +1025,7 @@ impl R820T {
         let flt_ext_widest = 0x00; /* r15[7]: flt_ext_wide off */
         let polyfil_cur = 0x60; /* r25[6:5]:min */
 
-        // Initialize register cache
-        self.regs.copy_from_slice(&REG_INIT[0..NUM_CACHE_REGS]);
+        // The register cache already holds the init sequence written just before
 
         // Init Flag & Xtal_check Result (inits VGA gain, needed?)
         self.write_reg_mask(handle, 0x0c, 0x00, 0x0f)?;
@@ -966,8 +1136,59 @@ impl R820T {
         )))
     }
 
+    /// Write a register sequence, sending runs of whole tuner registers in as few I2C
+    /// messages as `write_regs` allows. Demod and block writes go to the device.
+    fn write_sequence(&mut self, handle: &Device, writes: &[RegWrite]) -> Result<()> {
+        let mut i = 0;
+        while i < writes.len() {
+            let write = writes[i];
+            if write.space != RegSpace::Tuner {
+                handle.write_sequence(&writes[i..i + 1])?;
+                i += 1;
+                continue;
+            }
+            let reg = write.addr as usize;
+            if !(RW_REG_START..NUM_REGS).contains(&reg) {
+                return Err(RtlsdrErr(format!(
+                    "R820T register {:#04x} isn't writable",
+                    write.addr
+                )));
+            }
+            if write.mask != FULL_MASK {
+                self.write_reg_mask(handle, reg, write.val as u8, write.mask as u8)?;
+                i += 1;
+            } else {
+                // Extend over the following registers while nothing has to wait
+                let mut end = i + 1;
+                while end < writes.len()
+                    && writes[end - 1].delay.is_zero()
+                    && writes[end].space == RegSpace::Tuner
+                    && writes[end].mask == FULL_MASK
+                    && writes[end].addr as usize == reg + (end - i)
+                    && (writes[end].addr as usize) < NUM_REGS
+                {
+                    end += 1;
+                }
+                let vals: Vec<u8> = writes[i..end].iter().map(|w| w.val as u8).collect();
+                self.write_regs(handle, reg, &vals)?;
+                i = end;
+            }
+            let delay = writes[i - 1].delay;
+            if !delay.is_zero() {
+                crate::platform::sleep(delay);
+            }
+        }
+        Ok(())
+    }
+
     /// Write register with bit-masked data
-    fn write_reg_mask(&mut self, handle: &Device, reg: usize, val: u8, bit_mask: u8) -> Result<()> {
+    fn write_reg_mask(
+        &mut self,
+        handle: &impl I2cBus,
+        reg: usize,
+        val: u8,
+        bit_mask: u8,
+    ) -> Result<()> {
         let rc = self.read_cache_reg(reg);
         // Compute the desired register value: (rc & !mask) gets the unmasked bits and leaves the masked as 0,
         // and (val & mask) gets just the masked bits we want to set. Or together to get the desired register.
@@ -987,7 +1208,7 @@ impl R820T {
     }
 
     /// Write data to device registers (r82xx_write)
-    fn write_regs(&mut self, handle: &Device, reg: usize, val: &[u8]) -> Result<()> {
+    fn write_regs(&mut self, handle: &impl I2cBus, reg: usize, val: &[u8]) -> Result<()> {
         // Store write in local cache
         self.reg_cache_store(reg, val);
 
@@ -1017,7 +1238,7 @@ impl R820T {
     }
 
     // (r82xx_read)
-    fn read_reg(&self, handle: &Device, reg: usize, buf: &mut [u8], len: u8) -> Result<()> {
+    fn read_reg(&self, handle: &impl I2cBus, reg: usize, buf: &mut [u8], len: u8) -> Result<()> {
         assert!(buf.len() >= len as usize);
         handle.i2c_write(R820T_I2C_ADDR, &[reg as u8])?;
         handle.i2c_read(R820T_I2C_ADDR, buf, len)?;
@@ -1038,9 +1259,78 @@ impl R820T {
     }
 }
 
+// The I2C access the register helpers need, so they can run against a plain register
+// file in tests
+trait I2cBus {
+    fn i2c_write(&self, i2c_addr: u16, buffer: &[u8]) -> Result<usize>;
+    fn i2c_read(&self, i2c_addr: u16, buffer: &mut [u8], len: u8) -> Result<usize>;
+}
+
+impl I2cBus for Device {
+    fn i2c_write(&self, i2c_addr: u16, buffer: &[u8]) -> Result<usize> {
+        Device::i2c_write(self, i2c_addr, buffer)
+    }
+
+    fn i2c_read(&self, i2c_addr: u16, buffer: &mut [u8], len: u8) -> Result<usize> {
+        Device::i2c_read(self, i2c_addr, buffer, len)
+    }
+}
+
 fn bit_reverse(byte: u8) -> u8 {
     const LUT: [u8; 16] = [
         0x0, 0x8, 0x4, 0xc, 0x2, 0xa, 0x6, 0xe, 0x1, 0x9, 0x5, 0xd, 0x3, 0xb, 0x7, 0xf,
     ];
     (LUT[(byte & 0xf) as usize] << 4) | LUT[(byte >> 4) as usize]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    // Register file standing in for the chip; reads return zeros
+    struct RegFile(RefCell<[u8; NUM_REGS]>);
+
+    impl I2cBus for RegFile {
+        fn i2c_write(&self, _i2c_addr: u16, buffer: &[u8]) -> Result<usize> {
+            let reg = buffer[0] as usize;
+            self.0.borrow_mut()[reg..reg + buffer.len() - 1].copy_from_slice(&buffer[1..]);
+            Ok(buffer.len())
+        }
+
+        fn i2c_read(&self, _i2c_addr: u16, buffer: &mut [u8], len: u8) -> Result<usize> {
+            buffer[..len as usize].fill(0);
+            Ok(len as usize)
+        }
+    }
+
+    fn vga_reg(bus: &RegFile) -> u8 {
+        bus.0.borrow()[0x0c] & 0x9f
+    }
+
+    #[test]
+    fn vga_override_survives_manual_gain() {
+        let bus = RegFile(RefCell::new([0; NUM_REGS]));
+        let mut tuner = R820T::with_init_regs();
+        tuner.apply_stage_gain(&bus, GainStage::Vga, 0x04).unwrap();
+        assert_eq!(vga_reg(&bus), 0x04);
+
+        tuner.apply_gain(&bus, TunerGain::Manual(200)).unwrap();
+        assert_eq!(vga_reg(&bus), 0x04);
+        tuner.apply_gain(&bus, TunerGain::Auto).unwrap();
+        assert_eq!(vga_reg(&bus), 0x04);
+
+        tuner.apply_if_gain(&bus, 1, 100).unwrap();
+        let index = tuner.vga.unwrap();
+        tuner.apply_gain(&bus, TunerGain::Manual(300)).unwrap();
+        assert_eq!(vga_reg(&bus), index);
+    }
+
+    #[test]
+    fn manual_gain_without_override_uses_fixed_vga() {
+        let bus = RegFile(RefCell::new([0; NUM_REGS]));
+        let mut tuner = R820T::with_init_regs();
+        tuner.apply_gain(&bus, TunerGain::Manual(200)).unwrap();
+        assert_eq!(vga_reg(&bus), 0x0b);
+    }
+}